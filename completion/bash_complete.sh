@@ -16,33 +16,83 @@ _theca() {
 	COMPREPLY=()
 	cmd="${COMP_WORDS[1]}"
 	cur="${COMP_WORDS[COMP_CWORD]}"
-	commands="add edit del clear transfer import search info new-profile encrypt-profile decrypt-profile list-profiles --help --version"
-	global_opts="--profile --profile-folder --encrypted --key"
+	commands="add edit del history diff revert clear trash restore undo redo log config q tui shell transfer import export import-dir search info tags agenda new-profile encrypt-profile decrypt-profile list-profiles --help --version"
+	global_opts="--profile --profile-folder --encrypted --key --backup --no-backup --lock-timeout --trash-days --color"
 
 	case "${cmd}" in
 		add)
 			COMPREPLY=( $(compgen -W \
-        		"${global_opts} --started --urgent --body --editor - --yes" -- $cur) )
+        		"${global_opts} --started --urgent --body --editor - --yes --tag --due" -- $cur) )
         	return 0
 			;;
 		edit)
 			COMPREPLY=( $(compgen -W \
-        		"${global_opts} --started --urgent --none --body --editor - --yes" -- $cur) )
+        		"${global_opts} --started --urgent --none --body --editor - --yes --tag --untag --due --no-due" -- $cur) )
         	return 0
 			;;
 		search)
 			COMPREPLY=( $(compgen -W \
-        		"${global_opts} --search-body --regex --limit --reverse --datesort --json --condensed" -- $cur) )
+        		"${global_opts} --search-body --regex --ignore-case --fuzzy --full-text --query --all-profiles --skip-encrypted --limit --reverse --no-reverse --datesort --no-datesort --json --format --template --condensed --no-condensed --tag" -- $cur) )
         	return 0
 			;;
-		del|clear|transfer|import|new-profile)
+		import)
+			COMPREPLY=( $(compgen -W \
+        		"${global_opts} --yes --format --columns --dry-run" -- $cur) )
+        	return 0
+			;;
+		del|restore|revert|transfer|new-profile)
 			COMPREPLY=( $(compgen -W \
         		"${global_opts} --yes" -- $cur) )
         	return 0
 			;;
+		q)
+			COMPREPLY=( $(compgen -W \
+        		"${global_opts} --json --condensed --no-condensed --datesort --no-datesort --reverse --no-reverse --limit" -- $cur) )
+        	return 0
+			;;
+		config)
+			COMPREPLY=( $(compgen -W \
+        		"${global_opts} --json --condensed --no-condensed --datesort --no-datesort --reverse" -- $cur) )
+        	return 0
+			;;
+		export)
+			COMPREPLY=( $(compgen -W \
+        		"${global_opts} --format" -- $cur) )
+        	return 0
+			;;
+		import-dir)
+			COMPREPLY=( $(compgen -W \
+        		"${global_opts}" -- $cur) )
+        	return 0
+			;;
+		shell)
+			COMPREPLY=( $(compgen -W \
+        		"${global_opts}" -- $cur) )
+        	return 0
+			;;
+		tui)
+			COMPREPLY=( $(compgen -W \
+        		"${global_opts} --yes --datesort --no-datesort --reverse" -- $cur) )
+        	return 0
+			;;
+		history|log)
+			COMPREPLY=( $(compgen -W \
+        		"${global_opts} --json --limit" -- $cur) )
+        	return 0
+			;;
+		clear)
+			COMPREPLY=( $(compgen -W \
+        		"${global_opts} --yes --purge" -- $cur) )
+        	return 0
+			;;
+		trash)
+			COMPREPLY=( $(compgen -W \
+        		"${global_opts} --empty --yes --limit --reverse --no-reverse --datesort --no-datesort --json --format --template --condensed --no-condensed" -- $cur) )
+        	return 0
+			;;
 		encrypt-profile)
 			COMPREPLY=( $(compgen -W \
-				"${global_opts} --new-key"))
+				"${global_opts} --new-key --kdf-cost"))
 			return 0
 			;;
 		list-profiles)
@@ -56,6 +106,11 @@ _theca() {
 			return 0
 			;;
 		info)
+			COMPREPLY=( $(compgen -W \
+        		"${global_opts} --json --format --template" -- $cur) )
+        	return 0
+			;;
+		tags|agenda|diff|undo|redo)
 			COMPREPLY=( $(compgen -W \
         		"${global_opts}" -- $cur) )
         	return 0
@@ -67,13 +122,13 @@ _theca() {
 
 	if [[ "${cmd}" =~ "^[0-9]+$" ]]; then
 		COMPREPLY=( $(compgen -W \
-    		"${global_opts} --json --condensed" -- $cur) )
+    		"${global_opts} --json --template --condensed --no-condensed" -- $cur) )
     	return 0
 	fi
 
 	if [ ${COMP_CWORD} -eq 1 ]; then
         COMPREPLY=( $(compgen -W \
-        	"${commands} --help --version --limit --reverse --datesort --json --condensed" -- $cur) )
+        	"${commands} --help --version --limit --reverse --no-reverse --datesort --no-datesort --json --format --template --condensed --no-condensed --tag --due-before --due-after --query" -- $cur) )
         return 0
     fi
 } &&
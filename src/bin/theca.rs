@@ -17,8 +17,13 @@ extern crate theca;
 extern crate docopt;
 
 use docopt::Docopt;
-use theca::{Args, Profile, setup_args, parse_cmds, version};
+use theca::{Args, Profile, setup_args, parse_cmds, print_config, resolve_settings,
+            search_all_profiles, version};
+use theca::alias::{self, Aliases};
 use theca::errors::Result;
+use theca::lock::ProfileLock;
+use theca::{shell, tui};
+use std::env;
 use std::process::exit;
 
 static USAGE: &'static str = "
@@ -26,19 +31,37 @@ theca - simple cli note taking tool
 
 Usage:
     theca [options] new-profile [<name>]
-    theca [options] encrypt-profile [--new-key KEY]
+    theca [options] encrypt-profile [--new-key KEY] [--kdf-cost COST]
     theca [options] decrypt-profile
     theca [options] list-profiles
     theca [options] info
-    theca [options] clear
-    theca [options] [-s|-u|-n]
+    theca [options] config
+    theca [options] q [<name>]
+    theca [options] tui
+    theca [options] shell
+    theca [options] clear [--purge]
+    theca [options] tags
+    theca [options] agenda
+    theca [options] trash [--empty]
+    theca [options] undo
+    theca [options] redo
+    theca [options] log
+    theca [options] restore <id>
+    theca [options] export [--format FORMAT] [<path>]
+    theca [options] import-dir <dir>
+    theca [options] [-s|-u|-n] [--tag TAG]... [--due-before DATE] [--due-after DATE]
+    theca [options] search [--regex, --full-text, --search-body] [--all-profiles [--skip-encrypted]] [-s|-u|-n] [--tag TAG]... [<pattern>]
     theca [options] <id>
-    theca [options] search [--regex, --search-body] [-s|-u|-n] <pattern>
     theca [options] transfer <id> to <name>
     theca [options] import <id> from <name>
-    theca [options] add <title> [-s|-u] [-b BODY|-t|-]
-    theca [options] edit <id> [<title>] [-s|-u|-n] [-b BODY|-t|-]
+    theca [options] import --format FORMAT [--columns MAP] [--dry-run] <file>
+    theca [options] add <title> [-s|-u] [-b BODY|-t|-] [--tag TAG]... [--due DATE]
+    theca [options] edit <id> [<title>] [-s|-u|-n] [-b BODY|-t|-] [--tag TAG]...
+                    [--untag TAG]... [--due DATE|--no-due]
     theca [options] del <id>...
+    theca [options] history <id>
+    theca [options] diff <id> [<rev>] [<rev>]
+    theca [options] revert <id> <rev>
 
 Profiles:
     -f PATH, --profile-folder PATH      Path to folder containing profile.json
@@ -47,16 +70,54 @@ Profiles:
     -p PROFILE, --profile PROFILE       Specify non-default profile [default
                                         can be set with env var
                                         THECA_DEFAULT_PROFILE].
+    --lock-timeout SECONDS              Wait up to SECONDS for another theca
+                                        process using the profile to finish
+                                        [default: 10].
+    --backup                            Keep the last saved version of the
+                                        profile as PROFILE.json.bak [default
+                                        can be set with env var THECA_BACKUP].
+    --no-backup                         Don't keep a backup even if the config
+                                        file or THECA_BACKUP asks for one.
+
+Trash:
+    --empty                             Permanently delete the notes in the
+                                        trash when used with `trash`.
+    --purge                             Permanently delete the notes instead
+                                        of moving them to the trash when used
+                                        with `clear`.
+    --trash-days DAYS                   Permanently delete notes that have
+                                        been in the trash for more than DAYS
+                                        days when the profile is saved, 0
+                                        keeps them forever (30 by default,
+                                        can be set with env var
+                                        THECA_TRASH_DAYS).
 
 Printing format:
     -c, --condensed                     Use the condensed printing format.
+    --no-condensed                      Use the expanded printing format even
+                                        if the config file sets condensed.
     -j, --json                          Print list output as a JSON object.
+    --template TEMPLATE                 Print each note (or the profile info)
+                                        by filling in TEMPLATE, like
+                                        '{id}: {title} ({status})', or the
+                                        template of that name from the
+                                        config file.
+    --color WHEN                        Use bold and colored output always,
+                                        never or only when printing to a
+                                        terminal (auto) [default can be set
+                                        with env var THECA_COLOR].
 
 Note list formatting:
     -l LIMIT, --limit LIMIT             Limit output to LIMIT notes
                                         [default: 0].
-    -d, --datesort                      Sort notes by date.
+    -d, --datesort                      Sort notes by date, except for the
+                                        ranked results of fuzzy and
+                                        full-text searches.
+    --no-datesort                       Don't sort notes by date even if the
+                                        config file sets datesort.
     -r, --reverse                       Reverse list.
+    --no-reverse                        Don't reverse the list even if the
+                                        config file sets reverse.
 
 Input:
     -y, --yes                           Silently agree to any [y/n] prompts.
@@ -66,6 +127,23 @@ Statuses:
     -s, --started                       Started status.
     -u, --urgent                        Urgent status.
 
+Tags:
+    -g TAG, --tag TAG                   Add TAG to a note when used with `add`
+                                        or `edit`, otherwise only show notes
+                                        tagged with TAG.
+    --untag TAG                         Remove TAG from a note when used with
+                                        `edit`.
+
+Due dates:
+    --due DATE                          Set the due date of a note. DATE can
+                                        be a date (2015-01-22), an offset
+                                        (+3d, +2w), today, tomorrow or a
+                                        weekday (friday, next friday).
+    --no-due                            Remove the due date of a note when
+                                        used with `edit`.
+    --due-before DATE                   Only show notes due before DATE.
+    --due-after DATE                    Only show notes due after DATE.
+
 Body:
     -b BODY, --body BODY                Set body of the note to BODY.
     -t, --editor                        Drop to $EDITOR to set/edit note body.
@@ -80,6 +158,11 @@ Encryption:
                                         profile when using `encrypt-profile`,
                                         a prompt will be displayed if no key
                                         is provided.
+    --kdf-cost COST                     Work factor (log2 of N) of the scrypt
+                                        key derivation when using
+                                        `encrypt-profile`, between 10 and 20,
+                                        the current cost is kept (or 15 used)
+                                        if no cost is provided.
 
 Search:
     --search-body                       Search the note bodies instead of
@@ -87,6 +170,48 @@ Search:
 
     --regex                             Set search pattern to regex (default
                                         is keyword).
+    -i, --ignore-case                   Ignore case when searching by keyword
+                                        or regex.
+    --fuzzy                             Find the notes with every word of the
+                                        pattern in them even if misspelled or
+                                        with letters left out, best matches
+                                        first whether or not --datesort is
+                                        used.
+
+    --full-text                         Search the words of the note titles
+                                        and bodies together with a full-text
+                                        index, best matches first whether or
+                                        not --datesort is used. Quote words
+                                        to find them in a row and end a word
+                                        with * to match its prefix.
+    --query QUERY                       Only list or search the notes that
+                                        match QUERY, like 'status:urgent
+                                        touched:>7d body:postgres
+                                        title:/^deploy/ -tag:done'.
+
+    --all-profiles                      Search every profile in the profile
+                                        folder, asking for the key of each
+                                        encrypted one, and print what was
+                                        found under the name of its profile.
+    --skip-encrypted                    Leave out the encrypted profiles when
+                                        searching with --all-profiles.
+
+Import and export:
+    --format FORMAT                     Format to export the notes to with
+                                        `export` or import them from with
+                                        `import`. markdown (the default for
+                                        `export`) writes a file per note to
+                                        the folder PATH, todotxt, org, csv
+                                        and tsv write a single file to PATH
+                                        (or print it). csv or tsv print a
+                                        list, search or the trash as a table.
+    --columns MAP                       Read the CSV or TSV columns named in
+                                        MAP, a list of COLUMN=FIELD pairs
+                                        like Task=title,Notes=body, into
+                                        those fields when importing.
+    --dry-run                           Print what importing a CSV or TSV
+                                        file would change without changing
+                                        the profile.
 
 Miscellaneous:
     -h, --help                          Display this help and exit.
@@ -94,20 +219,57 @@ Miscellaneous:
 ";
 
 fn theca_main() -> Result<()> {
+    // expand any aliases and saved queries before parsing the arguments
+    let argv: Vec<String> = env::args().collect();
+    let aliases = try!(Aliases::load(&try!(alias::profile_folder(&argv))));
     let mut args: Args = try!(Docopt::new(USAGE)
                                   .unwrap()
+                                  .argv(try!(aliases.expand(&argv)).into_iter())
                                   .version(Some(version()))
                                   .decode());
+
+    // these don't need a profile
+    if args.cmd_config {
+        return print_config(&args);
+    }
+    if args.cmd_q {
+        return aliases.list_queries(args.flag_json);
+    }
+
+    // searching every profile opens each of them itself
+    if args.cmd_search && args.flag_all_profiles {
+        try!(resolve_settings(&mut args));
+        return search_all_profiles(&args);
+    }
+
     try!(setup_args(&mut args));
 
-    let (mut profile, profile_fingerprint) = try!(Profile::new(&args.flag_profile,
-                                                               &args.flag_profile_folder,
-                                                               &args.flag_key,
-                                                               args.cmd_new_profile,
-                                                               args.flag_encrypted,
-                                                               args.flag_yes));
+    // the tui and the shell take the lock whenever they load or save the profile
+    if args.cmd_tui {
+        return tui::run(&args);
+    }
+    if args.cmd_shell {
+        return shell::run(&args, &aliases, USAGE);
+    }
+
+    // hold the lock on the profile until any changes have been saved
+    let profile_name = if args.cmd_new_profile && !args.arg_name.is_empty() {
+        args.arg_name[0].clone()
+    } else {
+        args.flag_profile.clone()
+    };
+    let _lock = try!(ProfileLock::acquire(&profile_name,
+                                          &args.flag_profile_folder,
+                                          args.flag_lock_timeout));
+
+    let (mut profile, snapshot) = try!(Profile::new(&args.flag_profile,
+                                                    &args.flag_profile_folder,
+                                                    &args.flag_key,
+                                                    args.cmd_new_profile,
+                                                    args.flag_encrypted,
+                                                    args.flag_yes));
 
-    try!(parse_cmds(&mut profile, &mut args, &profile_fingerprint));
+    try!(parse_cmds(&mut profile, &mut args, &snapshot));
 
     Ok(())
 }
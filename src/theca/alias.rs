@@ -0,0 +1,370 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// alias.rs
+//   user defined command aliases and saved queries, read from aliases.toml in
+//   the profile folder and expanded before the arguments are parsed.
+
+// std imports
+use std::collections::BTreeMap;
+use std::env::var;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+// random things
+use rustc_serialize::json::as_pretty_json;
+use toml::Value;
+
+// theca imports
+use config::{Config, color_output, parse_toml};
+use utils::{display_width, find_profile_folder, format_field, pretty_line};
+use errors::{Result, Error};
+
+/// name of the aliases file in the profile folder
+pub static ALIASES_FILE: &'static str = "aliases.toml";
+
+/// how many aliases can refer to each other before giving up
+const MAX_DEPTH: usize = 16;
+
+/// the commands in the usage message, aliases can't hide them
+static COMMANDS: [&'static str; 28] = ["add",
+                                       "agenda",
+                                       "clear",
+                                       "config",
+                                       "decrypt-profile",
+                                       "del",
+                                       "diff",
+                                       "edit",
+                                       "encrypt-profile",
+                                       "export",
+                                       "history",
+                                       "import",
+                                       "import-dir",
+                                       "info",
+                                       "list-profiles",
+                                       "log",
+                                       "new-profile",
+                                       "q",
+                                       "redo",
+                                       "restore",
+                                       "revert",
+                                       "search",
+                                       "shell",
+                                       "tags",
+                                       "transfer",
+                                       "trash",
+                                       "tui",
+                                       "undo"];
+
+/// the options in the usage message that take a value
+static VALUE_OPTIONS: [&'static str; 25] = ["-b",
+                                            "--body",
+                                            "--color",
+                                            "--columns",
+                                            "--due",
+                                            "--due-after",
+                                            "--due-before",
+                                            "-f",
+                                            "--profile-folder",
+                                            "--format",
+                                            "-g",
+                                            "--tag",
+                                            "--template",
+                                            "-k",
+                                            "--key",
+                                            "--kdf-cost",
+                                            "-l",
+                                            "--limit",
+                                            "--lock-timeout",
+                                            "--new-key",
+                                            "-p",
+                                            "--profile",
+                                            "--query",
+                                            "--trash-days",
+                                            "--untag"];
+
+/// A saved query as printed by `theca q`
+#[derive(RustcEncodable, Clone, Debug)]
+pub struct QueryLine {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+/// The aliases and saved queries from an aliases file
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Aliases {
+    pub aliases: BTreeMap<String, Vec<String>>,
+    pub queries: BTreeMap<String, Vec<String>>,
+}
+
+/// split a command line into words like a shell would, with single and double
+/// quotes and backslash escapes
+pub fn split_args(line: &str) -> Result<Vec<String>> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return specific_fail!(format!("unclosed quote in '{}'", line)),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => {
+                            match chars.next() {
+                                Some(c) if c == '"' || c == '\\' => word.push(c),
+                                Some(c) => {
+                                    word.push('\\');
+                                    word.push(c);
+                                }
+                                None => {
+                                    return specific_fail!(format!("unclosed quote in '{}'", line))
+                                }
+                            }
+                        }
+                        Some(c) => word.push(c),
+                        None => return specific_fail!(format!("unclosed quote in '{}'", line)),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(word.clone());
+                    word.clear();
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+// quote a word so split_args would give it back
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.chars().any(|c| c.is_whitespace() || "'\"\\".contains(c)) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace("'", "'\\''"))
+    }
+}
+
+/// position of the command (or first positional argument) in `argv`, which
+/// starts with the program name
+pub fn command_pos(argv: &[String]) -> Option<usize> {
+    let mut i = 1;
+    while i < argv.len() {
+        let arg = &argv[i];
+        if arg == "--" {
+            return None;
+        }
+        if !arg.starts_with('-') {
+            return Some(i);
+        }
+        if VALUE_OPTIONS.contains(&&arg[..]) {
+            i += 1;
+        }
+        i += 1;
+    }
+    None
+}
+
+// the words of an alias or query, either a string or an array of strings
+fn read_args(value: &Value, name: &str) -> Result<Vec<String>> {
+    match *value {
+        Value::String(ref s) => split_args(s),
+        Value::Array(ref a) => {
+            a.iter()
+             .map(|v| match v.as_str() {
+                 Some(s) => Ok(s.to_string()),
+                 None => specific_fail!(format!("{} should be a list of strings", name)),
+             })
+             .collect()
+        }
+        _ => specific_fail!(format!("{} should be a string or a list of strings", name)),
+    }
+}
+
+impl Aliases {
+    /// parse the contents of an aliases file
+    pub fn parse(contents: &str) -> Result<Aliases> {
+        let table = try!(parse_toml(contents));
+
+        let mut aliases = Aliases::default();
+        for (section, value) in table.iter() {
+            if section != "aliases" && section != "queries" {
+                return specific_fail!(format!("unknown section '{}'", section));
+            }
+            let entries = match value.as_table() {
+                Some(t) => t,
+                None => return specific_fail!(format!("{} should be a table", section)),
+            };
+            for (name, value) in entries.iter() {
+                let key = format!("{}.{}", section, name);
+                let args = try!(read_args(value, &key));
+                match &section[..] {
+                    "aliases" => {
+                        if COMMANDS.contains(&&name[..]) {
+                            return specific_fail!(format!("alias '{}' would hide the {} command",
+                                                          name,
+                                                          name));
+                        }
+                        if name.is_empty() || name.starts_with('-') ||
+                           name.chars().all(|c| c.is_digit(10)) {
+                            return specific_fail!(format!("'{}' can't be used as an alias", name));
+                        }
+                        aliases.aliases.insert(name.clone(), args);
+                    }
+                    _ => {
+                        let mut argv = vec![String::new()];
+                        argv.extend(args.iter().cloned());
+                        if let Some(p) = command_pos(&argv) {
+                            if argv[p] != "search" {
+                                return specific_fail!(format!("saved query '{}' should list or \
+                                                               search notes",
+                                                              name));
+                            }
+                        }
+                        aliases.queries.insert(name.clone(), args);
+                    }
+                }
+            }
+        }
+        Ok(aliases)
+    }
+
+    /// read the aliases file from `profile_folder`, there are no aliases if
+    /// it doesn't exist
+    pub fn load(profile_folder: &str) -> Result<Aliases> {
+        let path = try!(aliases_path(profile_folder));
+        if !path.is_file() {
+            return Ok(Aliases::default());
+        }
+        let mut contents = String::new();
+        try!(try!(File::open(&path)).read_to_string(&mut contents));
+        match Aliases::parse(&contents) {
+            Ok(a) => Ok(a),
+            Err(e) => specific_fail!(format!("error in {}: {}", path.display(), e.desc)),
+        }
+    }
+
+    /// replace any alias used as the command in `argv` (and any alias it expands
+    /// to) with its arguments, and `q NAME` with the saved query
+    pub fn expand(&self, argv: &[String]) -> Result<Vec<String>> {
+        let mut argv = argv.to_vec();
+        let mut seen: Vec<String> = vec![];
+        while let Some(pos) = command_pos(&argv) {
+            let name = argv[pos].clone();
+            if name == "q" {
+                // without a name the saved queries are listed
+                if pos + 1 >= argv.len() || argv[pos + 1].starts_with('-') {
+                    break;
+                }
+                let query = match self.queries.get(&argv[pos + 1]) {
+                    Some(q) => q,
+                    None => {
+                        return specific_fail!(format!("there is no saved query called '{}'",
+                                                      argv[pos + 1]))
+                    }
+                };
+                argv.splice(pos..pos + 2, query.iter().cloned());
+                break;
+            }
+            let alias = match self.aliases.get(&name) {
+                Some(a) => a,
+                None => break,
+            };
+            if seen.contains(&name) || seen.len() >= MAX_DEPTH {
+                return specific_fail!(format!("alias '{}' expands to itself", name));
+            }
+            seen.push(name);
+            argv.splice(pos..pos + 1, alias.iter().cloned());
+        }
+        Ok(argv)
+    }
+
+    /// print the saved queries for `theca q`
+    pub fn list_queries(&self, json: bool) -> Result<()> {
+        let lines: Vec<QueryLine> = self.queries
+                                        .iter()
+                                        .map(|(name, args)| {
+                                            QueryLine {
+                                                name: name.clone(),
+                                                args: args.clone(),
+                                            }
+                                        })
+                                        .collect();
+        if json {
+            println!("{}", as_pretty_json(&lines));
+            return Ok(());
+        }
+        if lines.is_empty() {
+            println!("there are no saved queries");
+            return Ok(());
+        }
+        let width = lines.iter().map(|l| display_width(&l.name)).max().unwrap_or(0).max(4);
+        try!(pretty_line(&format!("{}  command\n", format_field("name", width, false)),
+                         "",
+                         color_output()));
+        for l in lines.iter() {
+            let args: Vec<String> = l.args.iter().map(|a| quote_arg(a)).collect();
+            println!("{}  {}", format_field(&l.name, width, false), args.join(" "));
+        }
+        Ok(())
+    }
+}
+
+/// the profile folder aliases are read from, before the arguments have been
+/// parsed: --profile-folder, then $THECA_PROFILE_FOLDER, then the config file
+pub fn profile_folder(argv: &[String]) -> Result<String> {
+    for (i, arg) in argv.iter().enumerate().skip(1) {
+        if arg == "--" {
+            break;
+        }
+        if (arg == "-f" || arg == "--profile-folder") && i + 1 < argv.len() {
+            return Ok(argv[i + 1].clone());
+        }
+        if arg.starts_with("--profile-folder=") {
+            return Ok(arg["--profile-folder=".len()..].to_string());
+        }
+    }
+    match var("THECA_PROFILE_FOLDER") {
+        Ok(ref f) if !f.is_empty() => return Ok(f.clone()),
+        _ => {}
+    }
+    Ok(try!(Config::load()).profile_folder.unwrap_or_else(String::new))
+}
+
+// path of the aliases file in `profile_folder`
+fn aliases_path(profile_folder: &str) -> Result<PathBuf> {
+    Ok(try!(find_profile_folder(profile_folder)).join(ALIASES_FILE))
+}
@@ -0,0 +1,583 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// config.rs
+//   the config file, working out the settings to use from the command line,
+//   the environment and the config file, and the settings used while printing.
+
+// std imports
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::env::{var, home_dir};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+// random things
+use rustc_serialize::json::as_pretty_json;
+use time::{now, strftime};
+use toml::{Parser, Table, Value};
+
+// theca imports
+use Args;
+use profile::{DATEFMT_SHORT, TRASH_DAYS, parse_trash_days};
+use utils::c::istty;
+use utils::{find_profile_folder, pretty_line, STDOUT_FILENO};
+use errors::{Result, Error};
+
+// what printing uses, set from the effective settings once they are known
+thread_local!(static CURRENT: RefCell<Current> = RefCell::new(Current {
+    color: Color::Auto,
+    date_format: DATEFMT_SHORT.to_string(),
+    editor: None,
+}));
+
+struct Current {
+    color: Color,
+    date_format: String,
+    editor: Option<String>,
+}
+
+/// When to use bold and colored output
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    pub fn parse(s: &str) -> Result<Color> {
+        match s {
+            "auto" => Ok(Color::Auto),
+            "always" => Ok(Color::Always),
+            "never" => Ok(Color::Never),
+            _ => {
+                specific_fail!(format!("invalid color setting '{}', it should be auto, always or \
+                                        never",
+                                       s))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{}",
+               match *self {
+                   Color::Auto => "auto",
+                   Color::Always => "always",
+                   Color::Never => "never",
+               })
+    }
+}
+
+/// The settings in one section of the config file, `None` if they aren't set
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Settings {
+    pub datesort: Option<bool>,
+    pub reverse: Option<bool>,
+    pub condensed: Option<bool>,
+    pub editor: Option<String>,
+    pub color: Option<Color>,
+    pub date_format: Option<String>,
+    pub backup: Option<bool>,
+    pub trash_days: Option<i64>,
+}
+
+/// The contents of a config file
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Config {
+    /// where it was read from, `None` if there isn't one
+    pub path: Option<PathBuf>,
+    pub profile: Option<String>,
+    pub profile_folder: Option<String>,
+    pub settings: Settings,
+    /// overrides for single profiles, from the `[profiles.NAME]` sections
+    pub profiles: BTreeMap<String, Settings>,
+    /// output templates `--template` can name, from the `[templates]` section
+    pub templates: BTreeMap<String, String>,
+}
+
+/// parse a TOML file, failing with the position of the first error
+pub fn parse_toml(contents: &str) -> Result<Table> {
+    let mut parser = Parser::new(contents);
+    match parser.parse() {
+        Some(t) => Ok(t),
+        None => {
+            let e = &parser.errors[0];
+            let (line, col) = parser.to_linecol(e.lo);
+            specific_fail!(format!("line {}, column {}: {}", line + 1, col + 1, e.desc))
+        }
+    }
+}
+
+// check that a date format can actually be used
+fn check_date_format(format: &str) -> Result<()> {
+    match strftime(format, &now()) {
+        Ok(_) => Ok(()),
+        Err(_) => specific_fail!(format!("invalid date format '{}'", format)),
+    }
+}
+
+fn expect_str<'a>(value: &'a Value, key: &str) -> Result<&'a str> {
+    match value.as_str() {
+        Some(s) => Ok(s),
+        None => specific_fail!(format!("{} should be a string", key)),
+    }
+}
+
+fn expect_bool(value: &Value, key: &str) -> Result<bool> {
+    match value.as_bool() {
+        Some(b) => Ok(b),
+        None => specific_fail!(format!("{} should be true or false", key)),
+    }
+}
+
+// read the settings that can be set for every profile or a single one, anything
+// else in the table is handed back
+fn read_settings(table: &Table, section: &str) -> Result<(Settings, Vec<(String, Value)>)> {
+    let mut settings = Settings::default();
+    let mut rest = vec![];
+    for (key, value) in table.iter() {
+        let name = if section.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", section, key)
+        };
+        match &key[..] {
+            "datesort" => settings.datesort = Some(try!(expect_bool(value, &name))),
+            "reverse" => settings.reverse = Some(try!(expect_bool(value, &name))),
+            "condensed" => settings.condensed = Some(try!(expect_bool(value, &name))),
+            "backup" => settings.backup = Some(try!(expect_bool(value, &name))),
+            "editor" => settings.editor = Some(try!(expect_str(value, &name)).to_string()),
+            "color" => settings.color = Some(try!(Color::parse(try!(expect_str(value, &name))))),
+            "date_format" => {
+                let format = try!(expect_str(value, &name));
+                try!(check_date_format(format));
+                settings.date_format = Some(format.to_string());
+            }
+            "trash_days" => {
+                settings.trash_days = match value.as_integer() {
+                    Some(d) if d >= 0 => Some(d),
+                    _ => return specific_fail!(format!("{} should be a number of days", name)),
+                }
+            }
+            _ => rest.push((key.clone(), value.clone())),
+        }
+    }
+    Ok((settings, rest))
+}
+
+// expand a leading ~ to the home directory
+fn expand_home(path: &str) -> String {
+    if path == "~" || path.starts_with("~/") {
+        if let Some(home) = home_dir() {
+            return format!("{}{}", home.display(), &path[1..]);
+        }
+    }
+    path.to_string()
+}
+
+impl Config {
+    /// parse the contents of a config file
+    pub fn parse(contents: &str) -> Result<Config> {
+        let table = try!(parse_toml(contents));
+
+        let (settings, rest) = try!(read_settings(&table, ""));
+        let mut config = Config { settings: settings, ..Config::default() };
+        for (key, value) in rest.into_iter() {
+            match &key[..] {
+                "profile" => config.profile = Some(try!(expect_str(&value, &key)).to_string()),
+                "profile_folder" => {
+                    config.profile_folder = Some(expand_home(try!(expect_str(&value, &key))))
+                }
+                "profiles" => {
+                    let profiles = match value.as_table() {
+                        Some(p) => p,
+                        None => return specific_fail_str!("profiles should be a table"),
+                    };
+                    for (name, section) in profiles.iter() {
+                        let section_name = format!("profiles.{}", name);
+                        let table = match section.as_table() {
+                            Some(t) => t,
+                            None => {
+                                return specific_fail!(format!("{} should be a table",
+                                                              section_name))
+                            }
+                        };
+                        let (settings, rest) = try!(read_settings(table, &section_name));
+                        if let Some(&(ref k, _)) = rest.first() {
+                            return specific_fail!(format!("{}.{} can't be set for a single \
+                                                           profile",
+                                                          section_name,
+                                                          k));
+                        }
+                        config.profiles.insert(name.clone(), settings);
+                    }
+                }
+                "templates" => {
+                    let templates = match value.as_table() {
+                        Some(t) => t,
+                        None => return specific_fail_str!("templates should be a table"),
+                    };
+                    for (name, template) in templates.iter() {
+                        let template = try!(expect_str(template, &format!("templates.{}", name)));
+                        config.templates.insert(name.clone(), template.to_string());
+                    }
+                }
+                _ => return specific_fail!(format!("unknown setting '{}'", key)),
+            }
+        }
+        Ok(config)
+    }
+
+    /// the template `--template` is for, the one from the config file if it
+    /// names one and otherwise the template itself
+    pub fn template(&self, template: &str) -> String {
+        self.templates.get(template).cloned().unwrap_or_else(|| template.to_string())
+    }
+
+    /// read the config file at `path`
+    pub fn from_file(path: PathBuf) -> Result<Config> {
+        let mut contents = String::new();
+        try!(try!(File::open(&path)).read_to_string(&mut contents));
+        let mut config = match Config::parse(&contents) {
+            Ok(c) => c,
+            Err(e) => {
+                return specific_fail!(format!("error in config file {}: {}",
+                                              path.display(),
+                                              e.desc))
+            }
+        };
+        config.path = Some(path);
+        Ok(config)
+    }
+
+    /// read the config file from $THECA_CONFIG, or the first of
+    /// $XDG_CONFIG_HOME/theca/config.toml (~/.config if that isn't set) and
+    /// ~/.theca/config.toml that exists, an empty config is used if there
+    /// isn't one
+    pub fn load() -> Result<Config> {
+        if let Ok(path) = var("THECA_CONFIG") {
+            if !path.is_empty() {
+                let path = PathBuf::from(expand_home(&path));
+                if !path.is_file() {
+                    return specific_fail!(format!("config file {} doesn't exist",
+                                                  path.display()));
+                }
+                return Config::from_file(path);
+            }
+        }
+        let mut candidates = vec![];
+        match var("XDG_CONFIG_HOME") {
+            Ok(ref x) if !x.is_empty() => candidates.push(PathBuf::from(x)),
+            _ => {
+                if let Some(home) = home_dir() {
+                    candidates.push(home.join(".config"));
+                }
+            }
+        }
+        for c in candidates.iter_mut() {
+            c.push("theca");
+            c.push("config.toml");
+        }
+        if let Some(home) = home_dir() {
+            candidates.push(home.join(".theca").join("config.toml"));
+        }
+        match candidates.into_iter().find(|c| c.is_file()) {
+            Some(path) => Config::from_file(path),
+            None => Ok(Config::default()),
+        }
+    }
+}
+
+/// Where an effective setting came from
+#[derive(Clone, PartialEq, Debug)]
+pub enum Source {
+    Default,
+    /// the config file, with the name of the profile for per-profile settings
+    Config(Option<String>),
+    Env(&'static str),
+    CommandLine,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Source::Default => write!(f, "default"),
+            Source::Config(None) => write!(f, "config file"),
+            Source::Config(Some(ref p)) => write!(f, "config file [profiles.{}]", p),
+            Source::Env(name) => write!(f, "${}", name),
+            Source::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+/// A setting along with where it came from
+#[derive(Clone, PartialEq, Debug)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn new(value: T) -> Setting<T> {
+        Setting {
+            value: value,
+            source: Source::Default,
+        }
+    }
+
+    // use `value` instead if it is set
+    fn layer(&mut self, value: Option<T>, source: Source) {
+        if let Some(v) = value {
+            self.value = v;
+            self.source = source;
+        }
+    }
+}
+
+// the value of an environment variable if it is set to something
+fn env_var(name: &str) -> Option<String> {
+    match var(name) {
+        Ok(v) if !v.is_empty() => Some(v),
+        _ => None,
+    }
+}
+
+// a boolean setting from the command line, turned on with `--name` and off with
+// `--no-name`
+fn flag(name: &str, on: bool, off: bool) -> Result<Option<bool>> {
+    match (on, off) {
+        (true, true) => {
+            specific_fail!(format!("--{} and --no-{} can't be used together", name, name))
+        }
+        (true, false) => Ok(Some(true)),
+        (false, true) => Ok(Some(false)),
+        (false, false) => Ok(None),
+    }
+}
+
+fn non_empty(s: &str) -> Option<String> {
+    if s.is_empty() { None } else { Some(s.to_string()) }
+}
+
+/// A setting as printed by `theca config`
+#[derive(RustcEncodable, Clone, Debug)]
+pub struct ConfigLine {
+    pub name: String,
+    pub value: String,
+    pub source: String,
+}
+
+#[derive(RustcEncodable)]
+struct ConfigOutput {
+    config_file: Option<String>,
+    settings: Vec<ConfigLine>,
+}
+
+/// The settings theca runs with, the command line takes precedence over the
+/// environment which takes precedence over the config file
+#[derive(Clone, PartialEq, Debug)]
+pub struct Effective {
+    pub config_file: Option<PathBuf>,
+    pub profile: Setting<String>,
+    pub profile_folder: Setting<String>,
+    pub datesort: Setting<bool>,
+    pub reverse: Setting<bool>,
+    pub condensed: Setting<bool>,
+    pub editor: Setting<Option<String>>,
+    pub color: Setting<Color>,
+    pub date_format: Setting<String>,
+    pub backup: Setting<bool>,
+    pub trash_days: Setting<i64>,
+}
+
+impl Effective {
+    /// work out the settings from the command line `args`, the environment and
+    /// `config`
+    pub fn resolve(args: &Args, config: &Config) -> Result<Effective> {
+        let global = Source::Config(None);
+
+        let mut profile = Setting::new("default".to_string());
+        profile.layer(config.profile.clone(), global.clone());
+        profile.layer(env_var("THECA_DEFAULT_PROFILE"),
+                      Source::Env("THECA_DEFAULT_PROFILE"));
+        profile.layer(non_empty(&args.flag_profile), Source::CommandLine);
+
+        let mut profile_folder = Setting::new(String::new());
+        profile_folder.layer(config.profile_folder.clone(), global.clone());
+        profile_folder.layer(env_var("THECA_PROFILE_FOLDER"),
+                             Source::Env("THECA_PROFILE_FOLDER"));
+        profile_folder.layer(non_empty(&args.flag_profile_folder), Source::CommandLine);
+        if let (&Source::Default, Ok(p)) = (&profile_folder.source, find_profile_folder("")) {
+            profile_folder.value = p.display().to_string();
+        }
+
+        let mut effective = Effective {
+            config_file: config.path.clone(),
+            profile: profile,
+            profile_folder: profile_folder,
+            datesort: Setting::new(false),
+            reverse: Setting::new(false),
+            condensed: Setting::new(false),
+            editor: Setting::new(None),
+            color: Setting::new(Color::Auto),
+            date_format: Setting::new(DATEFMT_SHORT.to_string()),
+            backup: Setting::new(false),
+            trash_days: Setting::new(TRASH_DAYS),
+        };
+
+        // settings for every profile, then those for this one
+        let mut sections = vec![(&config.settings, global)];
+        if let Some(s) = config.profiles.get(&effective.profile.value) {
+            sections.push((s, Source::Config(Some(effective.profile.value.clone()))));
+        }
+        for &(s, ref source) in sections.iter() {
+            effective.datesort.layer(s.datesort, source.clone());
+            effective.reverse.layer(s.reverse, source.clone());
+            effective.condensed.layer(s.condensed, source.clone());
+            effective.editor.layer(s.editor.clone().map(Some), source.clone());
+            effective.color.layer(s.color, source.clone());
+            effective.date_format.layer(s.date_format.clone(), source.clone());
+            effective.backup.layer(s.backup, source.clone());
+            effective.trash_days.layer(s.trash_days, source.clone());
+        }
+
+        effective.datesort.layer(try!(flag("datesort", args.flag_datesort, args.flag_no_datesort)),
+                                 Source::CommandLine);
+        effective.reverse.layer(try!(flag("reverse", args.flag_reverse, args.flag_no_reverse)),
+                                Source::CommandLine);
+        effective.condensed.layer(try!(flag("condensed",
+                                            args.flag_condensed,
+                                            args.flag_no_condensed)),
+                                  Source::CommandLine);
+
+        // $VISUAL is preferred to $EDITOR like most other programs do
+        effective.editor.layer(env_var("EDITOR").map(Some), Source::Env("EDITOR"));
+        effective.editor.layer(env_var("VISUAL").map(Some), Source::Env("VISUAL"));
+
+        if let Some(c) = env_var("THECA_COLOR") {
+            effective.color.layer(Some(try!(Color::parse(&c))), Source::Env("THECA_COLOR"));
+        }
+        if !args.flag_color.is_empty() {
+            effective.color.layer(Some(try!(Color::parse(&args.flag_color))),
+                                  Source::CommandLine);
+        }
+
+        if let Some(f) = env_var("THECA_DATE_FORMAT") {
+            try!(check_date_format(&f));
+            effective.date_format.layer(Some(f), Source::Env("THECA_DATE_FORMAT"));
+        }
+
+        effective.backup.layer(env_var("THECA_BACKUP").map(|v| v != "0"),
+                               Source::Env("THECA_BACKUP"));
+        effective.backup.layer(try!(flag("backup", args.flag_backup, args.flag_no_backup)),
+                               Source::CommandLine);
+
+        if let Some(d) = env_var("THECA_TRASH_DAYS") {
+            effective.trash_days.layer(Some(try!(parse_trash_days(&d))),
+                                       Source::Env("THECA_TRASH_DAYS"));
+        }
+        if !args.flag_trash_days.is_empty() {
+            effective.trash_days.layer(Some(try!(parse_trash_days(&args.flag_trash_days))),
+                                       Source::CommandLine);
+        }
+
+        Ok(effective)
+    }
+
+    /// set `args` and the settings used for printing to the effective values
+    pub fn apply(&self, args: &mut Args) {
+        args.flag_profile = self.profile.value.clone();
+        args.flag_profile_folder = self.profile_folder.value.clone();
+        args.flag_datesort = self.datesort.value;
+        args.flag_reverse = self.reverse.value;
+        args.flag_condensed = self.condensed.value;
+        args.flag_color = self.color.value.to_string();
+        args.flag_backup = self.backup.value;
+        args.flag_trash_days = self.trash_days.value.to_string();
+        CURRENT.with(|c| {
+            let mut c = c.borrow_mut();
+            c.color = self.color.value;
+            c.date_format = self.date_format.value.clone();
+            c.editor = self.editor.value.clone();
+        });
+    }
+
+    /// each setting with where it came from
+    pub fn lines(&self) -> Vec<ConfigLine> {
+        fn line<T: fmt::Display>(name: &str, s: &Setting<T>) -> ConfigLine {
+            ConfigLine {
+                name: name.to_string(),
+                value: s.value.to_string(),
+                source: s.source.to_string(),
+            }
+        }
+        let editor = Setting {
+            value: self.editor.value.clone().unwrap_or_else(String::new),
+            source: self.editor.source.clone(),
+        };
+        vec![line("profile", &self.profile),
+             line("profile_folder", &self.profile_folder),
+             line("datesort", &self.datesort),
+             line("reverse", &self.reverse),
+             line("condensed", &self.condensed),
+             line("editor", &editor),
+             line("color", &self.color),
+             line("date_format", &self.date_format),
+             line("backup", &self.backup),
+             line("trash_days", &self.trash_days)]
+    }
+
+    /// print the settings for `theca config`
+    pub fn print(&self, json: bool) -> Result<()> {
+        if json {
+            println!("{}",
+                     as_pretty_json(&ConfigOutput {
+                         config_file: self.config_file.as_ref().map(|p| p.display().to_string()),
+                         settings: self.lines(),
+                     }));
+            return Ok(());
+        }
+        let tty = color_output();
+        let config_file = match self.config_file {
+            Some(ref p) => p.display().to_string(),
+            None => "none".to_string(),
+        };
+        try!(pretty_line("config file: ", &format!("{}\n", config_file), tty));
+        for l in self.lines().iter() {
+            try!(pretty_line(&format!("{}: ", l.name),
+                             &format!("{} ({})\n", l.value, l.source),
+                             tty));
+        }
+        Ok(())
+    }
+}
+
+/// whether output should be bold and colored
+pub fn color_output() -> bool {
+    match CURRENT.with(|c| c.borrow().color) {
+        Color::Auto => istty(STDOUT_FILENO),
+        Color::Always => true,
+        Color::Never => false,
+    }
+}
+
+/// the format last touched times are printed in
+pub fn date_format() -> String {
+    CURRENT.with(|c| c.borrow().date_format.clone())
+}
+
+/// the editor to use for note bodies
+pub fn editor() -> Option<String> {
+    CURRENT.with(|c| c.borrow().editor.clone())
+}
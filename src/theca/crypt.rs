@@ -7,50 +7,394 @@
 // licensed under the MIT license <http://opensource.org/licenses/MIT>
 //
 // crypt.rs
-//   defintions of the AES encryption, decryption, and PBKDF2 key derivation
-//   functions required to read and write encrypted profiles.
+//   defintions of the AES encryption, decryption, and scrypt/PBKDF2 key
+//   derivation functions required to read and write encrypted profiles.
+//
+//   encrypted profiles are written as a small header followed by the
+//   AES-256-GCM ciphertext and tag, the header is authenticated along with
+//   the ciphertext:
+//
+//       magic       5 bytes   "THECA"
+//       version     1 byte    FORMAT_VERSION
+//       kdf         1 byte    KDF_SCRYPT or KDF_PBKDF2_SHA256
+//       kdf params  9 bytes   scrypt log_n, r, p (r and p big endian)
+//                   4 bytes   or pbkdf2 rounds (big endian)
+//       salt        16 bytes  random, see below
+//       nonce       12 bytes  random per save
+//       ciphertext
+//       tag         16 bytes
+//
+//   the salt and nonce are regenerated every time a profile is saved. while
+//   keys are cached (in `theca shell`) the key derived for the salt a profile
+//   was last written with is kept, so reading it back doesn't derive it again.
+//
+//   profiles written before the header existed (AES-256-CBC with the IV as
+//   the first 16 bytes) can still be decrypted, they are upgraded the next
+//   time the profile is saved.
 
+use std::cell::RefCell;
+use std::fmt;
 use std::iter::repeat;
+use std::path::{Path, PathBuf};
 use crypto::{symmetriccipher, buffer, aes, blockmodes};
 use crypto::buffer::{ReadBuffer, WriteBuffer, BufferResult};
+use crypto::aead::{AeadEncryptor, AeadDecryptor};
+use crypto::aes_gcm::AesGcm;
 use crypto::pbkdf2::pbkdf2;
+use crypto::scrypt::{scrypt, ScryptParams};
 use crypto::hmac::Hmac;
 use crypto::sha2::Sha256;
 use crypto::digest::Digest;
-use crypto::fortuna::Fortuna;
-use rand::{SeedableRng, Rng};
+use rand::{OsRng, Rng};
 
-// ALL the encryption functions thx rust-crypto ^_^
-pub fn encrypt(data: &[u8], key: &[u8]) -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
-    let mut iv = [0u8; 16];
-    let mut f: Fortuna = SeedableRng::from_seed(data);
-    f.fill_bytes(&mut iv);
+use errors::{Result, Error};
 
-    let mut encryptor =
-        aes::cbc_encryptor(aes::KeySize::KeySize256, key, &iv, blockmodes::PkcsPadding);
+/// magic bytes at the start of every encrypted profile with a header
+pub static MAGIC: &'static [u8] = b"THECA";
+/// current version of the encrypted profile format
+pub const FORMAT_VERSION: u8 = 1;
+/// kdf identifier for PBKDF2-HMAC-SHA256
+pub const KDF_PBKDF2_SHA256: u8 = 1;
+/// kdf identifier for scrypt
+pub const KDF_SCRYPT: u8 = 2;
+/// PBKDF2 rounds used for profiles written with PBKDF2
+pub const PBKDF2_ROUNDS: u32 = 100000;
+/// scrypt cost (log2 of N) used for newly written profiles, ~32MiB of memory
+pub const SCRYPT_DEFAULT_COST: u8 = 15;
+/// lowest scrypt cost that can be set with --kdf-cost
+pub const SCRYPT_MIN_COST: u8 = 10;
+/// highest scrypt cost that can be set with --kdf-cost, ~1GiB of memory
+pub const SCRYPT_MAX_COST: u8 = 20;
+/// scrypt block size parameter
+pub const SCRYPT_R: u32 = 8;
+/// scrypt parallelization parameter
+pub const SCRYPT_P: u32 = 1;
+/// PBKDF2 rounds used by profiles written before the header existed
+pub const LEGACY_PBKDF2_ROUNDS: u32 = 2056;
 
-    let mut final_result = Vec::<u8>::new();
-    final_result.extend_from_slice(&iv);
-    let mut read_buffer = buffer::RefReadBuffer::new(data);
-    let mut buffer = [0; 4096];
-    let mut write_buffer = buffer::RefWriteBuffer::new(&mut buffer);
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+// how many derived keys are kept once caching is turned on
+const KEY_CACHE_LEN: usize = 8;
 
-    loop {
-        let result = try!(encryptor.encrypt(&mut read_buffer, &mut write_buffer, true));
+thread_local!(static KEY_CACHE: RefCell<Option<Vec<CachedKey>>> = RefCell::new(None));
 
-        final_result.extend(write_buffer.take_read_buffer().take_remaining());
+// a key derived from a passphrase for a profile, kept while caching is on
+struct CachedKey {
+    profile: PathBuf,
+    kdf: Kdf,
+    salt: Vec<u8>,
+    passphrase: String,
+    key: Vec<u8>,
+}
 
-        if let BufferResult::BufferUnderflow = result {
-            break;
+/// keep the keys derived from passphrases in memory from now on, along with
+/// their salts, so a long running `theca shell` only derives a key when a
+/// profile is saved with a fresh salt and not every time it is read
+pub fn cache_keys() {
+    KEY_CACHE.with(|c| {
+        let mut cache = c.borrow_mut();
+        if cache.is_none() {
+            *cache = Some(vec![]);
+        }
+    });
+}
+
+// derive the key of `profile` for `passphrase` and `salt`, from the cache if
+// it's on. a new key replaces the one cached for the salt the profile had
+// before.
+fn derive_key_cached(profile: &Path, kdf: Kdf, passphrase: &str, salt: &[u8]) -> Vec<u8> {
+    KEY_CACHE.with(|c| {
+        let mut cache = c.borrow_mut();
+        let keys = match *cache {
+            Some(ref mut keys) => keys,
+            None => return kdf.derive_key(passphrase, salt),
+        };
+        let found = keys.iter().position(|k| {
+            k.profile == profile && k.kdf == kdf && k.salt == salt && k.passphrase == passphrase
+        });
+        match found {
+            Some(p) => {
+                // most recently used last
+                let cached = keys.remove(p);
+                let key = cached.key.clone();
+                keys.push(cached);
+                key
+            }
+            None => {
+                let key = kdf.derive_key(passphrase, salt);
+                keys.retain(|k| k.profile != profile);
+                keys.push(CachedKey {
+                    profile: profile.to_path_buf(),
+                    kdf: kdf,
+                    salt: salt.to_vec(),
+                    passphrase: passphrase.to_string(),
+                    key: key.clone(),
+                });
+                if keys.len() > KEY_CACHE_LEN {
+                    keys.remove(0);
+                }
+                key
+            }
+        }
+    })
+}
+
+/// Key derivation function and parameters used for an encrypted profile
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Kdf {
+    Pbkdf2 {
+        rounds: u32,
+    },
+    Scrypt {
+        log_n: u8,
+        r: u32,
+        p: u32,
+    },
+}
+
+impl Kdf {
+    /// scrypt with the default parameters
+    pub fn default_scrypt() -> Kdf {
+        Kdf::Scrypt {
+            log_n: SCRYPT_DEFAULT_COST,
+            r: SCRYPT_R,
+            p: SCRYPT_P,
         }
     }
 
-    Ok(final_result)
+    /// scrypt with a work factor of 2^`cost`
+    pub fn scrypt_with_cost(cost: u8) -> Result<Kdf> {
+        if cost < SCRYPT_MIN_COST || cost > SCRYPT_MAX_COST {
+            return specific_fail!(format!("kdf cost must be between {} and {}",
+                                          SCRYPT_MIN_COST,
+                                          SCRYPT_MAX_COST));
+        }
+        Ok(Kdf::Scrypt {
+            log_n: cost,
+            r: SCRYPT_R,
+            p: SCRYPT_P,
+        })
+    }
+
+    /// scrypt with the cost given on the command line with --kdf-cost
+    pub fn from_cost_arg(cost: &str) -> Result<Kdf> {
+        match cost.parse::<u8>() {
+            Ok(c) => Kdf::scrypt_with_cost(c),
+            Err(_) => specific_fail!(format!("invalid kdf cost '{}'", cost)),
+        }
+    }
+
+    // make sure parameters read from a header won't make scrypt panic or try
+    // to allocate an absurd amount of memory
+    fn validate(&self) -> Result<()> {
+        let valid = match *self {
+            Kdf::Pbkdf2 { rounds } => rounds > 0,
+            Kdf::Scrypt { log_n, r, p } => {
+                log_n > 0 && log_n <= SCRYPT_MAX_COST && r > 0 && p > 0 &&
+                (log_n as u64) < (r as u64) * 16 &&
+                (r as u64) * (p as u64) < 0x40000000 &&
+                (r as u64) * 128 << log_n <= (128 * SCRYPT_R as u64) << SCRYPT_MAX_COST
+            }
+        };
+        if valid {
+            Ok(())
+        } else {
+            specific_fail!(format!("invalid key derivation parameters ({})", self))
+        }
+    }
+
+    fn derive_key(&self, passphrase: &str, salt: &[u8]) -> Vec<u8> {
+        match *self {
+            Kdf::Pbkdf2 { rounds } => password_to_key(passphrase, salt, rounds),
+            Kdf::Scrypt { log_n, r, p } => {
+                let mut key: Vec<u8> = repeat(0).take(32).collect();
+                scrypt(passphrase.as_bytes(),
+                       salt,
+                       &ScryptParams::new(log_n, r, p),
+                       &mut key);
+                key
+            }
+        }
+    }
+}
+
+impl fmt::Display for Kdf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Kdf::Pbkdf2 { rounds } => write!(f, "pbkdf2-hmac-sha256 (rounds: {})", rounds),
+            Kdf::Scrypt { log_n, r, p } => {
+                write!(f, "scrypt (cost: {}, r: {}, p: {})", log_n, r, p)
+            }
+        }
+    }
 }
 
+/// Header written in front of the ciphertext of an encrypted profile
+#[derive(Clone, PartialEq, Debug)]
+pub struct Header {
+    pub version: u8,
+    pub kdf: Kdf,
+    pub salt: Vec<u8>,
+    pub nonce: Vec<u8>,
+}
+
+impl Header {
+    /// create a header with a fresh random salt and nonce
+    pub fn new(kdf: Kdf) -> Result<Header> {
+        let mut rng = try!(OsRng::new());
+        let mut salt: Vec<u8> = repeat(0).take(SALT_LEN).collect();
+        let mut nonce: Vec<u8> = repeat(0).take(NONCE_LEN).collect();
+        rng.fill_bytes(&mut salt);
+        rng.fill_bytes(&mut nonce);
+        Ok(Header {
+            version: FORMAT_VERSION,
+            kdf: kdf,
+            salt: salt,
+            nonce: nonce,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(self.version);
+        match self.kdf {
+            Kdf::Pbkdf2 { rounds } => {
+                bytes.push(KDF_PBKDF2_SHA256);
+                bytes.extend_from_slice(&u32_to_bytes(rounds));
+            }
+            Kdf::Scrypt { log_n, r, p } => {
+                bytes.push(KDF_SCRYPT);
+                bytes.push(log_n);
+                bytes.extend_from_slice(&u32_to_bytes(r));
+                bytes.extend_from_slice(&u32_to_bytes(p));
+            }
+        }
+        bytes.extend_from_slice(&self.salt);
+        bytes.extend_from_slice(&self.nonce);
+        bytes
+    }
+
+    /// parse the header at the start of `data`, returning it and its length
+    pub fn from_bytes(data: &[u8]) -> Result<(Header, usize)> {
+        if !has_header(data) {
+            return specific_fail_str!("encrypted profile is missing its header");
+        }
+        let mut pos = MAGIC.len();
+        let version = try!(take(data, &mut pos, 1))[0];
+        if version != FORMAT_VERSION {
+            return specific_fail!(format!("unsupported encrypted profile version {}, is \
+                                           theca out of date?",
+                                          version));
+        }
+        let kdf = match try!(take(data, &mut pos, 1))[0] {
+            KDF_PBKDF2_SHA256 => {
+                Kdf::Pbkdf2 { rounds: bytes_to_u32(try!(take(data, &mut pos, 4))) }
+            }
+            KDF_SCRYPT => {
+                Kdf::Scrypt {
+                    log_n: try!(take(data, &mut pos, 1))[0],
+                    r: bytes_to_u32(try!(take(data, &mut pos, 4))),
+                    p: bytes_to_u32(try!(take(data, &mut pos, 4))),
+                }
+            }
+            k => return specific_fail!(format!("unknown key derivation function {}", k)),
+        };
+        try!(kdf.validate());
+        let salt = try!(take(data, &mut pos, SALT_LEN)).to_vec();
+        let nonce = try!(take(data, &mut pos, NONCE_LEN)).to_vec();
+        Ok((Header {
+            version: version,
+            kdf: kdf,
+            salt: salt,
+            nonce: nonce,
+        },
+            pos))
+    }
+}
+
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+    if data.len() < *pos + len {
+        return specific_fail_str!("encrypted profile is truncated");
+    }
+    let taken = &data[*pos..*pos + len];
+    *pos += len;
+    Ok(taken)
+}
+
+fn u32_to_bytes(n: u32) -> [u8; 4] {
+    [(n >> 24) as u8, (n >> 16) as u8, (n >> 8) as u8, n as u8]
+}
+
+fn bytes_to_u32(b: &[u8]) -> u32 {
+    ((b[0] as u32) << 24) | ((b[1] as u32) << 16) | ((b[2] as u32) << 8) | (b[3] as u32)
+}
+
+/// does `data` start with the encrypted profile header magic
+pub fn has_header(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// the key derivation function recorded in the header of an encrypted profile,
+/// if it has one
+pub fn header_kdf(data: &[u8]) -> Option<Kdf> {
+    Header::from_bytes(data).ok().map(|(h, _)| h.kdf)
+}
+
+/// encrypt the profile at `profile` using a key derived from `passphrase` with
+/// `kdf`
+pub fn encrypt_profile(data: &[u8], passphrase: &str, kdf: Kdf, profile: &Path) -> Result<Vec<u8>> {
+    try!(kdf.validate());
+    let header = try!(Header::new(kdf));
+    let key = derive_key_cached(profile, kdf, passphrase, &header.salt);
+    let mut output = header.to_bytes();
+
+    let mut ciphertext: Vec<u8> = repeat(0).take(data.len()).collect();
+    let mut tag = [0u8; TAG_LEN];
+    {
+        let mut cipher = AesGcm::new(aes::KeySize::KeySize256, &key, &header.nonce, &output);
+        cipher.encrypt(data, &mut ciphertext, &mut tag);
+    }
+    output.extend(ciphertext);
+    output.extend_from_slice(&tag);
+    Ok(output)
+}
+
+/// decrypt the profile at `profile` using a key derived from `passphrase`,
+/// profiles without a header are decrypted using the legacy AES-256-CBC format
+pub fn decrypt_profile(data: &[u8], passphrase: &str, profile: &Path) -> Result<Vec<u8>> {
+    if !has_header(data) {
+        let key = password_to_key(passphrase, &legacy_salt(passphrase), LEGACY_PBKDF2_ROUNDS);
+        return Ok(try!(decrypt(data, &key)));
+    }
+
+    let (header, header_len) = try!(Header::from_bytes(data));
+    if data.len() < header_len + TAG_LEN {
+        return specific_fail_str!("encrypted profile is truncated");
+    }
+    let key = derive_key_cached(profile, header.kdf, passphrase, &header.salt);
+    let (ciphertext, tag) = data[header_len..].split_at(data.len() - header_len - TAG_LEN);
+
+    let mut plaintext: Vec<u8> = repeat(0).take(ciphertext.len()).collect();
+    let mut cipher = AesGcm::new(aes::KeySize::KeySize256,
+                                 &key,
+                                 &header.nonce,
+                                 &data[..header_len]);
+    if !cipher.decrypt(ciphertext, &mut plaintext, tag) {
+        return specific_fail_str!("invalid encryption key (or the profile has been corrupted)");
+    }
+    Ok(plaintext)
+}
+
+// legacy AES-256-CBC decryption, only used to read profiles written before
+// the header existed
 pub fn decrypt(encrypted_data: &[u8],
                key: &[u8])
-               -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
+               -> ::std::result::Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
+    if encrypted_data.len() < 16 {
+        return Err(symmetriccipher::SymmetricCipherError::InvalidLength);
+    }
     let iv = &encrypted_data[0..16];
 
     let mut decryptor =
@@ -72,16 +416,19 @@ pub fn decrypt(encrypted_data: &[u8],
     Ok(final_result)
 }
 
-pub fn password_to_key(p: &str) -> Vec<u8> {
-    // yehh.... idk
+/// the salt used by profiles written before the header existed, the hex
+/// sha256 of the passphrase
+pub fn legacy_salt(p: &str) -> Vec<u8> {
     let mut salt_sha = Sha256::new();
     salt_sha.input(p.as_bytes());
-    let salt = salt_sha.result_str();
+    salt_sha.result_str().into_bytes()
+}
 
+pub fn password_to_key(p: &str, salt: &[u8], rounds: u32) -> Vec<u8> {
     let mut mac = Hmac::new(Sha256::new(), p.as_bytes());
     let mut key: Vec<u8> = repeat(0).take(32).collect();
 
-    pbkdf2(&mut mac, salt.as_bytes(), 2056, key.as_mut_slice());
+    pbkdf2(&mut mac, salt, rounds, key.as_mut_slice());
 
     key
 }
@@ -0,0 +1,323 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// csv.rs
+//   notes as rows of a CSV or TSV table, used by `--format csv|tsv` for lists
+//   and searches, `export` and `import`.
+
+// theca imports
+use export::ImportedNote;
+use item::Item;
+use markdown::parse_status;
+use todotxt::DONE_TAG;
+use utils::localize_due_string;
+use errors::{Result, Error};
+
+/// the columns of a table of notes, in the order they are written
+pub static COLUMNS: [&'static str; 8] = ["id",
+                                         "uuid",
+                                         "title",
+                                         "status",
+                                         "tags",
+                                         "due",
+                                         "last_touched",
+                                         "body"];
+
+/// the fields of a note a column can be imported into, `done` tags the note
+/// `done`
+pub static FIELDS: [&'static str; 9] = ["id",
+                                        "uuid",
+                                        "title",
+                                        "status",
+                                        "tags",
+                                        "due",
+                                        "last_touched",
+                                        "body",
+                                        "done"];
+
+/// the separator between the fields of a row for `--format`, a comma for csv
+/// and a tab for tsv
+pub fn separator(format: &str) -> Result<char> {
+    match &*format.to_lowercase() {
+        "csv" => Ok(','),
+        "tsv" => Ok('\t'),
+        _ => {
+            specific_fail!(format!("unknown format '{}' for a list of notes, use csv or tsv",
+                                   format))
+        }
+    }
+}
+
+// a field as it is written to a row. CSV fields with a separator, quote or
+// line break in them are quoted (RFC 4180), TSV can't quote so tabs, line
+// breaks and backslashes are escaped with a backslash instead.
+fn field(value: &str, sep: char) -> String {
+    if sep == '\t' {
+        let mut out = String::new();
+        for c in value.chars() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '\t' => out.push_str("\\t"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                _ => out.push(c),
+            }
+        }
+        out
+    } else if value.contains(|c: char| c == sep || c == '"' || c == '\n' || c == '\r') ||
+              value.trim() != value {
+        format!("\"{}\"", value.replace("\"", "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// `fields` as a row of a table
+pub fn row(fields: &[String], sep: char) -> String {
+    let mut line = fields.iter().map(|f| field(f, sep)).collect::<Vec<_>>().join(&sep.to_string());
+    line.push('\n');
+    line
+}
+
+/// the header row of a table of notes
+pub fn header(sep: char) -> String {
+    row(&COLUMNS.iter().map(|c| c.to_string()).collect::<Vec<_>>(), sep)
+}
+
+// the fields of a note in the order of `COLUMNS`
+fn note_fields(note: &Item) -> Result<Vec<String>> {
+    let due = match note.due {
+        Some(ref d) => try!(localize_due_string(d)),
+        None => String::new(),
+    };
+    Ok(vec![note.id.to_string(),
+            note.uuid.clone(),
+            note.title.clone(),
+            note.status.to_string(),
+            note.tags.join(","),
+            due,
+            note.last_touched.clone(),
+            note.body.clone()])
+}
+
+/// a note as a row of a table, in the order of `COLUMNS`
+pub fn note_row(note: &Item, sep: char) -> Result<String> {
+    Ok(row(&try!(note_fields(note)), sep))
+}
+
+/// `notes` as a table with a header row
+pub fn to_table(notes: &[Item], sep: char) -> Result<String> {
+    let mut out = header(sep);
+    for note in notes.iter() {
+        out.push_str(&try!(note_row(note, sep)));
+    }
+    Ok(out)
+}
+
+/// the notes found in several profiles as a table, with the name of the
+/// profile of each note in a `profile` column before the others
+pub fn to_profiles_table(hits: &[(String, Vec<Item>)], sep: char) -> Result<String> {
+    let mut names = vec!["profile".to_string()];
+    names.extend(COLUMNS.iter().map(|c| c.to_string()));
+    let mut out = row(&names, sep);
+    for &(ref profile, ref notes) in hits.iter() {
+        for note in notes.iter() {
+            let mut fields = vec![profile.clone()];
+            fields.extend(try!(note_fields(note)));
+            out.push_str(&row(&fields, sep));
+        }
+    }
+    Ok(out)
+}
+
+// undo the escapes of a TSV field
+fn unescape(value: &str) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(n) => out.push(n),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// read the rows of a table, blank lines are skipped
+pub fn parse_rows(text: &str, sep: char) -> Result<Vec<Vec<String>>> {
+    // spreadsheets often start their files with a byte order mark
+    let text = text.trim_left_matches('\u{feff}');
+    let mut rows = vec![];
+    if sep == '\t' {
+        for line in text.lines() {
+            if !line.is_empty() {
+                rows.push(line.split('\t').map(unescape).collect());
+            }
+        }
+        return Ok(rows);
+    }
+
+    let mut fields = vec![];
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    value.push('"');
+                } else {
+                    quoted = false;
+                }
+            } else {
+                if c == '\n' {
+                    line += 1;
+                }
+                value.push(c);
+            }
+        } else if c == '"' && value.trim().is_empty() {
+            value.clear();
+            quoted = true;
+        } else if c == sep {
+            fields.push(value);
+            value = String::new();
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            line += 1;
+            if !fields.is_empty() || !value.is_empty() {
+                fields.push(value);
+                rows.push(fields);
+            }
+            fields = vec![];
+            value = String::new();
+        } else {
+            value.push(c);
+        }
+    }
+    if quoted {
+        return specific_fail!(format!("a quoted field isn't closed by line {}", line));
+    }
+    if !fields.is_empty() || !value.is_empty() {
+        fields.push(value);
+        rows.push(fields);
+    }
+    Ok(rows)
+}
+
+// a column name as a field name, `Last touched` is `last_touched`
+fn field_name(name: &str) -> String {
+    name.trim().to_lowercase().replace(' ', "_").replace('-', "_")
+}
+
+/// the field each column of a table with the header `header` is read into,
+/// None for columns that are ignored. columns named after a field (in any
+/// case) are read into it unless `mapping`, a list of `COLUMN=FIELD` pairs
+/// separated by commas, says otherwise. a column mapped to nothing is ignored.
+pub fn column_fields(header: &[String], mapping: &str) -> Result<Vec<Option<&'static str>>> {
+    let mut fields: Vec<Option<&'static str>> =
+        header.iter()
+              .map(|name| FIELDS.iter().cloned().find(|f| *f == field_name(name)))
+              .collect();
+    for pair in mapping.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        let (column, to) = match pair.find('=') {
+            Some(i) => (pair[..i].trim(), field_name(&pair[i + 1..])),
+            None => return specific_fail!(format!("expected COLUMN=FIELD, found '{}'", pair)),
+        };
+        let field = if to.is_empty() {
+            None
+        } else {
+            match FIELDS.iter().cloned().find(|f| *f == to) {
+                Some(f) => Some(f),
+                None => {
+                    return specific_fail!(format!("a note has no field '{}', use one of {}",
+                                                  to,
+                                                  FIELDS.join(", ")))
+                }
+            }
+        };
+        match header.iter().position(|h| h.trim().to_lowercase() == column.to_lowercase()) {
+            Some(i) => fields[i] = field,
+            None => return specific_fail!(format!("there is no column named '{}'", column)),
+        }
+    }
+    for (i, field) in fields.iter().enumerate() {
+        if let Some(f) = *field {
+            if fields[..i].contains(field) {
+                return specific_fail!(format!("more than one column is read into {}", f));
+            }
+        }
+    }
+    Ok(fields)
+}
+
+fn is_true(value: &str) -> bool {
+    match &*value.trim().to_lowercase() {
+        "x" | "1" | "y" | "yes" | "true" | "done" => true,
+        _ => false,
+    }
+}
+
+/// read a note from a row of a table, `fields` are the fields its columns are
+/// read into
+pub fn row_note(row: &[String], fields: &[Option<&'static str>]) -> Result<ImportedNote> {
+    let mut note = ImportedNote {
+        uuid: None,
+        id: None,
+        title: None,
+        status: None,
+        last_touched: None,
+        tags: vec![],
+        due: None,
+        body: None,
+    };
+    let mut done = false;
+    for (value, field) in row.iter().zip(fields.iter()) {
+        let field = match *field {
+            Some(f) => f,
+            None => continue,
+        };
+        let trimmed = value.trim();
+        match field {
+            "id" if !trimmed.is_empty() => {
+                note.id = match trimmed.parse() {
+                    Ok(i) => Some(i),
+                    Err(_) => return specific_fail!(format!("invalid note id '{}'", trimmed)),
+                }
+            }
+            "uuid" if !trimmed.is_empty() => note.uuid = Some(trimmed.to_lowercase()),
+            "title" => note.title = Some(trimmed.to_string()),
+            "status" => note.status = Some(try!(parse_status(trimmed))),
+            "tags" => {
+                note.tags = trimmed.split(',')
+                                   .map(|t| t.trim().to_string())
+                                   .filter(|t| !t.is_empty())
+                                   .collect()
+            }
+            "due" if !trimmed.is_empty() => note.due = Some(trimmed.to_string()),
+            "last_touched" if !trimmed.is_empty() => note.last_touched = Some(trimmed.to_string()),
+            "body" => note.body = Some(value.clone()),
+            "done" => done = is_true(value),
+            _ => {}
+        }
+    }
+    if done && !note.tags.iter().any(|t| t == DONE_TAG) {
+        note.tags.push(DONE_TAG.to_string());
+    }
+    Ok(note)
+}
@@ -0,0 +1,107 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// diff.rs
+//   line based unified diffs, used to compare revisions of a note body.
+
+/// lines of unchanged text shown around each change
+pub const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Op {
+    Same,
+    Removed,
+    Added,
+}
+
+// (op, line index in old, line index in new) for every line of both texts, using
+// the longest common subsequence of the lines
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<(Op, usize, usize)> {
+    let (n, m) = (old.len(), new.len());
+    // lcs[i][j] is the length of the lcs of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                ::std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut script = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            script.push((Op::Same, i, j));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            script.push((Op::Removed, i, j));
+            i += 1;
+        } else {
+            script.push((Op::Added, i, j));
+            j += 1;
+        }
+    }
+    script
+}
+
+// the start of a range in a hunk header, empty ranges start at the line before
+fn range(start: usize, len: usize) -> String {
+    if len == 0 {
+        format!("{},0", start)
+    } else if len == 1 {
+        format!("{}", start + 1)
+    } else {
+        format!("{},{}", start + 1, len)
+    }
+}
+
+/// a unified diff of the lines of `old` and `new`, empty if they are the same
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let script = edit_script(&old_lines, &new_lines);
+
+    let changes: Vec<usize> = (0..script.len()).filter(|&i| script[i].0 != Op::Same).collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let mut c = 0;
+    while c < changes.len() {
+        // extend the hunk while the next change is close enough that the context
+        // around the two would overlap
+        let start = changes[c].saturating_sub(CONTEXT);
+        let mut last = changes[c];
+        while c + 1 < changes.len() && changes[c + 1] - last <= 2 * CONTEXT {
+            c += 1;
+            last = changes[c];
+        }
+        let end = ::std::cmp::min(last + CONTEXT + 1, script.len());
+        c += 1;
+
+        let hunk = &script[start..end];
+        let old_len = hunk.iter().filter(|h| h.0 != Op::Added).count();
+        let new_len = hunk.iter().filter(|h| h.0 != Op::Removed).count();
+        out.push_str(&format!("@@ -{} +{} @@\n",
+                              range(hunk[0].1, old_len),
+                              range(hunk[0].2, new_len)));
+        for &(op, i, j) in hunk {
+            match op {
+                Op::Same => out.push_str(&format!(" {}\n", old_lines[i])),
+                Op::Removed => out.push_str(&format!("-{}\n", old_lines[i])),
+                Op::Added => out.push_str(&format!("+{}\n", new_lines[j])),
+            }
+        }
+    }
+    out
+}
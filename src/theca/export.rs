@@ -0,0 +1,595 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// export.rs
+//   `theca export` and the commands that import notes written in the formats
+//   it exports to.
+
+// std imports
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, read_dir, remove_file, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// random things
+use time::{now, strftime};
+
+// theca imports
+use Args;
+use csv::{column_fields, parse_rows, row_note, separator, to_table};
+use item::{Item, Status};
+use markdown::{add_uuid, file_name, from_markdown, to_markdown};
+use org::{add_ids, from_org, notes_to_org};
+use profile::{Profile, DATEFMT};
+use todotxt::{parse_line, to_line, Task};
+use utils::{new_uuid, parse_date_input, parse_last_touched, write_atomic};
+use errors::{Result, Error};
+
+/// The formats notes can be exported to and imported from
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    /// a folder of markdown files with YAML front matter, one per note
+    Markdown,
+    /// a todo.txt file, a task per line
+    TodoTxt,
+    /// an org-mode file, a headline per note
+    Org,
+    /// a table with a row per note, its fields separated by commas
+    Csv,
+    /// a table with a row per note, its fields separated by tabs
+    Tsv,
+}
+
+impl Format {
+    /// the format named by `--format`, markdown if it wasn't used
+    pub fn from_arg(format: &str) -> Result<Format> {
+        match &*format.to_lowercase() {
+            "" | "markdown" | "md" => Ok(Format::Markdown),
+            "todotxt" | "todo.txt" => Ok(Format::TodoTxt),
+            "org" | "orgmode" | "org-mode" => Ok(Format::Org),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            _ => {
+                specific_fail!(format!("unknown format '{}', use markdown, todotxt, org, csv or \
+                                        tsv",
+                                       format))
+            }
+        }
+    }
+}
+
+/// export the notes in `profile` to `args.arg_path` in the format picked with
+/// `--format`, formats that are a single file are printed if there's no path
+pub fn export(profile: &Profile, args: &Args) -> Result<()> {
+    let path = Path::new(&args.arg_path);
+    match try!(Format::from_arg(&args.flag_format)) {
+        Format::Markdown => {
+            if args.arg_path.is_empty() {
+                return specific_fail_str!("markdown is exported to a folder, give the path of \
+                                           one to write the notes to");
+            }
+            export_markdown(&profile.notes, path)
+        }
+        Format::TodoTxt => {
+            let mut lines = String::new();
+            for note in profile.notes.iter() {
+                lines.push_str(&try!(to_line(note)));
+                lines.push('\n');
+            }
+            write_export(&lines, path, profile.notes.len())
+        }
+        Format::Org => write_export(&try!(notes_to_org(&profile.notes)), path, profile.notes.len()),
+        Format::Csv | Format::Tsv => {
+            let table = try!(to_table(&profile.notes, try!(separator(&args.flag_format))));
+            write_export(&table, path, profile.notes.len())
+        }
+    }
+}
+
+// write an export that is a single file to `path`, or print it if there is no
+// path
+fn write_export(contents: &str, path: &Path, count: usize) -> Result<()> {
+    if path.as_os_str().is_empty() {
+        print!("{}", contents);
+    } else {
+        try!(write_atomic(path, contents.as_bytes(), false));
+        println!("exported {} notes to {}", count, path.display());
+    }
+    Ok(())
+}
+
+/// write the files an import read from with the uuids of the notes they added
+/// (see `import_org` and `import_dir`), once the profile has been saved so a
+/// failed import never changes them
+pub fn write_sources(sources: &[(PathBuf, String)]) -> Result<()> {
+    for &(ref path, ref contents) in sources.iter() {
+        try!(write_atomic(path, contents.as_bytes(), false));
+    }
+    Ok(())
+}
+
+/// import the notes in `args.arg_file`, which is in the format picked with
+/// `--format`, returns the files to write with `write_sources` once the
+/// profile has been saved
+pub fn import(profile: &mut Profile, args: &Args) -> Result<Vec<(PathBuf, String)>> {
+    if args.flag_format.is_empty() {
+        return specific_fail!(format!("use --format to say what format {} is in (todotxt, org, \
+                                       csv or tsv)",
+                                      args.arg_file));
+    }
+    let path = Path::new(&args.arg_file);
+    let format = try!(Format::from_arg(&args.flag_format));
+    if format != Format::Csv && format != Format::Tsv {
+        if !args.flag_columns.is_empty() {
+            return specific_fail_str!("--columns only works when importing csv or tsv");
+        }
+        if args.flag_dry_run {
+            return specific_fail_str!("--dry-run only works when importing csv or tsv");
+        }
+    }
+    match format {
+        Format::Markdown => {
+            specific_fail_str!("markdown notes are imported from a folder with `import-dir`")
+        }
+        Format::TodoTxt => {
+            try!(import_todotxt(profile, path));
+            Ok(vec![])
+        }
+        Format::Org => import_org(profile, path),
+        Format::Csv | Format::Tsv => {
+            try!(import_table(profile,
+                              path,
+                              try!(separator(&args.flag_format)),
+                              &args.flag_columns,
+                              args.flag_dry_run));
+            Ok(vec![])
+        }
+    }
+}
+
+/// create or update notes from the rows of the CSV or TSV file `path`, whose
+/// first row names its columns. `columns` maps the columns to the fields of a
+/// note (see `csv::column_fields`), rows are matched to notes by their uuid.
+/// with `dry_run` what would change is printed and `profile` is left alone.
+pub fn import_table(profile: &mut Profile,
+                    path: &Path,
+                    sep: char,
+                    columns: &str,
+                    dry_run: bool)
+                    -> Result<()> {
+    let mut contents = String::new();
+    try!(try!(File::open(path)).read_to_string(&mut contents));
+    let mut rows = try!(parse_rows(&contents, sep)).into_iter();
+    let header = match rows.next() {
+        Some(h) => h,
+        None => return specific_fail!(format!("{} is empty", path.display())),
+    };
+    let fields = try!(column_fields(&header, columns));
+    if fields.iter().all(|f| f.is_none()) {
+        return specific_fail!(format!("none of the columns of {} are fields of a note, map \
+                                       them to fields with --columns",
+                                      path.display()));
+    }
+    if dry_run {
+        let mut read = vec![];
+        let mut ignored = vec![];
+        for (name, field) in header.iter().zip(fields.iter()) {
+            match *field {
+                Some(f) => read.push(format!("{} from {}", f, name)),
+                None => ignored.push(name.clone()),
+            }
+        }
+        println!("reading {}", read.join(", "));
+        if !ignored.is_empty() {
+            println!("ignoring {}", ignored.join(", "));
+        }
+    }
+
+    // rows are numbered like a spreadsheet does, the header is row 1
+    let mut notes = vec![];
+    for (i, row) in rows.enumerate() {
+        let name = format!("row {}", i + 2);
+        match row_note(&row, &fields) {
+            Ok(n) => notes.push((name, n)),
+            Err(e) => return specific_fail!(format!("{}: {}", name, e.desc)),
+        }
+    }
+    if dry_run {
+        try!(import_notes(&mut profile.clone(), notes, path, true));
+    } else {
+        try!(import_notes(profile, notes, path, false));
+    }
+    Ok(())
+}
+
+/// create or update notes from the headlines in the org file `path`, matching
+/// them to notes by their ID property, headlines marked DONE move their note to
+/// the trash. returns the file with ID properties added to the headlines of new
+/// notes, to write with `write_sources`.
+pub fn import_org(profile: &mut Profile, path: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let mut contents = String::new();
+    try!(try!(File::open(path)).read_to_string(&mut contents));
+    let notes = try!(from_org(&contents));
+    let heads: Vec<(usize, bool)> = notes.iter().map(|&(l, ref n)| (l, n.uuid.is_none())).collect();
+    let named = notes.into_iter()
+                     .map(|(line, n)| (format!("the headline on line {}", line + 1), n))
+                     .collect();
+    let ids = try!(import_notes(profile, named, path, false));
+
+    // give headlines the uuid of their new note so importing them again
+    // updates it
+    let mut added = vec![];
+    for ((line, missing), id) in heads.into_iter().zip(ids) {
+        if let (Some(id), true) = (id, missing) {
+            if let Some(note) = profile.notes.iter().find(|n| n.id == id) {
+                added.push((line, note));
+            }
+        }
+    }
+    if added.is_empty() {
+        Ok(vec![])
+    } else {
+        Ok(vec![(path.to_path_buf(), add_ids(&contents, &added))])
+    }
+}
+
+// a todo.txt date as a date in DATEFMT, or the current time if there isn't one
+fn todotxt_date(day: &Option<String>) -> Result<String> {
+    let t = match *day {
+        Some(ref d) => try!(parse_date_input(d, &now())),
+        None => now(),
+    };
+    Ok(try!(strftime(DATEFMT, &t)))
+}
+
+/// create or update notes from the tasks in the todo.txt file `path`, matching
+/// them to notes by their `uuid:`. tasks that have been done are notes tagged
+/// `done`.
+pub fn import_todotxt(profile: &mut Profile, path: &Path) -> Result<()> {
+    let mut contents = String::new();
+    try!(try!(File::open(path)).read_to_string(&mut contents));
+    let mut notes = vec![];
+    for (i, line) in contents.lines().enumerate() {
+        if let Some(task) = parse_line(line) {
+            let name = format!("line {}", i + 1);
+            match todotxt_note(&task) {
+                Ok(n) => notes.push((name, n)),
+                Err(e) => return specific_fail!(format!("{}: {}", name, e.desc)),
+            }
+        }
+    }
+    try!(import_notes(profile, notes, path, false));
+    Ok(())
+}
+
+fn todotxt_note(task: &Task) -> Result<ImportedNote> {
+    Ok(ImportedNote {
+        uuid: task.uuid.clone(),
+        id: None,
+        title: Some(task.text.clone()),
+        status: Some(task.status()),
+        last_touched: Some(try!(todotxt_date(&task.created))),
+        tags: task.tags(),
+        due: task.due.clone(),
+        body: if task.body.is_empty() {
+            None
+        } else {
+            Some(task.body.clone())
+        },
+    })
+}
+
+// the markdown files in `dir` sorted by name, along with their contents
+fn markdown_files(dir: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let mut files = vec![];
+    for entry in try!(read_dir(dir)) {
+        let path = try!(entry).path();
+        if path.is_file() && path.extension().map_or(false, |e| e == "md") {
+            let mut contents = String::new();
+            try!(try!(File::open(&path)).read_to_string(&mut contents));
+            files.push((path, contents));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned())
+}
+
+/// write each note to its own markdown file in `dir`, files from an earlier
+/// export of a note that has since been renamed are removed
+pub fn export_markdown(notes: &[Item], dir: &Path) -> Result<()> {
+    try!(create_dir_all(dir));
+    let mut existing: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for (path, contents) in try!(markdown_files(dir)) {
+        if let Ok(ImportedNote { uuid: Some(uuid), .. }) = from_markdown(&contents) {
+            existing.entry(uuid).or_insert_with(Vec::new).push(path);
+        }
+    }
+
+    for note in notes.iter() {
+        let path = dir.join(file_name(note));
+        let contents = to_markdown(note);
+        for old in existing.get(&note.uuid).map_or(&[][..], |p| &p[..]) {
+            if *old != path {
+                try!(remove_file(old));
+            }
+        }
+        // leave files that haven't changed alone, for anything syncing the folder
+        let mut current = String::new();
+        let unchanged = File::open(&path)
+                            .and_then(|mut f| f.read_to_string(&mut current))
+                            .is_ok() && current == contents;
+        if !unchanged {
+            try!(write_atomic(&path, contents.as_bytes(), false));
+        }
+    }
+    println!("exported {} notes to {}", notes.len(), dir.display());
+    Ok(())
+}
+
+/// A note read from a file in one of the formats notes are exported to, the
+/// fields that weren't in the file are None (or empty)
+#[derive(Clone, PartialEq, Debug)]
+pub struct ImportedNote {
+    pub uuid: Option<String>,
+    pub id: Option<usize>,
+    pub title: Option<String>,
+    pub status: Option<Status>,
+    pub last_touched: Option<String>,
+    pub tags: Vec<String>,
+    pub due: Option<String>,
+    pub body: Option<String>,
+}
+
+// what importing a note did
+enum Imported {
+    Added(usize),
+    Updated(usize),
+    Unchanged,
+    Skipped(&'static str),
+}
+
+// a new note for an imported note that isn't in the profile, keeping its uuid
+// so importing it again updates it
+fn new_note(profile: &Profile, imported: ImportedNote) -> Result<Item> {
+    let uuid = match imported.uuid {
+        Some(ref u) if u.len() == 36 && u.chars().all(|c| c.is_digit(16) || c == '-') => {
+            u.clone()
+        }
+        _ => new_uuid(),
+    };
+    let now = try!(strftime(DATEFMT, &now()));
+    let last_touched = match imported.last_touched {
+        Some(ref lt) if parse_last_touched(lt).is_ok() => lt.clone(),
+        _ => now,
+    };
+    let mut note = Item {
+        id: imported.id
+                    .unwrap_or_else(|| profile.notes.iter().map(|n| n.id).max().unwrap_or(0) + 1),
+        uuid: uuid,
+        title: imported.title.unwrap_or_else(String::new).replace("\n", ""),
+        status: imported.status.unwrap_or(Status::Blank),
+        body: imported.body.unwrap_or_else(String::new),
+        last_touched: last_touched,
+        tags: vec![],
+        due: try!(due_date(&imported.due, None)),
+        history: vec![],
+    };
+    note.update_tags(&imported.tags, &[]);
+    Ok(note)
+}
+
+// the due date from an imported note, `current` is kept if it is the same day
+fn due_date(due: &Option<String>, current: Option<&String>) -> Result<Option<String>> {
+    let due = match *due {
+        Some(ref d) => d,
+        None => return Ok(None),
+    };
+    if let Some(c) = current {
+        if c == due || c.starts_with(&format!("{} ", due)) {
+            return Ok(Some(c.clone()));
+        }
+    }
+    if parse_last_touched(due).is_ok() {
+        return Ok(Some(due.clone()));
+    }
+    let day = try!(parse_date_input(due, &now()));
+    Ok(Some(try!(strftime(DATEFMT, &day))))
+}
+
+// a tag with the characters some formats can't have in a tag (like spaces)
+// replaced
+fn tag_key(tag: &str) -> String {
+    tag.chars()
+       .map(|c| {
+           if c.is_alphanumeric() || c == '@' {
+               c
+           } else {
+               '_'
+           }
+       })
+       .collect()
+}
+
+// update `note` from an imported note, returns whether anything changed
+fn update_note(note: &mut Item, imported: ImportedNote) -> Result<bool> {
+    let old = note.clone();
+    if let Some(title) = imported.title {
+        note.title = title.replace("\n", "");
+    }
+    if let Some(status) = imported.status {
+        note.status = status;
+    }
+    if let Some(body) = imported.body {
+        note.body = body;
+    }
+    // keep the note's spelling of tags the format had to change
+    let tags: Vec<String> = imported.tags
+                                    .iter()
+                                    .map(|t| {
+                                        old.tags
+                                           .iter()
+                                           .find(|o| tag_key(o) == tag_key(t))
+                                           .unwrap_or(t)
+                                           .clone()
+                                    })
+                                    .collect();
+    note.tags.clear();
+    note.update_tags(&tags, &[]);
+    note.due = try!(due_date(&imported.due, old.due.as_ref()));
+    if note.title == old.title && note.status == old.status && note.body == old.body &&
+       note.tags == old.tags && note.due == old.due {
+        return Ok(false);
+    }
+    note.record_revision(&old);
+    note.last_touched = try!(strftime(DATEFMT, &now()));
+    Ok(true)
+}
+
+// add, update or trash the note an imported note is for, matching them by uuid
+fn import_note(profile: &mut Profile, imported: ImportedNote) -> Result<Imported> {
+    let uuid = imported.uuid.clone().unwrap_or_else(String::new);
+    if let Some(pos) = profile.notes.iter().position(|n| !uuid.is_empty() && n.uuid == uuid) {
+        if try!(update_note(&mut profile.notes[pos], imported)) {
+            Ok(Imported::Updated(profile.notes[pos].id))
+        } else {
+            Ok(Imported::Unchanged)
+        }
+    } else if profile.trash.iter().any(|t| !uuid.is_empty() && t.note.uuid == uuid) {
+        Ok(Imported::Skipped("the note is in the trash"))
+    } else {
+        let note = try!(new_note(profile, imported));
+        Ok(Imported::Added(profile.insert_note(note)))
+    }
+}
+
+// no two of the imported notes can be the same note
+fn check_duplicates(notes: &[(String, ImportedNote)]) -> Result<()> {
+    let mut seen: BTreeMap<&str, &str> = BTreeMap::new();
+    for &(ref name, ref imported) in notes.iter() {
+        if let Some(ref uuid) = imported.uuid {
+            if let Some(other) = seen.get(&**uuid) {
+                return specific_fail!(format!("{} and {} are both note {}", other, name, uuid));
+            }
+            seen.insert(uuid, name);
+        }
+    }
+    Ok(())
+}
+
+// import `notes`, named for messages by where they were read from, returns the
+// ids of the new notes (or None for notes that weren't added). a `preview`
+// prints what happens to each note and says what would be imported.
+fn import_notes(profile: &mut Profile,
+                notes: Vec<(String, ImportedNote)>,
+                from: &Path,
+                preview: bool)
+                -> Result<Vec<Option<usize>>> {
+    try!(check_duplicates(&notes));
+    let (mut added, mut updated) = (0, 0);
+    let mut ids = vec![];
+    for (name, imported) in notes {
+        let result = match import_note(profile, imported) {
+            Ok(r) => r,
+            Err(e) => return specific_fail!(format!("{}: {}", name, e.desc)),
+        };
+        if preview {
+            match result {
+                Imported::Added(id) => {
+                    match profile.notes.iter().find(|n| n.id == id && !n.title.is_empty()) {
+                        Some(n) => println!("{}: add note {}, {}", name, id, n.title),
+                        None => println!("{}: add note {}", name, id),
+                    }
+                }
+                Imported::Updated(id) => println!("{}: update note {}", name, id),
+                _ => {}
+            }
+        }
+        ids.push(match result {
+            Imported::Added(id) => {
+                added += 1;
+                Some(id)
+            }
+            Imported::Updated(_) => {
+                updated += 1;
+                None
+            }
+            Imported::Unchanged => None,
+            Imported::Skipped(why) => {
+                println!("skipping {}, {}", name, why);
+                None
+            }
+        });
+    }
+
+    let verb = |past: &str, now: &str| if preview { now.to_string() } else { past.to_string() };
+    let mut done = vec![];
+    if added > 0 {
+        done.push(format!("{} {} new notes", verb("imported", "import"), added));
+    }
+    if updated > 0 {
+        done.push(if done.is_empty() {
+            format!("{} {} notes", verb("updated", "update"), updated)
+        } else {
+            format!("{} {}", verb("updated", "update"), updated)
+        });
+    }
+    if preview && !done.is_empty() {
+        done[0] = format!("would {}", done[0]);
+    }
+    match done.pop() {
+        None => println!("nothing to import, the notes in {} are up to date", from.display()),
+        Some(last) if done.is_empty() => println!("{} from {}", last, from.display()),
+        Some(last) => println!("{} and {} from {}", done.join(", "), last, from.display()),
+    }
+    Ok(ids)
+}
+
+/// create or update notes from the markdown files in `dir`, files are matched
+/// to notes by the uuid in their front matter so importing a folder that was
+/// exported from the profile changes nothing. returns the new files with the
+/// uuids of their notes added, to write with `write_sources`.
+pub fn import_dir(profile: &mut Profile, dir: &Path) -> Result<Vec<(PathBuf, String)>> {
+    if !dir.is_dir() {
+        return specific_fail!(format!("{} isn't a folder", dir.display()));
+    }
+    let mut notes = vec![];
+    let mut files = vec![];
+    for (path, contents) in try!(markdown_files(dir)) {
+        let mut imported = match from_markdown(&contents) {
+            Ok(n) => n,
+            Err(e) => return specific_fail!(format!("{}: {}", display_name(&path), e.desc)),
+        };
+        // files without a title are named after it
+        if imported.title.is_none() {
+            imported.title = path.file_stem().map(|s| s.to_string_lossy().into_owned());
+        }
+        notes.push((display_name(&path), imported));
+        files.push((path, contents));
+    }
+
+    // give files the uuid of their new note so importing them again updates it
+    let missing: Vec<bool> = notes.iter()
+                                  .map(|&(_, ref n)| {
+                                      n.uuid.as_ref().map_or(true, |u| u.len() != 36)
+                                  })
+                                  .collect();
+    let ids = try!(import_notes(profile, notes, dir, false));
+    let mut sources = vec![];
+    for (((path, contents), id), missing) in files.into_iter().zip(ids).zip(missing) {
+        if let (Some(id), true) = (id, missing) {
+            if let Some(note) = profile.notes.iter().find(|n| n.id == id) {
+                sources.push((path, add_uuid(&contents, &note.uuid)));
+            }
+        }
+    }
+    Ok(sources)
+}
@@ -0,0 +1,357 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// index.rs
+//   the full-text search index of a profile, an inverted index over the
+//   titles and bodies of its notes ranked with BM25. the index of a plaintext
+//   profile is kept next to it and updated when the profile is saved, the
+//   index of an encrypted profile is only ever held in memory.
+
+// std imports
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{remove_file, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// random things
+use rustc_serialize::json::{self, decode};
+
+// crypto imports
+use crypto::sha2::Sha256;
+use crypto::digest::Digest;
+
+// theca imports
+use item::Item;
+use profile::Profile;
+use utils::write_atomic;
+use errors::{Result, Error};
+
+/// bump when the layout of the index changes so old indexes are rebuilt
+const INDEX_VERSION: u32 = 1;
+
+/// BM25 term frequency saturation
+const K1: f64 = 1.2;
+/// BM25 document length normalization
+const B: f64 = 0.75;
+
+/// A note in the index
+#[derive(RustcEncodable, RustcDecodable, Clone, PartialEq, Debug)]
+pub struct Doc {
+    /// hash of the title and body the note was indexed with
+    pub signature: String,
+    /// number of words in the title and body
+    pub len: usize,
+}
+
+/// An inverted index of the words in the titles and bodies of notes
+#[derive(RustcEncodable, RustcDecodable, Clone, PartialEq, Debug)]
+pub struct Index {
+    pub version: u32,
+    /// the indexed notes by uuid
+    pub docs: BTreeMap<String, Doc>,
+    /// for each word, the uuids of the notes it is in and its positions in each
+    pub terms: BTreeMap<String, BTreeMap<String, Vec<usize>>>,
+}
+
+/// A part of a full-text query, every part has to match for a note to be found
+#[derive(Clone, PartialEq, Debug)]
+pub enum Clause {
+    /// a word
+    Term(String),
+    /// words that start with this
+    Prefix(String),
+    /// words next to each other in this order
+    Phrase(Vec<String>),
+}
+
+/// split `text` into lowercase words, anything that isn't a letter or a number
+/// separates them
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// parse a full-text query, words are matched as they are, `word*` matches the
+/// words starting with `word` and `"some words"` matches those words in a row
+pub fn parse_query(query: &str) -> Result<Vec<Clause>> {
+    let mut clauses = vec![];
+    let mut rest = query.trim();
+    while !rest.is_empty() {
+        let (part, quoted) = if rest.starts_with('"') {
+            match rest[1..].find('"') {
+                Some(end) => {
+                    let part = &rest[1..end + 1];
+                    rest = &rest[end + 2..];
+                    (part, true)
+                }
+                None => return specific_fail_str!("a quoted phrase in the pattern isn't closed"),
+            }
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let part = &rest[..end];
+            rest = &rest[end..];
+            (part, false)
+        };
+        rest = rest.trim_left();
+
+        let prefix = !quoted && part.ends_with('*');
+        let words = tokenize(part);
+        if words.is_empty() {
+            continue;
+        }
+        clauses.push(if prefix && words.len() == 1 {
+            Clause::Prefix(words[0].clone())
+        } else if words.len() == 1 {
+            Clause::Term(words[0].clone())
+        } else {
+            Clause::Phrase(words)
+        });
+    }
+    if clauses.is_empty() {
+        return specific_fail_str!("the pattern has no words to search for");
+    }
+    Ok(clauses)
+}
+
+// the words of a note, there is a gap between the title and the body so a
+// phrase can't run from one into the other
+fn note_words(note: &Item) -> Vec<Option<String>> {
+    let mut words: Vec<Option<String>> = tokenize(&note.title).into_iter().map(Some).collect();
+    words.push(None);
+    words.extend(tokenize(&note.body).into_iter().map(Some));
+    words
+}
+
+fn signature(note: &Item) -> String {
+    let mut hasher = Sha256::new();
+    hasher.input_str(&format!("{}\n{}", note.title, note.body));
+    hasher.result_str()
+}
+
+/// path of the index kept for the profile at `profile_path`
+pub fn index_path(profile_path: &Path) -> PathBuf {
+    let name = match profile_path.file_name() {
+        Some(n) => n.to_string_lossy().into_owned(),
+        None => String::new(),
+    };
+    profile_path.with_file_name(format!(".{}.index", name))
+}
+
+impl Index {
+    pub fn new() -> Index {
+        Index {
+            version: INDEX_VERSION,
+            docs: BTreeMap::new(),
+            terms: BTreeMap::new(),
+        }
+    }
+
+    /// read the index at `path`, an empty index if there isn't one or it can't
+    /// be used, it is rebuilt by `update`
+    pub fn load(path: &Path) -> Index {
+        let mut contents = String::new();
+        match File::open(path).and_then(|mut f| f.read_to_string(&mut contents)) {
+            Ok(_) => {
+                match decode::<Index>(&contents) {
+                    Ok(ref i) if i.version == INDEX_VERSION => i.clone(),
+                    _ => Index::new(),
+                }
+            }
+            Err(_) => Index::new(),
+        }
+    }
+
+    /// write the index to `path`
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let encoded = try!(json::encode(self));
+        write_atomic(path, encoded.as_bytes(), false)
+    }
+
+    fn remove(&mut self, uuid: &str) {
+        self.docs.remove(uuid);
+        let mut empty = vec![];
+        for (term, postings) in self.terms.iter_mut() {
+            if postings.remove(uuid).is_some() && postings.is_empty() {
+                empty.push(term.clone());
+            }
+        }
+        for term in empty {
+            self.terms.remove(&term);
+        }
+    }
+
+    fn add(&mut self, note: &Item) {
+        let words = note_words(note);
+        for (pos, word) in words.iter().enumerate() {
+            if let Some(ref w) = *word {
+                self.terms
+                    .entry(w.clone())
+                    .or_insert_with(BTreeMap::new)
+                    .entry(note.uuid.clone())
+                    .or_insert_with(Vec::new)
+                    .push(pos);
+            }
+        }
+        self.docs.insert(note.uuid.clone(),
+                         Doc {
+                             signature: signature(note),
+                             len: words.len() - 1,
+                         });
+    }
+
+    /// bring the index up to date with `notes`, only the notes that were added,
+    /// changed or removed since it was last updated are (re)indexed. returns
+    /// whether anything changed.
+    pub fn update(&mut self, notes: &[Item]) -> bool {
+        let current: BTreeSet<&str> = notes.iter().map(|n| &n.uuid[..]).collect();
+        let gone: Vec<String> = self.docs
+                                    .keys()
+                                    .filter(|u| !current.contains(&u[..]))
+                                    .cloned()
+                                    .collect();
+        let mut changed = !gone.is_empty();
+        for uuid in gone {
+            self.remove(&uuid);
+        }
+        for note in notes.iter() {
+            let stale = match self.docs.get(&note.uuid) {
+                Some(d) => d.signature != signature(note),
+                None => true,
+            };
+            if stale {
+                self.remove(&note.uuid);
+                self.add(note);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    // the notes a clause matches and how many times it matches each
+    fn matches(&self, clause: &Clause) -> BTreeMap<String, usize> {
+        let mut found = BTreeMap::new();
+        match *clause {
+            Clause::Term(ref t) => {
+                if let Some(postings) = self.terms.get(t) {
+                    for (uuid, positions) in postings.iter() {
+                        found.insert(uuid.clone(), positions.len());
+                    }
+                }
+            }
+            Clause::Prefix(ref p) => {
+                for (term, postings) in self.terms.range(p.clone()..) {
+                    if !term.starts_with(&p[..]) {
+                        break;
+                    }
+                    for (uuid, positions) in postings.iter() {
+                        *found.entry(uuid.clone()).or_insert(0) += positions.len();
+                    }
+                }
+            }
+            Clause::Phrase(ref words) => {
+                let postings: Vec<&BTreeMap<String, Vec<usize>>> =
+                    match words.iter().map(|w| self.terms.get(w)).collect() {
+                        Some(p) => p,
+                        None => return found,
+                    };
+                for (uuid, starts) in postings[0].iter() {
+                    let count = starts.iter()
+                                      .filter(|&&s| {
+                                          postings[1..].iter().enumerate().all(|(i, p)| {
+                                              p.get(uuid).map_or(false, |ps| {
+                                                  ps.binary_search(&(s + i + 1)).is_ok()
+                                              })
+                                          })
+                                      })
+                                      .count();
+                    if count > 0 {
+                        found.insert(uuid.clone(), count);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// the uuids of the notes matching every clause of `query`, best match
+    /// first, along with their BM25 scores
+    pub fn search(&self, query: &[Clause]) -> Vec<(String, f64)> {
+        let n = self.docs.len() as f64;
+        if n == 0.0 {
+            return vec![];
+        }
+        let avg_len = self.docs.values().map(|d| d.len).sum::<usize>() as f64 / n;
+        let mut scores: Option<BTreeMap<String, f64>> = None;
+        for clause in query.iter() {
+            let found = self.matches(clause);
+            let df = found.len() as f64;
+            let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+            let mut next = BTreeMap::new();
+            for (uuid, tf) in found {
+                let previous = match scores {
+                    Some(ref s) => {
+                        match s.get(&uuid) {
+                            Some(s) => *s,
+                            None => continue,
+                        }
+                    }
+                    None => 0.0,
+                };
+                let len = self.docs.get(&uuid).map_or(0, |d| d.len) as f64;
+                let tf = tf as f64;
+                let score = idf * tf * (K1 + 1.0) /
+                            (tf + K1 * (1.0 - B + B * len / avg_len.max(1.0)));
+                next.insert(uuid, previous + score);
+            }
+            scores = Some(next);
+        }
+        let mut ranked: Vec<(String, f64)> = scores.unwrap_or_else(BTreeMap::new)
+                                                   .into_iter()
+                                                   .collect();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(::std::cmp::Ordering::Equal));
+        ranked
+    }
+}
+
+/// keep the index next to the profile at `profile_path` in step with it after
+/// it was saved, profiles that haven't been searched with `--full-text` yet
+/// don't have one. encrypted profiles never have their words written to disk,
+/// so any index left from before the profile was encrypted is removed.
+pub fn update_saved(profile: &Profile, profile_path: &Path) -> Result<()> {
+    let path = index_path(profile_path);
+    if !path.exists() {
+        return Ok(());
+    }
+    if profile.encrypted {
+        try!(remove_file(&path));
+        return Ok(());
+    }
+    let mut index = Index::load(&path);
+    if index.update(&profile.notes) {
+        try!(index.save(&path));
+    }
+    Ok(())
+}
+
+/// the index to search `profile` with, the one kept next to a plaintext
+/// profile (brought up to date if it has to be) or one built in memory for an
+/// encrypted profile. the caller has to hold the lock of the profile since
+/// the index on disk may be rewritten
+pub fn profile_index(profile: &Profile, profile_path: &Path) -> Result<Index> {
+    let mut index = if profile.encrypted {
+        Index::new()
+    } else {
+        Index::load(&index_path(profile_path))
+    };
+    if index.update(&profile.notes) && !profile.encrypted && profile_path.exists() {
+        try!(index.save(&index_path(profile_path)));
+    }
+    Ok(index)
+}
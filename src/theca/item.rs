@@ -1,33 +1,180 @@
+use std::cmp::min;
 use std::fmt;
 use std::iter::repeat;
 use std::io::{self, Write};
 
-use rustc_serialize::{self, Decodable, Encodable};
+use rustc_serialize::{self, Decodable, Decoder, Encodable};
 
 use lineformat::LineFormat;
-use utils::{format_field, localize_last_touched_string};
+use matcher::{write_highlighted, Matcher};
+use utils::{format_field, localize_last_touched_string, localize_due_string, legacy_uuid};
 use errors::Result;
 
 /// Represents a note within a profile
-#[derive(RustcDecodable, RustcEncodable, Clone, Debug)]
+#[derive(RustcEncodable, Clone, Debug)]
 pub struct Item {
     pub id: usize,
+    pub uuid: String,
     pub title: String,
     pub status: Status,
     pub body: String,
     pub last_touched: String,
+    pub tags: Vec<String>,
+    pub due: Option<String>,
+    pub history: Vec<Revision>,
+}
+
+/// how many earlier versions of a note are kept
+pub const HISTORY_LEN: usize = 50;
+
+/// An earlier version of a note, only the fields that were changed by the edit
+/// that replaced it are kept
+#[derive(RustcEncodable, RustcDecodable, Clone, Debug)]
+pub struct Revision {
+    pub rev: usize,
+    pub last_touched: String,
+    pub title: Option<String>,
+    pub status: Option<Status>,
+    pub body: Option<String>,
+}
+
+impl Revision {
+    /// names of the fields the next revision changed
+    pub fn changed(&self) -> Vec<&'static str> {
+        let mut changed = vec![];
+        if self.title.is_some() {
+            changed.push("title");
+        }
+        if self.status.is_some() {
+            changed.push("status");
+        }
+        if self.body.is_some() {
+            changed.push("body");
+        }
+        changed
+    }
+}
+
+// manually decoded so profiles written before a field existed still load
+impl Decodable for Item {
+    fn decode<D: Decoder>(decoder: &mut D) -> ::std::result::Result<Item, D::Error> {
+        decoder.read_struct("Item", 9usize, |d| {
+            let mut item = Item {
+                id: try!(d.read_struct_field("id", 0usize, Decodable::decode)),
+                uuid: try!(d.read_struct_field("uuid", 1usize, Option::<String>::decode))
+                          .unwrap_or_else(String::new),
+                title: try!(d.read_struct_field("title", 2usize, Decodable::decode)),
+                status: try!(d.read_struct_field("status", 3usize, Decodable::decode)),
+                body: try!(d.read_struct_field("body", 4usize, Decodable::decode)),
+                last_touched: try!(d.read_struct_field("last_touched", 5usize, Decodable::decode)),
+                tags: try!(d.read_struct_field("tags", 6usize, Option::<Vec<String>>::decode))
+                          .unwrap_or_else(Vec::new),
+                due: try!(d.read_struct_field("due", 7usize, Decodable::decode)),
+                history: try!(d.read_struct_field("history",
+                                                  8usize,
+                                                  Option::<Vec<Revision>>::decode))
+                             .unwrap_or_else(Vec::new),
+            };
+            // notes written before uuids existed get one derived from the note so
+            // it stays the same until the profile is next saved
+            if item.uuid.is_empty() {
+                item.uuid = legacy_uuid(item.id, &item.title, &item.last_touched);
+            }
+            Ok(item)
+        })
+    }
 }
 
 impl Item {
+    /// number of the current version of the note
+    pub fn revision(&self) -> usize {
+        self.history.last().map_or(1, |r| r.rev + 1)
+    }
+
+    /// number of the oldest version of the note that is still kept
+    pub fn oldest_revision(&self) -> usize {
+        self.history.first().map_or(self.revision(), |r| r.rev)
+    }
+
+    /// add `old`, the version of the note before it was edited, to the history if
+    /// the title, status or body were changed, returns whether it was added
+    pub fn record_revision(&mut self, old: &Item) -> bool {
+        let rev = Revision {
+            rev: old.revision(),
+            last_touched: old.last_touched.clone(),
+            title: if old.title != self.title { Some(old.title.clone()) } else { None },
+            status: if old.status != self.status { Some(old.status) } else { None },
+            body: if old.body != self.body { Some(old.body.clone()) } else { None },
+        };
+        if rev.changed().is_empty() {
+            return false;
+        }
+        self.history = old.history.clone();
+        self.history.push(rev);
+        if self.history.len() > HISTORY_LEN {
+            let extra = self.history.len() - HISTORY_LEN;
+            self.history.drain(..extra);
+        }
+        true
+    }
+
+    /// the note as it was at revision `rev`, if that revision is still kept
+    pub fn at_revision(&self, rev: usize) -> Option<Item> {
+        if rev < self.oldest_revision() || rev > self.revision() {
+            return None;
+        }
+        let mut note = self.clone();
+        // walk back from the current version undoing each edit
+        while let Some(r) = note.history.pop() {
+            if r.rev < rev {
+                note.history.push(r);
+                break;
+            }
+            if let Some(title) = r.title {
+                note.title = title;
+            }
+            if let Some(status) = r.status {
+                note.status = status;
+            }
+            if let Some(body) = r.body {
+                note.body = body;
+            }
+            note.last_touched = r.last_touched;
+        }
+        Some(note)
+    }
+
+    /// does the note carry every one of `tags`
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|t| self.tags.contains(t))
+    }
+
+    /// add `add` to and remove `remove` from the note tags, ignoring duplicates
+    pub fn update_tags(&mut self, add: &[String], remove: &[String]) {
+        for t in add.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            if !self.tags.iter().any(|e| e == t) {
+                self.tags.push(t.to_string());
+            }
+        }
+        self.tags.retain(|t| !remove.iter().any(|r| r.trim() == t));
+    }
+
     /// print a note as a line
-    pub fn print(&self, line_format: &LineFormat, search_body: bool) -> Result<()> {
-        self.write(&mut io::stdout(), line_format, search_body)
+    pub fn print(&self,
+                 line_format: &LineFormat,
+                 search_body: bool,
+                 highlight: Option<&Matcher>)
+                 -> Result<()> {
+        self.write(&mut io::stdout(), line_format, search_body, highlight)
     }
 
+    /// write the note as a line of a list, with the parts of the title (and
+    /// the body when `search_body` is set) that `highlight` matches highlighted
     pub fn write<T: Write>(&self,
                            output: &mut T,
                            line_format: &LineFormat,
-                           search_body: bool)
+                           search_body: bool,
+                           highlight: Option<&Matcher>)
                            -> Result<()> {
         let column_seperator: String = repeat(' ')
                                            .take(line_format.colsep)
@@ -37,14 +184,10 @@ impl Item {
                     format_field(&self.id.to_string(), line_format.id_width, false)));
         try!(write!(output, "{}", column_seperator));
         if !self.body.is_empty() && !search_body {
-            try!(write!(output,
-                        "{}",
-                        format_field(&self.title, line_format.title_width - 4, true)));
+            try!(write_title(output, &self.title, line_format.title_width - 4, highlight));
             try!(write!(output, "{}", format_field(&" (+)".to_string(), 4, false)));
         } else {
-            try!(write!(output,
-                        "{}",
-                        format_field(&self.title, line_format.title_width, true)));
+            try!(write_title(output, &self.title, line_format.title_width, highlight));
         }
         try!(write!(output, "{}", column_seperator));
         if line_format.status_width != 0 {
@@ -55,6 +198,20 @@ impl Item {
                                      false)));
             try!(write!(output, "{}", column_seperator));
         }
+        if line_format.tags_width != 0 {
+            try!(write!(output,
+                        "{}",
+                        format_field(&self.tags.join(","), line_format.tags_width, true)));
+            try!(write!(output, "{}", column_seperator));
+        }
+        if line_format.due_width != 0 {
+            let due = match self.due {
+                Some(ref d) => try!(localize_due_string(d)),
+                None => "".to_string(),
+            };
+            try!(write!(output, "{}", format_field(&due, line_format.due_width, false)));
+            try!(write!(output, "{}", column_seperator));
+        }
         try!(writeln!(output,
                       "{}",
                       format_field(&try!(localize_last_touched_string(&*self.last_touched)),
@@ -62,13 +219,48 @@ impl Item {
                                    false)));
         if search_body {
             for l in self.body.lines() {
-                try!(writeln!(output, "\t{}", l));
+                try!(write!(output, "\t"));
+                match highlight {
+                    Some(m) => try!(write_highlighted(output, l, &m.spans(l))),
+                    None => try!(write!(output, "{}", l)),
+                }
+                try!(writeln!(output, ""));
             }
         }
         Ok(())
     }
 }
 
+// write the title column, highlighting what `highlight` matches in the part of
+// the title that fits in it
+fn write_title<T: Write>(output: &mut T,
+                         title: &str,
+                         width: usize,
+                         highlight: Option<&Matcher>)
+                         -> Result<()> {
+    let field = format_field(title, width, true);
+    let m = match highlight {
+        Some(m) => m,
+        None => {
+            try!(write!(output, "{}", field));
+            return Ok(());
+        }
+    };
+    let shown = title.char_indices()
+                     .zip(field.chars())
+                     .take_while(|&((_, a), b)| a == b)
+                     .last()
+                     .map_or(0, |((i, c), _)| i + c.len_utf8());
+    let spans: Vec<_> = m.spans(title)
+                         .into_iter()
+                         .filter(|&(s, _)| s < shown)
+                         .map(|(s, e)| (s, min(e, shown)))
+                         .collect();
+    try!(write_highlighted(output, &field[..shown], &spans));
+    try!(write!(output, "{}", &field[shown..]));
+    Ok(())
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Status {
     Blank,
@@ -0,0 +1,396 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// journal.rs
+//   a per-profile journal of the changes made by each command so that they can
+//   be undone and redone. the notes in an entry are kept without their history
+//   when the note is in the profile on the other side of the command too, the
+//   history is rebuilt from that note and the revisions the command added.
+
+// random things
+use rustc_serialize::{Decodable, Decoder, Encodable};
+use rustc_serialize::json::encode;
+use time::{now, strftime};
+
+// theca imports
+use item::{Item, Revision, HISTORY_LEN};
+use profile::{Profile, Trashed, DATEFMT};
+use utils::{get_yn_input, new_uuid};
+use errors::{Result, Error};
+
+/// how many commands are kept in the journal
+pub const JOURNAL_LEN: usize = 50;
+
+/// A note before and after a command, `None` if it didn't exist
+#[derive(RustcEncodable, Clone, Debug)]
+pub struct NoteChange {
+    pub uuid: String,
+    pub before: Option<Item>,
+    pub after: Option<Item>,
+    /// the revisions the command added to the history of the note
+    pub revisions: Vec<Revision>,
+}
+
+// entries written before the revisions were kept apart have none
+impl Decodable for NoteChange {
+    fn decode<D: Decoder>(decoder: &mut D) -> ::std::result::Result<NoteChange, D::Error> {
+        decoder.read_struct("NoteChange", 4usize, |d| {
+            Ok(NoteChange {
+                uuid: try!(d.read_struct_field("uuid", 0usize, Decodable::decode)),
+                before: try!(d.read_struct_field("before", 1usize, Decodable::decode)),
+                after: try!(d.read_struct_field("after", 2usize, Decodable::decode)),
+                revisions: try!(d.read_struct_field("revisions",
+                                                    3usize,
+                                                    Option::<Vec<Revision>>::decode))
+                               .unwrap_or_else(Vec::new),
+            })
+        })
+    }
+}
+
+/// A note in the trash before and after a command, `None` if it wasn't there
+#[derive(RustcEncodable, RustcDecodable, Clone, Debug)]
+pub struct TrashChange {
+    pub uuid: String,
+    pub before: Option<Trashed>,
+    pub after: Option<Trashed>,
+}
+
+/// The entry for the other half of a command that changed two profiles
+#[derive(RustcEncodable, RustcDecodable, Clone, Debug)]
+pub struct Link {
+    pub profile: String,
+    pub entry: String,
+}
+
+/// The changes made to a profile by one command
+#[derive(RustcEncodable, RustcDecodable, Clone, Debug)]
+pub struct Entry {
+    pub id: usize,
+    pub uuid: String,
+    pub time: String,
+    pub command: String,
+    pub undone: bool,
+    /// whether the profile is encrypted after the command, if it changed
+    pub encrypted: Option<bool>,
+    pub notes: Vec<NoteChange>,
+    pub trash: Vec<TrashChange>,
+    pub link: Option<Link>,
+}
+
+/// What `theca log` prints for an entry
+#[derive(RustcEncodable, Clone, Debug)]
+pub struct LogLine {
+    pub id: usize,
+    pub time: String,
+    pub command: String,
+    pub undone: bool,
+}
+
+// compare two values by their JSON encoding, Item doesn't implement PartialEq
+fn same<T: Encodable>(a: &T, b: &T) -> bool {
+    match (encode(a), encode(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// the history of the note `uuid` in `notes` or `trash`, if it's in either
+fn history_of(notes: &[Item], trash: &[Trashed], uuid: &str) -> Option<Vec<Revision>> {
+    notes.iter()
+         .find(|n| n.uuid == uuid)
+         .or_else(|| trash.iter().map(|t| &t.note).find(|n| n.uuid == uuid))
+         .map(|n| n.history.clone())
+}
+
+// `note` without its history if `keep` isn't set
+fn snapshot(mut note: Item, keep: bool) -> Item {
+    if !keep {
+        note.history = vec![];
+    }
+    note
+}
+
+// the note `note` from an entry with its history, a note kept without one gets
+// the history of the note in the profile now (`current`) with the revisions the
+// command added put back when redoing or taken off when undoing
+fn with_history(mut note: Item,
+                current: Option<&Vec<Revision>>,
+                added: &[Revision],
+                forward: bool)
+                -> Item {
+    if !note.history.is_empty() {
+        return note;
+    }
+    if let Some(current) = current {
+        let first = added.first().map(|r| r.rev);
+        note.history = current.iter()
+                              .filter(|r| first.map_or(true, |f| r.rev < f))
+                              .cloned()
+                              .collect();
+        if forward {
+            note.history.extend(added.iter().cloned());
+            if note.history.len() > HISTORY_LEN {
+                let extra = note.history.len() - HISTORY_LEN;
+                note.history.drain(..extra);
+            }
+        }
+    }
+    note
+}
+
+// the uuids that are in `before` or `after` along with their values on each side
+// where those differ
+fn changed<T, F>(before: &[T], after: &[T], uuid: F) -> Vec<(String, Option<T>, Option<T>)>
+    where T: Clone + Encodable,
+          F: Fn(&T) -> &str
+{
+    let mut changes = vec![];
+    for b in before.iter() {
+        let a = after.iter().find(|a| uuid(a) == uuid(b)).cloned();
+        let b = Some(b.clone());
+        if !same(&b, &a) {
+            changes.push((uuid(b.as_ref().unwrap()).to_string(), b, a));
+        }
+    }
+    for a in after.iter() {
+        if !before.iter().any(|b| uuid(b) == uuid(a)) {
+            changes.push((uuid(a).to_string(), None, Some(a.clone())));
+        }
+    }
+    changes
+}
+
+impl Entry {
+    /// the changes made to the profile `before` to get to `after`, or `None` if
+    /// nothing was changed
+    pub fn between(command: &str, before: &Profile, after: &Profile) -> Result<Option<Entry>> {
+        // the history of a note only has to be kept if nothing else can give it back
+        let in_before = |uuid: &str| history_of(&before.notes, &before.trash, uuid).is_some();
+        let in_after = |uuid: &str| history_of(&after.notes, &after.trash, uuid).is_some();
+        let notes: Vec<NoteChange> = changed(&before.notes, &after.notes, |n| &n.uuid)
+                                         .into_iter()
+                                         .map(|(uuid, b, a)| {
+                                             let revisions = match (&b, &a) {
+                                                 (&Some(ref b), &Some(ref a)) => {
+                                                     a.history
+                                                      .iter()
+                                                      .filter(|r| r.rev >= b.revision())
+                                                      .cloned()
+                                                      .collect()
+                                                 }
+                                                 _ => vec![],
+                                             };
+                                             NoteChange {
+                                                 before: b.map(|n| snapshot(n, !in_after(&uuid))),
+                                                 after: a.map(|n| snapshot(n, !in_before(&uuid))),
+                                                 uuid: uuid,
+                                                 revisions: revisions,
+                                             }
+                                         })
+                                         .collect();
+        let trash: Vec<TrashChange> = changed(&before.trash, &after.trash, |t| &t.note.uuid)
+                                          .into_iter()
+                                          .map(|(uuid, b, a)| {
+                                              TrashChange {
+                                                  before: b.map(|mut t| {
+                                                      t.note = snapshot(t.note, !in_after(&uuid));
+                                                      t
+                                                  }),
+                                                  after: a.map(|mut t| {
+                                                      t.note = snapshot(t.note, !in_before(&uuid));
+                                                      t
+                                                  }),
+                                                  uuid: uuid,
+                                              }
+                                          })
+                                          .collect();
+        let encrypted = if before.encrypted != after.encrypted {
+            Some(after.encrypted)
+        } else {
+            None
+        };
+        if notes.is_empty() && trash.is_empty() && encrypted.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Entry {
+            id: 0,
+            uuid: new_uuid(),
+            time: try!(strftime(DATEFMT, &now())),
+            command: command.to_string(),
+            undone: false,
+            encrypted: encrypted,
+            notes: notes,
+            trash: trash,
+            link: None,
+        }))
+    }
+
+    /// a line for `theca log`
+    pub fn log_line(&self) -> LogLine {
+        LogLine {
+            id: self.id,
+            time: self.time.clone(),
+            command: self.command.clone(),
+            undone: self.undone,
+        }
+    }
+}
+
+/// the uuids of the notes in `before` that aren't in `after` at all, not even in
+/// the trash
+pub fn purged(before: &Profile, after: &Profile) -> Vec<String> {
+    before.notes
+          .iter()
+          .chain(before.trash.iter().map(|t| &t.note))
+          .filter(|n| history_of(&after.notes, &after.trash, &n.uuid).is_none())
+          .map(|n| n.uuid.clone())
+          .collect()
+}
+
+/// take the notes with `uuids`, which have been deleted for good, out of every
+/// entry so nothing of them is left in the profile. entries that are left with
+/// nothing to undo are dropped
+pub fn forget(journal: &mut Vec<Entry>, uuids: &[String]) {
+    if uuids.is_empty() {
+        return;
+    }
+    for e in journal.iter_mut() {
+        e.notes.retain(|c| !uuids.contains(&c.uuid));
+        e.trash.retain(|c| !uuids.contains(&c.uuid));
+    }
+    journal.retain(|e| !e.notes.is_empty() || !e.trash.is_empty() || e.encrypted.is_some());
+}
+
+/// add an entry to the journal, anything that was undone can't be redone after
+/// this so it is dropped
+pub fn record(journal: &mut Vec<Entry>, mut entry: Entry) {
+    while journal.last().map_or(false, |e| e.undone) {
+        journal.pop();
+    }
+    entry.id = journal.last().map_or(1, |e| e.id + 1);
+    journal.push(entry);
+    if journal.len() > JOURNAL_LEN {
+        let extra = journal.len() - JOURNAL_LEN;
+        journal.drain(..extra);
+    }
+}
+
+/// position of the entry `theca undo` would undo
+pub fn undo_pos(journal: &[Entry]) -> Option<usize> {
+    journal.iter().rposition(|e| !e.undone)
+}
+
+/// position of the entry `theca redo` would redo
+pub fn redo_pos(journal: &[Entry]) -> Option<usize> {
+    let next = undo_pos(journal).map_or(0, |p| p + 1);
+    if next < journal.len() {
+        Some(next)
+    } else {
+        None
+    }
+}
+
+/// undo (or redo if `forward` is set) the changes in `entry`, asking before
+/// throwing away changes made since unless `yes` is set
+pub fn apply(profile: &mut Profile, entry: &Entry, forward: bool, yes: bool) -> Result<()> {
+    // check the notes are still how the command left them, leaving out the
+    // history which the entry may not have
+    let mut changed_since = vec![];
+    for c in entry.notes.iter() {
+        let expected = if forward { &c.before } else { &c.after };
+        let expected = expected.clone().map(|n| snapshot(n, false));
+        let current = profile.notes.iter().find(|n| n.uuid == c.uuid).cloned();
+        let current = current.map(|n| snapshot(n, false));
+        if !same(&current, &expected) {
+            let id = current.as_ref().or(expected.as_ref()).map_or(0, |n| n.id);
+            changed_since.push(id.to_string());
+        }
+    }
+    for c in entry.trash.iter() {
+        let expected = if forward { &c.before } else { &c.after };
+        let expected = expected.clone().map(|mut t| {
+            t.note = snapshot(t.note, false);
+            t
+        });
+        let current = profile.trash.iter().find(|t| t.note.uuid == c.uuid).cloned();
+        let current = current.map(|mut t| {
+            t.note = snapshot(t.note, false);
+            t
+        });
+        if !same(&current, &expected) {
+            let id = current.as_ref().or(expected.as_ref()).map_or(0, |t| t.note.id);
+            changed_since.push(format!("{} (in the trash)", id));
+        }
+    }
+    if !changed_since.is_empty() && !yes {
+        let message = format!("note {} changed after '{}', {} it anyway?\n",
+                              changed_since.join(", "),
+                              entry.command,
+                              if forward { "redo" } else { "undo" });
+        if !try!(get_yn_input(&message)) {
+            return specific_fail_str!("ok bye ♥");
+        }
+    }
+
+    // the history of each note before any of them are replaced
+    let histories: Vec<Option<Vec<Revision>>> =
+        entry.notes
+             .iter()
+             .map(|c| &c.uuid)
+             .chain(entry.trash.iter().map(|c| &c.uuid))
+             .map(|uuid| history_of(&profile.notes, &profile.trash, uuid))
+             .collect();
+    let (note_histories, trash_histories) = histories.split_at(entry.notes.len());
+
+    for (c, history) in entry.notes.iter().zip(note_histories) {
+        profile.notes.retain(|n| n.uuid != c.uuid);
+        if let Some(note) = if forward { c.after.clone() } else { c.before.clone() } {
+            profile.insert_note(with_history(note, history.as_ref(), &c.revisions, forward));
+        }
+    }
+    for (c, history) in entry.trash.iter().zip(trash_histories) {
+        profile.trash.retain(|t| t.note.uuid != c.uuid);
+        if let Some(mut t) = if forward { c.after.clone() } else { c.before.clone() } {
+            t.note = with_history(t.note, history.as_ref(), &[], forward);
+            profile.trash.push(t);
+        }
+    }
+    if let Some(e) = entry.encrypted {
+        profile.encrypted = if forward { e } else { !e };
+    }
+    Ok(())
+}
+
+/// three-way merge the journal changed here (`ours`) and on disk (`theirs`)
+/// since it was loaded (`base`)
+pub fn merge_journal(base: &[Entry], ours: &[Entry], theirs: &[Entry]) -> Vec<Entry> {
+    // entries dropped here were undone before another command was run
+    let mut journal: Vec<Entry> = theirs.iter()
+                                        .filter(|t| {
+                                            !base.iter().any(|b| b.uuid == t.uuid) ||
+                                            ours.iter().any(|o| o.uuid == t.uuid)
+                                        })
+                                        .cloned()
+                                        .collect();
+    // entries undone or redone here
+    for o in ours.iter() {
+        if let Some(b) = base.iter().find(|b| b.uuid == o.uuid) {
+            if b.undone != o.undone {
+                if let Some(t) = journal.iter_mut().find(|t| t.uuid == o.uuid) {
+                    t.undone = o.undone;
+                }
+            }
+        }
+    }
+    // and the ones added here
+    for o in ours.iter().filter(|o| !base.iter().any(|b| b.uuid == o.uuid)) {
+        record(&mut journal, o.clone());
+    }
+    journal
+}
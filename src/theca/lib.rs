@@ -24,18 +24,28 @@ extern crate crypto;
 extern crate term;
 extern crate rand;
 extern crate tempdir;
+extern crate toml;
+extern crate unicode_width;
+extern crate unicode_segmentation;
 
 // std lib imports
-use std::env;
 use std::default::Default;
+use std::path::Path;
+use std::io::{stderr, Write};
 
 // theca imports
-use utils::{find_profile_folder, get_password, profiles_in_folder, profile_fingerprint,
-            extract_status};
-use errors::Result;
+use utils::{find_profile_folder, get_password, get_password_with_prompt, profiles_in_folder,
+            profile_names, profile_fingerprint, extract_status, filter_notes, parse_date_arg,
+            profile_kdf};
+use errors::{Result, Error};
+use crypt::Kdf;
+use lock::ProfileLock;
+use journal::Entry;
+use config::{color_output, Config, Effective};
+use matcher::Matcher;
 
 pub use self::libc::{STDIN_FILENO, STDOUT_FILENO, STDERR_FILENO};
-pub use profile::Profile;
+pub use profile::{Profile, Snapshot};
 
 #[macro_use]pub mod errors;
 pub mod profile;
@@ -43,6 +53,24 @@ pub mod item;
 pub mod lineformat;
 pub mod utils;
 pub mod crypt;
+pub mod lock;
+pub mod merge;
+pub mod diff;
+pub mod journal;
+pub mod config;
+pub mod alias;
+pub mod tui;
+pub mod readline;
+pub mod shell;
+pub mod markdown;
+pub mod todotxt;
+pub mod org;
+pub mod csv;
+pub mod output;
+pub mod index;
+pub mod query;
+pub mod matcher;
+pub mod export;
 
 /// Current version of theca
 pub fn version() -> String {
@@ -53,38 +81,85 @@ pub fn version() -> String {
 #[derive(RustcDecodable, Clone)]
 pub struct Args {
     pub cmd_add: bool,
+    pub cmd_agenda: bool,
     pub cmd_clear: bool,
+    pub cmd_config: bool,
     pub cmd_del: bool,
     pub cmd_decrypt_profile: bool,
+    pub cmd_diff: bool,
     pub cmd_edit: bool,
     pub cmd_encrypt_profile: bool,
+    pub cmd_export: bool,
+    pub cmd_history: bool,
     pub cmd_import: bool,
+    pub cmd_import_dir: bool,
     pub cmd_info: bool,
     pub cmd_list_profiles: bool,
+    pub cmd_log: bool,
     pub cmd_new_profile: bool,
+    pub cmd_q: bool,
+    pub cmd_redo: bool,
+    pub cmd_restore: bool,
+    pub cmd_revert: bool,
     pub cmd_search: bool,
+    pub cmd_shell: bool,
+    pub cmd_tags: bool,
     pub cmd_transfer: bool,
+    pub cmd_trash: bool,
+    pub cmd_tui: bool,
+    pub cmd_undo: bool,
     pub cmd__: bool,
-    pub arg_id: Vec<usize>,
+    pub arg_dir: String,
+    pub arg_file: String,
+    pub arg_id: Vec<String>,
     pub arg_name: Vec<String>,
+    pub arg_path: String,
     pub arg_pattern: String,
+    pub arg_rev: Vec<String>,
     pub arg_title: String,
+    pub flag_all_profiles: bool,
+    pub flag_backup: bool,
     pub flag_body: Vec<String>,
+    pub flag_color: String,
+    pub flag_columns: String,
     pub flag_condensed: bool,
     pub flag_datesort: bool,
+    pub flag_dry_run: bool,
+    pub flag_due: String,
+    pub flag_due_after: String,
+    pub flag_due_before: String,
     pub flag_editor: bool,
+    pub flag_empty: bool,
     pub flag_encrypted: bool,
+    pub flag_format: String,
+    pub flag_full_text: bool,
+    pub flag_fuzzy: bool,
+    pub flag_ignore_case: bool,
     pub flag_json: bool,
+    pub flag_kdf_cost: String,
     pub flag_key: String,
     pub flag_limit: usize,
+    pub flag_lock_timeout: u64,
     pub flag_new_key: String,
+    pub flag_no_backup: bool,
+    pub flag_no_condensed: bool,
+    pub flag_no_datesort: bool,
+    pub flag_no_due: bool,
+    pub flag_no_reverse: bool,
     pub flag_none: bool,
     pub flag_profile: String,
     pub flag_profile_folder: String,
+    pub flag_purge: bool,
+    pub flag_query: String,
     pub flag_regex: bool,
     pub flag_reverse: bool,
     pub flag_search_body: bool,
+    pub flag_skip_encrypted: bool,
     pub flag_started: bool,
+    pub flag_tag: Vec<String>,
+    pub flag_template: String,
+    pub flag_trash_days: String,
+    pub flag_untag: Vec<String>,
     pub flag_urgent: bool,
     pub flag_version: bool,
     pub flag_yes: bool,
@@ -92,13 +167,18 @@ pub struct Args {
 
 pub struct BoolFlags {
     pub condensed: bool,
+    pub csv: bool,
     pub datesort: bool,
     pub editor: bool,
     pub encrypted: bool,
+    pub full_text: bool,
+    pub fuzzy: bool,
+    pub ignore_case: bool,
     pub json: bool,
     pub regex: bool,
     pub reverse: bool,
     pub search_body: bool,
+    pub tsv: bool,
     pub yes: bool,
 }
 
@@ -106,13 +186,18 @@ impl BoolFlags {
     pub fn from_args(args: &Args) -> BoolFlags {
         BoolFlags {
             condensed: args.flag_condensed,
+            csv: args.flag_format.to_lowercase() == "csv",
             datesort: args.flag_datesort,
             editor: args.flag_editor,
             encrypted: args.flag_encrypted,
+            full_text: args.flag_full_text,
+            fuzzy: args.flag_fuzzy,
+            ignore_case: args.flag_ignore_case,
             json: args.flag_json,
             regex: args.flag_regex,
             reverse: args.flag_reverse,
             search_body: args.flag_search_body,
+            tsv: args.flag_format.to_lowercase() == "tsv",
             yes: args.flag_yes,
         }
     }
@@ -122,30 +207,36 @@ impl Default for BoolFlags {
     fn default() -> BoolFlags {
         BoolFlags {
             condensed: false,
+            csv: false,
             datesort: false,
             editor: false,
             encrypted: false,
+            full_text: false,
+            fuzzy: false,
+            ignore_case: false,
             json: false,
             regex: false,
             reverse: false,
             search_body: false,
+            tsv: false,
             yes: false,
         }
     }
 }
 
-pub fn setup_args(args: &mut Args) -> Result<()> {
-    if let Ok(val) = env::var("THECA_DEFAULT_PROFILE") {
-        if args.flag_profile.is_empty() && !val.is_empty() {
-            args.flag_profile = val;
-        }
-    }
+/// fill in the settings that weren't given on the command line, the command
+/// line takes precedence over the environment, which takes precedence over the
+/// config file
+pub fn resolve_settings(args: &mut Args) -> Result<()> {
+    let config = try!(Config::load());
+    let effective = try!(Effective::resolve(args, &config));
+    effective.apply(args);
+    args.flag_template = config.template(&args.flag_template);
+    Ok(())
+}
 
-    if let Ok(val) = env::var("THECA_PROFILE_FOLDER") {
-        if args.flag_profile_folder.is_empty() && !val.is_empty() {
-            args.flag_profile_folder = val;
-        }
-    }
+pub fn setup_args(args: &mut Args) -> Result<()> {
+    try!(resolve_settings(args));
 
     // if key is provided but --encrypted not set, it prob should be
     if !args.flag_key.is_empty() && !args.flag_encrypted {
@@ -157,18 +248,143 @@ pub fn setup_args(args: &mut Args) -> Result<()> {
         args.flag_key = try!(get_password());
     }
 
-    // if no profile is provided via cmd line or env set it to default
-    if args.flag_profile.is_empty() {
-        args.flag_profile = "default".to_string();
-    }
+    Ok(())
+}
 
+/// print the effective settings and where they came from, for `theca config`
+pub fn print_config(args: &Args) -> Result<()> {
+    let config = try!(Config::load());
+    let effective = try!(Effective::resolve(args, &config));
+    // so --color is used when printing them
+    effective.apply(&mut args.clone());
+    effective.print(args.flag_json)
+}
 
-    Ok(())
+// a short description of a command for the journal
+fn describe_cmd(args: &Args) -> String {
+    let ids = args.arg_id.join(" ");
+    if args.cmd_add {
+        format!("add {:?}", args.arg_title)
+    } else if args.cmd_edit {
+        format!("edit {}", ids)
+    } else if args.cmd_del {
+        format!("del {}", ids)
+    } else if args.cmd_clear && args.flag_purge {
+        "clear --purge".to_string()
+    } else if args.cmd_clear {
+        "clear".to_string()
+    } else if args.cmd_restore {
+        format!("restore {}", ids)
+    } else if args.cmd_revert {
+        format!("revert {} {}", ids, args.arg_rev.join(" "))
+    } else if args.cmd_trash {
+        "trash --empty".to_string()
+    } else if args.cmd_import {
+        format!("import --format {} {}", args.flag_format, args.arg_file)
+    } else if args.cmd_import_dir {
+        format!("import-dir {}", args.arg_dir)
+    } else if args.cmd_encrypt_profile {
+        "encrypt-profile".to_string()
+    } else if args.cmd_decrypt_profile {
+        "decrypt-profile".to_string()
+    } else {
+        String::new()
+    }
 }
 
-pub fn parse_cmds(profile: &mut Profile, args: &mut Args, profile_fingerprint: &u64) -> Result<()> {
+/// search every profile in the profile folder, for `search --all-profiles`.
+/// the key of each encrypted profile is asked for (unless `--key` is given or
+/// `--skip-encrypted` skips them) and a profile that can't be opened is
+/// skipped with a warning.
+pub fn search_all_profiles(args: &Args) -> Result<()> {
     let status = try!(extract_status(args.flag_none, args.flag_started, args.flag_urgent));
     let flags = BoolFlags::from_args(args);
+    if !args.flag_format.is_empty() {
+        try!(csv::separator(&args.flag_format));
+    }
+    let query = try!(query::parse_query_arg(&args.flag_query));
+    if args.arg_pattern.is_empty() && query.is_none() {
+        return specific_fail_str!("search for a pattern, a --query or both");
+    }
+    let highlight = if !args.flag_full_text && color_output() {
+        Some(try!(Matcher::new(&args.arg_pattern, &flags)))
+    } else {
+        None
+    };
+    let out = try!(output::formatter(&flags, &args.flag_template, highlight));
+
+    let folder = try!(find_profile_folder(&args.flag_profile_folder));
+    let mut hits = vec![];
+    for (name, encrypted) in try!(profile_names(&folder)) {
+        if encrypted && args.flag_skip_encrypted {
+            continue;
+        }
+        let key = if !encrypted {
+            String::new()
+        } else if !args.flag_key.is_empty() {
+            args.flag_key.clone()
+        } else {
+            try!(get_password_with_prompt(&format!("Key for {}: ", name)))
+        };
+        let mut notes = {
+            // held while searching too, a full-text search can bring the
+            // index kept next to the profile up to date
+            let _lock = try!(ProfileLock::acquire(&name,
+                                                  &args.flag_profile_folder,
+                                                  args.flag_lock_timeout));
+            let profile = match Profile::new(&name,
+                                             &args.flag_profile_folder,
+                                             &key,
+                                             false,
+                                             encrypted,
+                                             args.flag_yes) {
+                Ok((profile, _)) => profile,
+                Err(e) => {
+                    try!(writeln!(stderr(), "skipping the profile {}: {}", name, e.desc));
+                    continue;
+                }
+            };
+            if args.flag_full_text {
+                let path = folder.join(format!("{}.json", name));
+                let index = try!(index::profile_index(&profile, &path));
+                try!(profile.rank_notes(&index, &args.arg_pattern, &flags))
+            } else {
+                try!(profile.find_notes(&args.arg_pattern, &flags))
+            }
+        };
+        filter_notes(&mut notes,
+                     args.flag_limit,
+                     &flags,
+                     status,
+                     &args.flag_tag,
+                     None,
+                     None,
+                     query.as_ref());
+        if !notes.is_empty() {
+            hits.push((name, notes));
+        }
+    }
+    if hits.is_empty() {
+        return out.empty("nothing found");
+    }
+    out.hits(&hits)
+}
+
+pub fn parse_cmds(profile: &mut Profile, args: &mut Args, snapshot: &Snapshot) -> Result<()> {
+    let status = try!(extract_status(args.flag_none, args.flag_started, args.flag_urgent));
+    let flags = BoolFlags::from_args(args);
+    // --format picks csv or tsv for the commands that list notes
+    if !args.flag_format.is_empty() && !args.cmd_export && !args.cmd_import {
+        try!(csv::separator(&args.flag_format));
+    }
+    // highlight what a search matched when printing to a terminal
+    let highlight = if args.cmd_search && !args.flag_full_text && color_output() {
+        Some(try!(Matcher::new(&args.arg_pattern, &flags)))
+    } else {
+        None
+    };
+    let out = try!(output::formatter(&flags, &args.flag_template, highlight));
+    let query = try!(query::parse_query_arg(&args.flag_query));
 
     if [args.cmd_add,
         args.cmd_edit,
@@ -177,32 +393,80 @@ pub fn parse_cmds(profile: &mut Profile, args: &mut Args, profile_fingerprint: &
         args.cmd_decrypt_profile,
         args.cmd_transfer,
         args.cmd_clear,
+        args.cmd_restore,
+        args.cmd_revert,
+        args.cmd_trash && args.flag_empty,
+        args.cmd_undo,
+        args.cmd_redo,
+        args.cmd_import && !args.arg_file.is_empty() && !args.flag_dry_run,
+        args.cmd_import_dir,
         args.cmd_new_profile]
            .iter()
            .any(|c| c == &true) {
+        let before = profile.clone();
+        // files imported from that get the uuids of their new notes once the
+        // profile has been saved
+        let mut sources = vec![];
+
         // add
         if args.cmd_add {
+            let due = try!(parse_date_arg(&args.flag_due));
             try!(profile.add_note(&args.arg_title,
                                   &args.flag_body,
                                   status,
                                   args.cmd__,
                                   args.flag_editor,
                                   true));
+            let id = profile.notes.last().map_or(0, |n| n.id);
+            if !args.flag_tag.is_empty() {
+                try!(profile.tag_note(id, &args.flag_tag, &[]));
+            }
+            if due.is_some() {
+                try!(profile.set_due(id, due));
+            }
         }
 
         // edit
         if args.cmd_edit {
-            try!(profile.edit_note(args.arg_id[0],
+            let id = try!(profile.find_id(&args.arg_id[0]));
+            let due = try!(parse_date_arg(&args.flag_due));
+            try!(profile.edit_note(id,
                                    &args.arg_title,
                                    &args.flag_body,
                                    status,
                                    args.cmd__,
                                    flags));
+            if !args.flag_tag.is_empty() || !args.flag_untag.is_empty() {
+                try!(profile.tag_note(id, &args.flag_tag, &args.flag_untag));
+            }
+            if args.flag_no_due || due.is_some() {
+                try!(profile.set_due(id, due));
+            }
         }
 
         // delete
         if args.cmd_del {
-            profile.delete_note(&args.arg_id);
+            let ids = try!(args.arg_id
+                               .iter()
+                               .map(|i| profile.find_id(i))
+                               .collect::<Result<Vec<usize>>>());
+            try!(profile.delete_note(&ids));
+        }
+
+        // revert
+        if args.cmd_revert {
+            let id = try!(profile.find_id(&args.arg_id[0]));
+            try!(profile.revert_note(id, &args.arg_rev[0]));
+        }
+
+        // restore
+        if args.cmd_restore {
+            try!(profile.restore_note(&args.arg_id[0]));
+        }
+
+        // empty the trash
+        if args.cmd_trash && args.flag_empty {
+            try!(profile.empty_trash(args.flag_yes));
         }
 
         // transfer
@@ -211,9 +475,19 @@ pub fn parse_cmds(profile: &mut Profile, args: &mut Args, profile_fingerprint: &
             try!(profile.transfer_note(args));
         }
 
+        // import a file exported by something else
+        if args.cmd_import {
+            sources = try!(export::import(profile, args));
+        }
+
+        // import a folder of markdown files
+        if args.cmd_import_dir {
+            sources = try!(export::import_dir(profile, Path::new(&args.arg_dir)));
+        }
+
         // clear
         if args.cmd_clear {
-            try!(profile.clear(args.flag_yes));
+            try!(profile.clear(args.flag_yes, args.flag_purge));
         }
 
         // decrypt profile
@@ -228,6 +502,11 @@ pub fn parse_cmds(profile: &mut Profile, args: &mut Args, profile_fingerprint: &
         // FIXME: should test how this interacts with save_to_file when the profile has
         //        changed during execution
         if args.cmd_encrypt_profile {
+            // check the kdf cost before asking for a key
+            if !args.flag_kdf_cost.is_empty() {
+                try!(Kdf::from_cost_arg(&args.flag_kdf_cost));
+            }
+
             // get the new key
             if args.flag_new_key.is_empty() {
                 args.flag_new_key = try!(get_password());
@@ -242,21 +521,86 @@ pub fn parse_cmds(profile: &mut Profile, args: &mut Args, profile_fingerprint: &
             println!("encrypting '{}'", args.flag_profile);
         }
 
+        // undo and redo
+        if args.cmd_undo || args.cmd_redo {
+            if args.cmd_undo {
+                try!(profile.undo(args));
+            } else {
+                try!(profile.redo(args));
+            }
+            // undoing decrypt-profile needs a key to encrypt the profile with again
+            if profile.encrypted && args.flag_key.is_empty() {
+                args.flag_key = try!(get_password());
+            }
+        }
+
         // new profile
         if args.cmd_new_profile {
             if args.cmd_new_profile && args.arg_name.is_empty() {
-                args.arg_name.push("default".to_string())
+                args.arg_name.push(args.flag_profile.clone())
             }
             println!("creating profile '{}'", args.arg_name[0]);
         }
 
-        try!(profile.save_to_file(args, profile_fingerprint));
-    } else if !args.arg_id.is_empty() {
-        try!(profile.view_note(args.arg_id[0], args.flag_json, args.flag_condensed));
+        // record the changes in the journal, transfer records them itself since
+        // it changes two profiles
+        if !args.cmd_undo && !args.cmd_redo && !args.cmd_transfer && !args.cmd_new_profile {
+            if let Some(entry) = try!(Entry::between(&describe_cmd(args), &before, profile)) {
+                journal::record(&mut profile.journal, entry);
+            }
+            // clear --purge and trash --empty can't be undone, the notes they
+            // deleted mustn't be kept in the journal
+            let purged = journal::purged(&before, profile);
+            journal::forget(&mut profile.journal, &purged);
+        }
+
+        try!(profile.save_to_file(args, snapshot));
+        try!(export::write_sources(&sources));
+    } else if args.cmd_history {
+        let id = try!(profile.find_id(&args.arg_id[0]));
+        try!(profile.note_history(id, args.flag_json));
+    } else if args.cmd_diff {
+        let id = try!(profile.find_id(&args.arg_id[0]));
+        try!(profile.diff_note(id, &args.arg_rev));
+    } else if !args.arg_id.is_empty() && !args.cmd_import {
+        let id = try!(profile.find_id(&args.arg_id[0]));
+        try!(profile.view_note(id, &*out));
+    } else if args.cmd_search && args.flag_full_text {
+        let mut profile_path = try!(find_profile_folder(&args.flag_profile_folder));
+        profile_path.push(&(args.flag_profile.to_string() + ".json"));
+        let index = try!(index::profile_index(profile, &profile_path));
+        try!(profile.search_full_text(&index,
+                                      &args.arg_pattern,
+                                      args.flag_limit,
+                                      flags,
+                                      status,
+                                      &args.flag_tag,
+                                      query.as_ref(),
+                                      &*out));
     } else if args.cmd_search {
-        try!(profile.search_notes(&args.arg_pattern, args.flag_limit, flags, status));
+        try!(profile.search_notes(&args.arg_pattern,
+                                  args.flag_limit,
+                                  flags,
+                                  status,
+                                  &args.flag_tag,
+                                  query.as_ref(),
+                                  &*out));
+    } else if args.cmd_agenda {
+        try!(profile.print_agenda(flags));
+    } else if args.cmd_tags {
+        try!(profile.list_tags(args.flag_json));
+    } else if args.cmd_export {
+        try!(export::export(profile, args));
+    } else if args.cmd_log {
+        try!(profile.print_log(args.flag_limit, args.flag_json));
+    } else if args.cmd_trash {
+        try!(profile.list_trash(args.flag_limit, flags, &*out));
     } else if args.cmd_info {
-        try!(profile.stats(&args.flag_profile));
+        let mut profile_path = try!(find_profile_folder(&args.flag_profile_folder));
+        profile_path.push(&(args.flag_profile.to_string() + ".json"));
+        try!(profile.stats(&args.flag_profile, try!(profile_kdf(&profile_path)), &*out));
+    } else if args.cmd_import && args.flag_dry_run {
+        try!(export::import(profile, args));
     } else if args.cmd_import {
         // reverse(?) transfer a note
         let mut from_args = args.clone();
@@ -265,7 +609,10 @@ pub fn parse_cmds(profile: &mut Profile, args: &mut Args, profile_fingerprint: &
         from_args.flag_profile = args.arg_name[0].clone();
         from_args.arg_name[0] = args.flag_profile.clone();
 
-        let (mut from_profile, from_fingerprint) = try!(Profile::new(
+        let _from_lock = try!(ProfileLock::acquire(&from_args.flag_profile,
+                                                   &from_args.flag_profile_folder,
+                                                   from_args.flag_lock_timeout));
+        let (mut from_profile, from_snapshot) = try!(Profile::new(
                 &from_args.flag_profile,
                 &from_args.flag_profile_folder,
                 &from_args.flag_key,
@@ -274,12 +621,21 @@ pub fn parse_cmds(profile: &mut Profile, args: &mut Args, profile_fingerprint: &
                 from_args.flag_yes
             ));
 
-        try!(parse_cmds(&mut from_profile, &mut from_args, &from_fingerprint));
+        try!(parse_cmds(&mut from_profile, &mut from_args, &from_snapshot));
     } else if args.cmd_list_profiles {
         let profile_path = try!(find_profile_folder(&args.flag_profile_folder));
         try!(profiles_in_folder(&profile_path));
     } else if args.arg_id.is_empty() {
-        try!(profile.list_notes(args.flag_limit, flags, status));
+        let due_before = try!(parse_date_arg(&args.flag_due_before));
+        let due_after = try!(parse_date_arg(&args.flag_due_after));
+        try!(profile.list_notes(args.flag_limit,
+                                flags,
+                                status,
+                                &args.flag_tag,
+                                due_before,
+                                due_after,
+                                query.as_ref(),
+                                &*out));
     }
 
     Ok(())
@@ -294,7 +650,7 @@ mod tests {
     fn write_item_test_case(item: Item, search: bool) -> String {
         let mut bytes: Vec<u8> = vec![];
         let line_format = LineFormat::new(&[item.clone()], false, false).unwrap();
-        item.write(&mut bytes, &line_format, search).expect("item.write failed");
+        item.write(&mut bytes, &line_format, search, None).expect("item.write failed");
         String::from_utf8_lossy(&bytes).into_owned()
     }
 
@@ -302,10 +658,14 @@ mod tests {
     fn test_write_item__no_search_non_empty_body() {
         let item = Item {
             id: 0,
+            uuid: "".into(),
             title: "This is a title".into(),
             status: Status::Blank,
             body: "This is the body".into(),
             last_touched: "2016-07-08 15:31:14 -0800".into(),
+            tags: vec![],
+            due: None,
+            history: vec![],
         };
         assert_eq!(write_item_test_case(item, false),
                    "0   This is a title (+)  2016-07-08 16:31:14\n");
@@ -316,10 +676,14 @@ mod tests {
         // no search && empty body
         let item = Item {
             id: 0,
+            uuid: "".into(),
             title: "This is a title".into(),
             status: Status::Blank,
             body: "".into(),
             last_touched: "2016-07-08 15:31:14 -0800".into(),
+            tags: vec![],
+            due: None,
+            history: vec![],
         };
         assert_eq!(write_item_test_case(item, false),
                    "0   This is a title  2016-07-08 16:31:14\n");
@@ -329,10 +693,14 @@ mod tests {
     fn test_write_item__search_non_empty_body() {
         let item = Item {
             id: 0,
+            uuid: "".into(),
             title: "This is a title".into(),
             status: Status::Blank,
             body: "This is the body\nit has multiple lines".into(),
             last_touched: "2016-07-08 15:31:14 -0800".into(),
+            tags: vec![],
+            due: None,
+            history: vec![],
         };
         assert_eq!(write_item_test_case(item, true),
                    "0   This is a title      2016-07-08 16:31:14\n\tThis is the body\n\tit has \
@@ -344,10 +712,14 @@ mod tests {
         // search && empty body
         let item = Item {
             id: 0,
+            uuid: "".into(),
             title: "This is a title".into(),
             status: Status::Blank,
             body: "".into(),
             last_touched: "2016-07-08 15:31:14 -0800".into(),
+            tags: vec![],
+            due: None,
+            history: vec![],
         };
         assert_eq!(write_item_test_case(item, true),
                    "0   This is a title  2016-07-08 16:31:14\n");
@@ -357,13 +729,34 @@ mod tests {
     fn test_write_item__non_zero_status_width() {
         let item = Item {
             id: 0,
+            uuid: "".into(),
             title: "This is a title".into(),
             status: Status::Started,
             body: "This is the body".into(),
             last_touched: "2016-07-08 15:31:14 -0800".into(),
+            tags: vec![],
+            due: None,
+            history: vec![],
         };
         assert_eq!(write_item_test_case(item, false),
                    "0   This is a title (+)  Started  2016-07-08 16:31:14\n");
 
     }
+
+    #[test]
+    fn test_write_item__tags() {
+        let item = Item {
+            id: 0,
+            uuid: "".into(),
+            title: "This is a title".into(),
+            status: Status::Blank,
+            body: "".into(),
+            last_touched: "2016-07-08 15:31:14 -0800".into(),
+            tags: vec!["infra".into(), "ops".into()],
+            due: None,
+            history: vec![],
+        };
+        assert_eq!(write_item_test_case(item, false),
+                   "0   This is a title  infra,ops  2016-07-08 16:31:14\n");
+    }
 }
@@ -11,9 +11,11 @@
 //   tries to construct a line format that won't overflow the console
 //   width.
 
+use std::cmp::{min, max};
+
 use errors::Result;
 use item::{Item, Status};
-use utils::termsize;
+use utils::{display_width, termsize, localize_last_touched_string};
 
 #[derive(Clone, Copy)]
 pub struct LineFormat {
@@ -21,14 +23,28 @@ pub struct LineFormat {
     pub id_width: usize,
     pub title_width: usize,
     pub status_width: usize,
+    pub tags_width: usize,
+    pub due_width: usize,
     pub touched_width: usize,
 }
 
+/// the title column isn't narrowed past this before the tags and due columns
+/// are given up
+pub const MIN_TITLE_WIDTH: usize = 10;
+
 impl LineFormat {
     pub fn new(items: &[Item], condensed: bool, search: bool) -> Result<LineFormat> {
         // get termsize :>
-        let console_width = termsize();
+        LineFormat::with_width(items, condensed, search, termsize())
+    }
 
+    /// the line format for printing `items` on a console `console_width` columns
+    /// wide, 0 if the width isn't known
+    pub fn with_width(items: &[Item],
+                      condensed: bool,
+                      search: bool,
+                      console_width: usize)
+                      -> Result<LineFormat> {
         // set colsep
         let colsep = if condensed {
             1
@@ -41,6 +57,8 @@ impl LineFormat {
             id_width: 0,
             title_width: 0,
             status_width: 0,
+            tags_width: 0,
+            due_width: 0,
             touched_width: 0,
         };
 
@@ -56,22 +74,16 @@ impl LineFormat {
             line_format.id_width = 2;
         }
 
-        // get length of longest title string
-        line_format.title_width = match items.iter()
-                                             .max_by_key(|n| if !n.body.is_empty() {
-                                                 n.title.len() + 4
-                                             } else {
-                                                 n.title.len()
-                                             }) {
-            Some(n) => {
-                if n.body.is_empty() || search {
-                    n.title.len()
-                } else {
-                    n.title.len() + 4
-                }
-            }
-            None => 0,
-        };
+        // get width of the widest title in columns, titles of notes with a body
+        // get (+) after them unless the body is printed
+        line_format.title_width = items.iter()
+                                       .map(|n| if n.body.is_empty() || search {
+                                           display_width(&n.title)
+                                       } else {
+                                           display_width(&n.title) + 4
+                                       })
+                                       .max()
+                                       .unwrap_or(0);
         // if using extended and longest title is less than 5 chars
         // set title_width to 5 so "title" won't be truncated
         if line_format.title_width < 5 && !condensed {
@@ -96,31 +108,83 @@ impl LineFormat {
             0
         };
 
-        // last_touched has fixed string length so no need for silly iter stuff
+        // tags are printed comma seperated, truncate column if no items have tags
+        line_format.tags_width = items.iter()
+                                      .map(|n| display_width(&n.tags.join(",")))
+                                      .max()
+                                      .unwrap_or(0);
+        // if using extended and there are tags set tags_width to at least 4 so
+        // "tags" won't be truncated
+        if line_format.tags_width > 0 && line_format.tags_width < 4 && !condensed {
+            line_format.tags_width = 4;
+        }
+
+        // due dates are printed without the time so have a fixed length, truncate
+        // column if no items have a due date
+        line_format.due_width = if items.iter().any(|n| n.due.is_some()) {
+            10
+        } else {
+            0
+        };
+
+        // last_touched is printed in the configured date format, condensed
+        // printing only shows the start of it (the date with the default format)
+        let touched_width = items.iter()
+                                 .filter_map(|n| localize_last_touched_string(&n.last_touched).ok())
+                                 .map(|t| display_width(&t))
+                                 .max()
+                                 .unwrap_or(0);
         line_format.touched_width = if condensed {
-            10 // condensed
+            min(touched_width, 10)
         } else {
-            19 // expanded
+            // so "last touched" won't be truncated
+            max(touched_width, 12)
         };
 
         // check to make sure our new line format isn't bigger than the console
-        let line_width = line_format.line_width();
-        if console_width > 0 && line_width > console_width &&
-           (line_format.title_width - (line_width - console_width)) > 0 {
-            // if it is trim text from the title width since it is always the biggest...
-            // if there isn't any statuses, also give the title the colsep char space
-            line_format.title_width -= line_width - console_width;
+        if console_width > 0 {
+            line_format.fit(console_width, condensed);
         }
 
         Ok(line_format)
     }
 
+    // narrow the columns until a line fits in `console_width` columns, the title
+    // first since it is usually the widest (down to MIN_TITLE_WIDTH), then the
+    // tags, then the tags and due columns are dropped. on a console narrower than
+    // that the line is left to wrap
+    fn fit(&mut self, console_width: usize, condensed: bool) {
+        let over = |f: &LineFormat| f.line_width().saturating_sub(console_width);
+
+        let spare = self.title_width.saturating_sub(MIN_TITLE_WIDTH);
+        self.title_width -= min(over(self), spare);
+
+        // so "tags" won't be truncated
+        let min_tags = if condensed { 1 } else { 4 };
+        if self.tags_width > min_tags {
+            let spare = self.tags_width - min_tags;
+            self.tags_width -= min(over(self), spare);
+        }
+        if over(self) > 0 {
+            self.tags_width = 0;
+        }
+        if over(self) > 0 {
+            self.due_width = 0;
+        }
+    }
+
     pub fn line_width(&self) -> usize {
-        let columns = if self.status_width == 0 {
-            2 * self.colsep
-        } else {
-            3 * self.colsep
-        };
-        self.id_width + self.title_width + self.status_width + self.touched_width + columns
+        let mut columns = 2 * self.colsep;
+        if self.status_width != 0 {
+            columns += self.colsep;
+        }
+        if self.tags_width != 0 {
+            columns += self.colsep;
+        }
+        if self.due_width != 0 {
+            columns += self.colsep;
+        }
+        self.id_width + self.title_width + self.status_width + self.tags_width + self.due_width +
+        self.touched_width + columns
     }
 }
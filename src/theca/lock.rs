@@ -0,0 +1,134 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// lock.rs
+//   advisory locking of profiles so that concurrent theca processes can't
+//   clobber each others changes.
+
+// std imports
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::Error as IoError;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+// libc imports
+use libc::{flock, getpid, LOCK_EX, LOCK_NB, EWOULDBLOCK};
+
+// theca imports
+use errors::{Result, Error};
+use utils::find_profile_folder;
+
+// locks held by this process along with how many ProfileLocks share them, flock
+// locks belong to the open file so taking the same lock twice from one process
+// would block forever (transfer and import load the same profile more than once)
+thread_local!(static HELD: RefCell<HashMap<PathBuf, (File, usize)>> = RefCell::new(HashMap::new()));
+
+/// how long to sleep between attempts to take a lock
+const RETRY_MILLIS: u64 = 50;
+
+/// path of the lock file for a profile
+pub fn lock_path(profile_name: &str, profile_folder: &str) -> Result<PathBuf> {
+    let mut path = try!(find_profile_folder(profile_folder));
+    path.push(&format!(".{}.json.lock", profile_name));
+    Ok(path)
+}
+
+/// An exclusive advisory lock on a profile, held until it is dropped
+pub struct ProfileLock {
+    path: Option<PathBuf>,
+}
+
+impl ProfileLock {
+    /// lock a profile, waiting up to `timeout` seconds for any other theca
+    /// process holding it to finish
+    pub fn acquire(profile_name: &str, profile_folder: &str, timeout: u64) -> Result<ProfileLock> {
+        let path = try!(lock_path(profile_name, profile_folder));
+
+        // nothing to lock until the profile folder has been created
+        if !path.parent().map_or(false, |p| p.is_dir()) {
+            return Ok(ProfileLock { path: None });
+        }
+
+        let reentrant = HELD.with(|held| {
+            match held.borrow_mut().get_mut(&path) {
+                Some(&mut (_, ref mut count)) => {
+                    *count += 1;
+                    true
+                }
+                None => false,
+            }
+        });
+        if reentrant {
+            return Ok(ProfileLock { path: Some(path) });
+        }
+
+        // don't truncate the file until we hold the lock, it contains the pid of the
+        // current holder
+        let mut file = try!(OpenOptions::new().read(true).write(true).create(true).open(&path));
+        let started = Instant::now();
+        while unsafe { flock(file.as_raw_fd(), LOCK_EX | LOCK_NB) } != 0 {
+            let err = IoError::last_os_error();
+            if err.raw_os_error() != Some(EWOULDBLOCK) {
+                return Err(From::from(err));
+            }
+            if started.elapsed() >= Duration::from_secs(timeout) {
+                return specific_fail!(format!("profile '{}' is locked by another theca process \
+                                               ({}), gave up waiting after {} seconds",
+                                              profile_name,
+                                              lock_holder(&mut file),
+                                              timeout));
+            }
+            sleep(Duration::from_millis(RETRY_MILLIS));
+        }
+
+        try!(file.set_len(0));
+        try!(file.seek(SeekFrom::Start(0)));
+        try!(write!(file, "{}\n", unsafe { getpid() }));
+        try!(file.flush());
+
+        HELD.with(|held| held.borrow_mut().insert(path.clone(), (file, 1)));
+        Ok(ProfileLock { path: Some(path) })
+    }
+}
+
+impl Drop for ProfileLock {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.path {
+            HELD.with(|held| {
+                let mut held = held.borrow_mut();
+                let release = match held.get_mut(path) {
+                    Some(&mut (_, ref mut count)) => {
+                        *count -= 1;
+                        *count == 0
+                    }
+                    None => false,
+                };
+                // closing the file releases the lock
+                if release {
+                    held.remove(path);
+                }
+            });
+        }
+    }
+}
+
+// describe the process holding a lock from the pid it wrote to the lock file
+fn lock_holder(file: &mut File) -> String {
+    let mut contents = String::new();
+    if file.seek(SeekFrom::Start(0)).is_ok() && file.read_to_string(&mut contents).is_ok() {
+        if let Ok(pid) = contents.trim().parse::<u32>() {
+            return format!("pid {}", pid);
+        }
+    }
+    "unknown pid".to_string()
+}
@@ -0,0 +1,296 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// markdown.rs
+//   notes as markdown files, the body as the content with the rest of the note
+//   in YAML front matter, used by `export --format markdown` and `import-dir`.
+
+// random things
+use rustc_serialize::json;
+
+// theca imports
+use export::ImportedNote;
+use item::{Item, Status};
+use errors::{Result, Error};
+
+/// the longest the title part of a file name gets
+const SLUG_LEN: usize = 40;
+
+/// the name of the file a note is exported to, its id and its title made safe
+/// for a file name
+pub fn file_name(note: &Item) -> String {
+    let mut slug = String::new();
+    for c in note.title.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.chars().count() >= SLUG_LEN {
+            break;
+        }
+    }
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        format!("{}.md", note.id)
+    } else {
+        format!("{}-{}.md", note.id, slug)
+    }
+}
+
+// a YAML double quoted string, JSON strings are valid ones
+fn quote(s: &str) -> String {
+    json::encode(&s).unwrap_or_else(|_| "\"\"".to_string())
+}
+
+fn status_name(status: Status) -> &'static str {
+    match status {
+        Status::Blank => "none",
+        Status::Started => "started",
+        Status::Urgent => "urgent",
+    }
+}
+
+/// the contents of the markdown file for a note
+pub fn to_markdown(note: &Item) -> String {
+    let mut out = String::from("---\n");
+    out.push_str(&format!("uuid: {}\n", note.uuid));
+    out.push_str(&format!("id: {}\n", note.id));
+    out.push_str(&format!("title: {}\n", quote(&note.title)));
+    out.push_str(&format!("status: {}\n", status_name(note.status)));
+    out.push_str(&format!("last_touched: {}\n", quote(&note.last_touched)));
+    if !note.tags.is_empty() {
+        out.push_str(&format!("tags: [{}]\n",
+                              note.tags.iter().map(|t| quote(t)).collect::<Vec<_>>().join(", ")));
+    }
+    if let Some(ref due) = note.due {
+        out.push_str(&format!("due: {}\n", quote(due)));
+    }
+    out.push_str("---\n");
+    out.push_str(&note.body);
+    out
+}
+
+// split a YAML scalar off the start of `s`, returns it and the rest of `s`.
+// `stop` ends a plain (unquoted) scalar, for the commas in a flow list.
+fn split_scalar<'a>(s: &'a str, stop: &[char]) -> Result<(String, &'a str)> {
+    let s = s.trim_left();
+    if s.starts_with('"') {
+        let mut escaped = false;
+        for (i, c) in s.char_indices().skip(1) {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                return match json::decode::<String>(&s[..i + 1]) {
+                    Ok(v) => Ok((v, &s[i + 1..])),
+                    Err(_) => specific_fail!(format!("bad string {}", &s[..i + 1])),
+                };
+            }
+        }
+        specific_fail!(format!("unterminated string {}", s))
+    } else if s.starts_with('\'') {
+        // a quote inside a single quoted string is written twice
+        let mut value = String::new();
+        let mut chars = s.char_indices().skip(1).peekable();
+        while let Some((i, c)) = chars.next() {
+            if c == '\'' {
+                if chars.peek().map(|&(_, n)| n) == Some('\'') {
+                    chars.next();
+                } else {
+                    return Ok((value, &s[i + 1..]));
+                }
+            }
+            value.push(c);
+        }
+        specific_fail!(format!("unterminated string {}", s))
+    } else {
+        let mut end = s.len();
+        for (i, c) in s.char_indices() {
+            if stop.contains(&c) || (c == '#' && s[..i].ends_with(' ')) {
+                end = i;
+                break;
+            }
+        }
+        let value = s[..end].trim();
+        let value = if value == "~" || value == "null" {
+            ""
+        } else {
+            value
+        };
+        Ok((value.to_string(), &s[end..]))
+    }
+}
+
+// the only things allowed after a value are spaces and a comment
+fn check_rest(rest: &str) -> Result<()> {
+    let rest = rest.trim();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        specific_fail!(format!("unexpected '{}'", rest))
+    }
+}
+
+fn parse_scalar(s: &str) -> Result<String> {
+    let (value, rest) = try!(split_scalar(s, &[]));
+    try!(check_rest(rest));
+    Ok(value)
+}
+
+// a flow list like `[work, "deploy"]`
+fn parse_list(s: &str) -> Result<Vec<String>> {
+    let mut items = vec![];
+    let mut rest = &s.trim_left()[1..];
+    loop {
+        if rest.trim_left().starts_with(']') {
+            try!(check_rest(&rest.trim_left()[1..]));
+            return Ok(items);
+        }
+        let (item, r) = try!(split_scalar(rest, &[',', ']']));
+        items.push(item);
+        let r = r.trim_left();
+        if r.starts_with(',') {
+            rest = &r[1..];
+        } else if r.starts_with(']') {
+            rest = r;
+        } else {
+            return specific_fail!(format!("unterminated list {}", s));
+        }
+    }
+}
+
+/// a status by name, as written by theca or by hand
+pub fn parse_status(s: &str) -> Result<Status> {
+    match &*s.to_lowercase() {
+        "" | "none" | "blank" => Ok(Status::Blank),
+        "started" => Ok(Status::Started),
+        "urgent" => Ok(Status::Urgent),
+        _ => specific_fail!(format!("unknown status '{}'", s)),
+    }
+}
+
+/// read a note from the contents of a markdown file, a file without front
+/// matter is all body
+pub fn from_markdown(text: &str) -> Result<ImportedNote> {
+    let mut note = ImportedNote {
+        uuid: None,
+        id: None,
+        title: None,
+        status: None,
+        last_touched: None,
+        tags: vec![],
+        due: None,
+        body: Some(text.to_string()),
+    };
+    let first_end = text.find('\n').map_or(text.len(), |i| i + 1);
+    if text[..first_end].trim_right() != "---" {
+        return Ok(note);
+    }
+
+    // the front matter ends at the next `---` (or `...`) line
+    let mut pos = first_end;
+    let mut lines = vec![];
+    loop {
+        if pos >= text.len() {
+            return specific_fail_str!("the front matter isn't closed with a --- line");
+        }
+        let end = text[pos..].find('\n').map_or(text.len(), |i| pos + i + 1);
+        let line = text[pos..end].trim_right();
+        pos = end;
+        if line == "---" || line == "..." {
+            break;
+        }
+        lines.push(line);
+    }
+    note.body = Some(text[pos..].to_string());
+
+    // key of the block list being read, for `tags:` followed by `- tag` lines
+    let mut list_key: Option<&str> = None;
+    for line in lines {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if trimmed == "-" || trimmed.starts_with("- ") {
+            match list_key {
+                Some("tags") => note.tags.push(try!(parse_scalar(&trimmed[1..]))),
+                Some(_) => {}
+                None => return specific_fail!(format!("unexpected list item '{}'", trimmed)),
+            }
+            continue;
+        }
+        let colon = match line.find(':') {
+            Some(i) => i,
+            None => return specific_fail!(format!("expected `key: value`, found '{}'", line)),
+        };
+        let key = line[..colon].trim();
+        let value = line[colon + 1..].trim();
+        list_key = if value.is_empty() {
+            Some(key)
+        } else {
+            None
+        };
+        if value.is_empty() {
+            continue;
+        }
+        match key {
+            "uuid" => note.uuid = Some(try!(parse_scalar(value)).to_lowercase()),
+            "id" => {
+                let id = try!(parse_scalar(value));
+                note.id = match id.parse() {
+                    Ok(i) => Some(i),
+                    Err(_) => return specific_fail!(format!("invalid note id '{}'", id)),
+                };
+            }
+            "title" => note.title = Some(try!(parse_scalar(value))),
+            "status" => note.status = Some(try!(parse_status(&try!(parse_scalar(value))))),
+            "last_touched" => note.last_touched = Some(try!(parse_scalar(value))),
+            "tags" => {
+                note.tags = if value.starts_with('[') {
+                    try!(parse_list(value))
+                } else {
+                    try!(parse_scalar(value)).split(',').map(|t| t.trim().to_string()).collect()
+                };
+            }
+            "due" => note.due = Some(try!(parse_scalar(value))),
+            // anything else is left for other tools
+            _ => {}
+        }
+    }
+    note.tags.retain(|t| !t.trim().is_empty());
+    if note.due.as_ref().map_or(false, |d| d.is_empty()) {
+        note.due = None;
+    }
+    Ok(note)
+}
+
+/// `text` with `uuid` in its front matter, in place of the uuid that is there
+/// or as the first key, and with front matter added if it has none. the rest
+/// of the file is left as it is.
+pub fn add_uuid(text: &str, uuid: &str) -> String {
+    let line = format!("uuid: {}", uuid);
+    let first_end = text.find('\n').map_or(text.len(), |i| i + 1);
+    if text[..first_end].trim_right() != "---" {
+        return format!("---\n{}\n---\n{}", line, text);
+    }
+    let mut pos = first_end;
+    while pos < text.len() {
+        let end = text[pos..].find('\n').map_or(text.len(), |i| pos + i + 1);
+        let current = text[pos..end].trim_right();
+        if current == "---" || current == "..." {
+            break;
+        }
+        if current.starts_with("uuid:") {
+            return format!("{}{}{}", &text[..pos], line, &text[pos + current.len()..]);
+        }
+        pos = end;
+    }
+    format!("{}{}\n{}", &text[..first_end], line, &text[first_end..])
+}
@@ -0,0 +1,331 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// matcher.rs
+//   matching a `search` pattern against the titles or bodies of notes, by
+//   keyword (optionally ignoring case), regex or fuzzily, and highlighting
+//   the parts of the text that matched.
+
+// std imports
+use std::cmp::{max, min};
+use std::io::Write;
+
+// random things
+use regex::Regex;
+use term::{color, Terminal};
+use term::Attr::Bold;
+use term::terminfo::TerminfoTerminal;
+
+// theca imports
+use errors::{Result, Error};
+
+use BoolFlags;
+
+/// How a pattern is matched
+#[derive(Clone, Debug)]
+pub enum Mode {
+    /// the text contains the pattern
+    Keyword,
+    /// the text contains the pattern, ignoring case
+    IgnoreCase,
+    /// every word of the pattern is in the text, with a typo or two or with
+    /// letters left out
+    Fuzzy,
+    Regex(Regex),
+}
+
+/// A search pattern
+#[derive(Clone, Debug)]
+pub struct Matcher {
+    pub pattern: String,
+    pub mode: Mode,
+    // the lowercase words of the pattern for fuzzy matching
+    words: Vec<Vec<char>>,
+}
+
+// a byte range of the text that matched
+pub type Span = (usize, usize);
+
+fn lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+// every place `pattern` (lowercase) is in `text`, ignoring case
+fn find_ignore_case(text: &str, pattern: &[char]) -> Vec<Span> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut spans = vec![];
+    if pattern.is_empty() {
+        return spans;
+    }
+    let mut i = 0;
+    while i + pattern.len() <= chars.len() {
+        if pattern.iter().enumerate().all(|(k, p)| lower(chars[i + k].1) == *p) {
+            let end = chars.get(i + pattern.len()).map_or(text.len(), |c| c.0);
+            spans.push((chars[i].0, end));
+            i += pattern.len();
+        } else {
+            i += 1;
+        }
+    }
+    spans
+}
+
+// the words of `text` (runs of letters and numbers) with where they are
+fn text_words(text: &str) -> Vec<(Span, Vec<char>)> {
+    let mut words = vec![];
+    let mut current: Option<(usize, Vec<char>)> = None;
+    for (i, c) in text.char_indices() {
+        if c.is_alphanumeric() {
+            match current {
+                Some((_, ref mut w)) => w.push(lower(c)),
+                None => current = Some((i, vec![lower(c)])),
+            }
+        } else if let Some((start, w)) = current.take() {
+            words.push(((start, i), w));
+        }
+    }
+    if let Some((start, w)) = current {
+        words.push(((start, text.len()), w));
+    }
+    words
+}
+
+/// the number of single character edits (insertions, deletions, substitutions
+/// and swaps of neighbouring characters) that turn `a` into `b`
+pub fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in 0..a.len() + 1 {
+        d[i][0] = i;
+    }
+    for j in 0..b.len() + 1 {
+        d[0][j] = j;
+    }
+    for i in 1..a.len() + 1 {
+        for j in 1..b.len() + 1 {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = min(min(d[i - 1][j] + 1, d[i][j - 1] + 1), d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = min(d[i][j], d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+// how many typos a word of this length may have, short words have to be
+// spelled right or every other word would match them
+fn allowed_typos(len: usize) -> usize {
+    if len < 4 {
+        0
+    } else if len < 8 {
+        1
+    } else {
+        2
+    }
+}
+
+// the positions of the characters of `pattern` in `word` if they are all in it
+// in order
+fn subsequence(pattern: &[char], word: &[char]) -> Option<Vec<usize>> {
+    let mut positions = vec![];
+    let mut rest = pattern.iter().peekable();
+    for (i, c) in word.iter().enumerate() {
+        if rest.peek() == Some(&c) {
+            positions.push(i);
+            rest.next();
+        }
+    }
+    if rest.peek().is_none() {
+        Some(positions)
+    } else {
+        None
+    }
+}
+
+// the best fuzzy match of a word of the pattern in `text`, with its score and
+// the spans it covers. the word in the text scores best, then a misspelling
+// of it and then a word with some of its letters left out.
+fn fuzzy_word(word: &[char], text: &str) -> Option<(usize, Vec<Span>)> {
+    if let Some(&(start, end)) = find_ignore_case(text, word).first() {
+        let at_word_start = text[..start].chars().last().map_or(true, |c| !c.is_alphanumeric());
+        return Some((if at_word_start { 120 } else { 100 }, vec![(start, end)]));
+    }
+
+    // a misspelling of a word in the text or of the start of a longer one, like
+    // deplyo for deployment
+    let words = text_words(text);
+    let typos = allowed_typos(word.len());
+    let typo = words.iter()
+                    .filter(|&&(_, ref w)| w.len() + typos >= word.len())
+                    .map(|&(span, ref w)| {
+                        let whole = edit_distance(word, w);
+                        if w.len() > word.len() {
+                            let start = edit_distance(word, &w[..word.len()]);
+                            if start < whole {
+                                // the start of a word scores a little lower
+                                return (start, 5, span);
+                            }
+                        }
+                        (whole, 0, span)
+                    })
+                    .filter(|&(d, _, _)| d <= typos)
+                    .min_by_key(|&(d, penalty, _)| (d, penalty));
+    if let Some((d, penalty, span)) = typo {
+        return Some((70 - 20 * d - penalty, vec![span]));
+    }
+
+    if word.len() < 2 {
+        return None;
+    }
+    words.iter()
+         .filter_map(|&((start, _), ref w)| {
+             subsequence(word, w).map(|positions| {
+                 // the text of a word is its characters, so find where each
+                 // matched character starts
+                 let offsets: Vec<(usize, char)> = text[start..].char_indices().collect();
+                 let spans = positions.iter()
+                                      .map(|&p| {
+                                          let (o, c) = offsets[p];
+                                          (start + o, start + o + c.len_utf8())
+                                      })
+                                      .collect();
+                 (10 + 15 * word.len() / w.len(), spans)
+             })
+         })
+         .max_by_key(|&(score, _)| score)
+}
+
+// sort spans and join the ones that touch or overlap
+fn merge(mut spans: Vec<Span>) -> Vec<Span> {
+    spans.sort();
+    let mut merged: Vec<Span> = vec![];
+    for (start, end) in spans {
+        if let Some(last) = merged.last_mut() {
+            if start <= last.1 {
+                last.1 = max(last.1, end);
+                continue;
+            }
+        }
+        merged.push((start, end));
+    }
+    merged
+}
+
+impl Matcher {
+    /// a matcher for `pattern` in the mode picked by `--regex`, `--fuzzy` and
+    /// `--ignore-case`
+    pub fn new(pattern: &str, flags: &BoolFlags) -> Result<Matcher> {
+        if flags.fuzzy && flags.regex {
+            return specific_fail_str!("--fuzzy and --regex can't be used together");
+        }
+        let mode = if flags.regex {
+            let pattern = if flags.ignore_case {
+                format!("(?i){}", pattern)
+            } else {
+                pattern.to_string()
+            };
+            match Regex::new(&pattern) {
+                Ok(r) => Mode::Regex(r),
+                Err(e) => return specific_fail!(format!("regex error: {}.", e)),
+            }
+        } else if flags.fuzzy {
+            Mode::Fuzzy
+        } else if flags.ignore_case {
+            Mode::IgnoreCase
+        } else {
+            Mode::Keyword
+        };
+        Ok(Matcher {
+            pattern: pattern.to_string(),
+            mode: mode,
+            words: pattern.split_whitespace().map(|w| w.chars().map(lower).collect()).collect(),
+        })
+    }
+
+    /// how well `text` matches the pattern, None if it doesn't. only fuzzy
+    /// matches are scored, the other modes score how many times they match.
+    pub fn score(&self, text: &str) -> Option<usize> {
+        match self.mode {
+            Mode::Fuzzy => {
+                let mut total = 0;
+                for word in self.words.iter() {
+                    match fuzzy_word(word, text) {
+                        Some((score, _)) => total += score,
+                        None => return None,
+                    }
+                }
+                Some(total)
+            }
+            _ if self.pattern.is_empty() => Some(0),
+            _ => {
+                match self.spans(text).len() {
+                    0 => None,
+                    n => Some(n),
+                }
+            }
+        }
+    }
+
+    /// the parts of `text` that match the pattern
+    pub fn spans(&self, text: &str) -> Vec<Span> {
+        if self.pattern.is_empty() {
+            return vec![];
+        }
+        let spans = match self.mode {
+            Mode::Keyword => {
+                text.match_indices(&self.pattern[..])
+                    .map(|(i, m)| (i, i + m.len()))
+                    .collect()
+            }
+            Mode::IgnoreCase => {
+                let pattern: Vec<char> = self.pattern.chars().map(lower).collect();
+                find_ignore_case(text, &pattern)
+            }
+            Mode::Regex(ref re) => re.find_iter(text).filter(|&(s, e)| s < e).collect(),
+            Mode::Fuzzy => {
+                self.words
+                    .iter()
+                    .filter_map(|w| fuzzy_word(w, text))
+                    .flat_map(|(_, spans)| spans.into_iter())
+                    .collect()
+            }
+        };
+        merge(spans)
+    }
+}
+
+/// write `text` to `output` with the parts of it in `spans` highlighted
+pub fn write_highlighted<T: Write>(output: &mut T, text: &str, spans: &[Span]) -> Result<()> {
+    if spans.is_empty() {
+        try!(write!(output, "{}", text));
+        return Ok(());
+    }
+    let mut t = match TerminfoTerminal::new(&mut *output) {
+        Some(t) => t,
+        None => {
+            // no terminfo for this terminal, so nothing to highlight with
+            try!(write!(output, "{}", text));
+            return Ok(());
+        }
+    };
+    let mut at = 0;
+    for &(start, end) in spans.iter() {
+        let (start, end) = (min(max(start, at), text.len()), min(end, text.len()));
+        if start >= end {
+            continue;
+        }
+        try!(write!(t, "{}", &text[at..start]));
+        try!(t.fg(color::YELLOW));
+        try!(t.attr(Bold));
+        try!(write!(t, "{}", &text[start..end]));
+        try!(t.reset());
+        at = end;
+    }
+    try!(write!(t, "{}", &text[at..]));
+    Ok(())
+}
@@ -0,0 +1,337 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// merge.rs
+//   three-way merging of the notes in a profile that was changed on disk
+//   while theca was working on it.
+
+// std imports
+use std::cmp::Ordering;
+
+// theca imports
+use item::{Item, HISTORY_LEN};
+use profile::Trashed;
+use utils::{cmp_last_touched, get_yn_input};
+use errors::{Result, Error};
+
+/// A note field that can be merged on its own
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Field {
+    Title,
+    Status,
+    Body,
+    Tags,
+    Due,
+}
+
+pub static FIELDS: [Field; 5] = [Field::Title, Field::Status, Field::Body, Field::Tags, Field::Due];
+
+impl Field {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Field::Title => "title",
+            Field::Status => "status",
+            Field::Body => "body",
+            Field::Tags => "tags",
+            Field::Due => "due",
+        }
+    }
+
+    fn same(&self, a: &Item, b: &Item) -> bool {
+        match *self {
+            Field::Title => a.title == b.title,
+            Field::Status => a.status == b.status,
+            Field::Body => a.body == b.body,
+            Field::Tags => a.tags == b.tags,
+            Field::Due => a.due == b.due,
+        }
+    }
+
+    fn copy(&self, from: &Item, to: &mut Item) {
+        match *self {
+            Field::Title => to.title = from.title.clone(),
+            Field::Status => to.status = from.status,
+            Field::Body => to.body = from.body.clone(),
+            Field::Tags => to.tags = from.tags.clone(),
+            Field::Due => to.due = from.due.clone(),
+        }
+    }
+
+    /// the value of the field in `item` for showing to the user
+    pub fn show(&self, item: &Item) -> String {
+        match *self {
+            Field::Title => item.title.clone(),
+            Field::Status => format!("{}", item.status),
+            Field::Body => item.body.clone(),
+            Field::Tags => item.tags.join(","),
+            Field::Due => item.due.clone().unwrap_or_else(String::new),
+        }
+    }
+}
+
+/// A change that couldn't be merged automatically
+#[derive(Clone, Debug)]
+pub enum Conflict {
+    /// both sides changed the same field of a note to different values
+    Field {
+        field: Field,
+        ours: Item,
+        theirs: Item,
+    },
+    /// the note was deleted here but changed on disk
+    DeletedHere {
+        theirs: Item,
+    },
+    /// the note was changed here but deleted on disk
+    DeletedThere {
+        ours: Item,
+    },
+}
+
+impl Conflict {
+    /// id of the note the conflict is in
+    pub fn id(&self) -> usize {
+        match *self {
+            Conflict::Field { ref ours, .. } => ours.id,
+            Conflict::DeletedHere { ref theirs } => theirs.id,
+            Conflict::DeletedThere { ref ours } => ours.id,
+        }
+    }
+
+    /// a short description of the conflict
+    pub fn describe(&self) -> String {
+        match *self {
+            Conflict::Field { field, ref ours, ref theirs } => {
+                format!("note {} {} was changed both here ({:?}) and on disk ({:?})",
+                        ours.id,
+                        field.name(),
+                        field.show(ours),
+                        field.show(theirs))
+            }
+            Conflict::DeletedHere { ref theirs } => {
+                format!("note {} was deleted here but changed on disk", theirs.id)
+            }
+            Conflict::DeletedThere { ref ours } => {
+                format!("note {} was changed here but deleted on disk", ours.id)
+            }
+        }
+    }
+}
+
+/// The result of merging two versions of a profiles notes
+#[derive(Clone, Debug)]
+pub struct Merge {
+    /// merged notes, conflicting fields and notes are left as they are on disk
+    pub notes: Vec<Item>,
+    pub conflicts: Vec<Conflict>,
+    /// notes added here whose id was also used by a note added on disk, (old, new)
+    pub renumbered: Vec<(usize, usize)>,
+}
+
+impl Merge {
+    /// settle a conflict in favour of the changes made here (`ours`) or the
+    /// changes on disk
+    pub fn resolve(&mut self, conflict: &Conflict, ours: bool) {
+        if !ours {
+            return;
+        }
+        match *conflict {
+            Conflict::Field { field, ours: ref o, .. } => {
+                if let Some(n) = self.notes.iter_mut().find(|n| n.uuid == o.uuid) {
+                    field.copy(o, n);
+                    if cmp_last_touched(&o.last_touched, &n.last_touched)
+                           .unwrap_or(Ordering::Less) == Ordering::Greater {
+                        n.last_touched = o.last_touched.clone();
+                    }
+                }
+            }
+            Conflict::DeletedHere { ref theirs } => self.notes.retain(|n| n.uuid != theirs.uuid),
+            Conflict::DeletedThere { ref ours } => {
+                let mut kept = ours.clone();
+                if self.notes.iter().any(|n| n.id == ours.id) {
+                    kept.id = self.notes.iter().map(|n| n.id).max().unwrap_or(0) + 1;
+                    self.renumbered.push((ours.id, kept.id));
+                }
+                let position = self.notes.iter().position(|n| n.id > kept.id);
+                match position {
+                    Some(i) => self.notes.insert(i, kept),
+                    None => self.notes.push(kept),
+                }
+            }
+        }
+    }
+}
+
+fn find<'a>(notes: &'a [Item], uuid: &str) -> Option<&'a Item> {
+    notes.iter().find(|n| n.uuid == uuid)
+}
+
+// every field is the same, last_touched is ignored
+fn same_note(a: &Item, b: &Item) -> bool {
+    FIELDS.iter().all(|f| f.same(a, b))
+}
+
+// merge the changes made to a note on both sides
+fn merge_note(base: &Item, ours: &Item, theirs: &Item, conflicts: &mut Vec<Conflict>) -> Item {
+    let mut merged = theirs.clone();
+    let mut ours_changed = false;
+    for field in FIELDS.iter() {
+        if field.same(ours, base) || field.same(ours, theirs) {
+            continue;
+        }
+        if field.same(theirs, base) {
+            field.copy(ours, &mut merged);
+            ours_changed = true;
+        } else {
+            conflicts.push(Conflict::Field {
+                field: *field,
+                ours: ours.clone(),
+                theirs: theirs.clone(),
+            });
+        }
+    }
+    if ours_changed {
+        merge_history(base, ours, &mut merged);
+        if cmp_last_touched(&ours.last_touched, &theirs.last_touched)
+               .unwrap_or(Ordering::Less) == Ordering::Greater {
+            merged.last_touched = ours.last_touched.clone();
+        }
+    }
+    merged
+}
+
+// add the revisions made here to the history of a merged note that started out as
+// the version on disk, undoing them gives the version on disk since the fields
+// they changed weren't changed there
+fn merge_history(base: &Item, ours: &Item, merged: &mut Item) {
+    let base_rev = base.revision();
+    for r in ours.history.iter().filter(|r| r.rev >= base_rev) {
+        let mut added = r.clone();
+        // the first one is now the version on disk rather than the one loaded
+        if r.rev == base_rev {
+            added.last_touched = merged.last_touched.clone();
+        }
+        added.rev = merged.revision();
+        merged.history.push(added);
+    }
+    if merged.history.len() > HISTORY_LEN {
+        let extra = merged.history.len() - HISTORY_LEN;
+        merged.history.drain(..extra);
+    }
+}
+
+/// three-way merge the notes changed here (`ours`) and on disk (`theirs`) since
+/// they were loaded (`base`)
+pub fn merge_notes(base: &[Item], ours: &[Item], theirs: &[Item]) -> Merge {
+    let mut notes = vec![];
+    let mut conflicts = vec![];
+
+    // start from the version on disk
+    for t in theirs.iter() {
+        match (find(base, &t.uuid), find(ours, &t.uuid)) {
+            // added on disk
+            (None, _) => notes.push(t.clone()),
+            (Some(b), Some(o)) => notes.push(merge_note(b, o, t, &mut conflicts)),
+            // deleted here
+            (Some(b), None) => {
+                if !same_note(b, t) {
+                    conflicts.push(Conflict::DeletedHere { theirs: t.clone() });
+                    notes.push(t.clone());
+                }
+            }
+        }
+    }
+
+    // then add the notes added here, and those changed here that aren't on disk
+    // anymore
+    let mut renumbered = vec![];
+    let mut next_id = theirs.iter().chain(ours.iter()).map(|n| n.id).max().unwrap_or(0) + 1;
+    for o in ours.iter() {
+        match (find(base, &o.uuid), find(theirs, &o.uuid)) {
+            (None, None) => {
+                let mut added = o.clone();
+                // the id may have been taken by a note added on disk
+                if notes.iter().any(|n| n.id == o.id) {
+                    added.id = next_id;
+                    renumbered.push((o.id, next_id));
+                    next_id += 1;
+                }
+                notes.push(added);
+            }
+            // deleted on disk
+            (Some(b), None) => {
+                if !same_note(b, o) {
+                    conflicts.push(Conflict::DeletedThere { ours: o.clone() });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Merge {
+        notes: notes,
+        conflicts: conflicts,
+        renumbered: renumbered,
+    }
+}
+
+/// merge the notes, asking which version to keep for any conflicts, or failing
+/// if there are conflicts and `yes` is set
+pub fn merge_interactive(base: &[Item],
+                         ours: &[Item],
+                         theirs: &[Item],
+                         yes: bool)
+                         -> Result<Vec<Item>> {
+    let mut merge = merge_notes(base, ours, theirs);
+    if yes && !merge.conflicts.is_empty() {
+        let described: Vec<String> = merge.conflicts.iter().map(|c| c.describe()).collect();
+        return specific_fail!(format!("the profile was changed on disk and the changes \
+                                       conflict, nothing was saved:\n  {}",
+                                      described.join("\n  ")));
+    }
+    for conflict in merge.conflicts.clone().iter() {
+        let question = match *conflict {
+            Conflict::DeletedHere { .. } => "delete it anyway?",
+            _ => "keep your version?",
+        };
+        let message = format!("{}, {}\n", conflict.describe(), question);
+        let keep_ours = try!(get_yn_input(&message));
+        merge.resolve(conflict, keep_ours);
+    }
+    for &(old, new) in merge.renumbered.iter() {
+        println!("note {} was also added on disk, your note is now note {}", old, new);
+    }
+    Ok(merge.notes)
+}
+
+fn in_trash(trash: &[Trashed], uuid: &str) -> bool {
+    trash.iter().any(|t| t.note.uuid == uuid)
+}
+
+/// merge the trash changed here (`ours`) and on disk (`theirs`) since it was
+/// loaded (`base`), dropping anything that is among the merged `notes` again
+pub fn merge_trash(base: &[Trashed],
+                   ours: &[Trashed],
+                   theirs: &[Trashed],
+                   notes: &[Item])
+                   -> Vec<Trashed> {
+    let mut trash: Vec<Trashed> = vec![];
+    // notes purged or restored on either side stay gone
+    for t in theirs.iter() {
+        if in_trash(ours, &t.note.uuid) || !in_trash(base, &t.note.uuid) {
+            trash.push(t.clone());
+        }
+    }
+    for o in ours.iter() {
+        if !in_trash(base, &o.note.uuid) && !in_trash(theirs, &o.note.uuid) {
+            trash.push(o.clone());
+        }
+    }
+    trash.retain(|t| find(notes, &t.note.uuid).is_none());
+    trash
+}
@@ -0,0 +1,272 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// org.rs
+//   notes as org-mode headlines, used by `export --format org` and
+//   `import --format org`.
+
+// random things
+use time::strftime;
+
+// theca imports
+use export::ImportedNote;
+use item::{Item, Status};
+use todotxt::DONE_TAG;
+use utils::parse_last_touched;
+use errors::{Result, Error};
+
+/// the todo keywords in an exported file, so org knows STARTED is one
+pub static TODO_LINE: &'static str = "#+TODO: TODO STARTED | DONE";
+
+// an org tag can only have letters, numbers and `_@#%` in it
+fn org_tag(tag: &str) -> String {
+    tag.trim()
+       .chars()
+       .map(|c| {
+           if c.is_alphanumeric() || "_@#%".contains(c) {
+               c
+           } else {
+               '_'
+           }
+       })
+       .collect()
+}
+
+/// the headline of a note, `* STARTED Title :tag:` for a started note,
+/// `* [#A] Title` for an urgent one and `* DONE Title` for one tagged `done`
+pub fn headline(note: &Item) -> String {
+    let mut line = "*".to_string();
+    let done = note.tags.iter().any(|t| t == DONE_TAG);
+    if done {
+        line.push_str(" DONE");
+    }
+    match note.status {
+        Status::Started if !done => line.push_str(" STARTED"),
+        Status::Urgent => line.push_str(" [#A]"),
+        _ => {}
+    }
+    if !note.title.is_empty() {
+        line.push(' ');
+        line.push_str(&note.title);
+    }
+    let tags: Vec<String> = note.tags
+                                .iter()
+                                .filter(|t| *t != DONE_TAG)
+                                .map(|t| org_tag(t))
+                                .filter(|t| !t.is_empty())
+                                .collect();
+    if !tags.is_empty() {
+        line.push_str(&format!(" :{}:", tags.join(":")));
+    }
+    line
+}
+
+/// a note as an org entry, the headline followed by the deadline, a property
+/// drawer and the body
+pub fn to_org(note: &Item) -> Result<String> {
+    let mut out = headline(note);
+    out.push('\n');
+    if let Some(ref due) = note.due {
+        let day = try!(parse_last_touched(due));
+        out.push_str(&format!("DEADLINE: <{}>\n", try!(strftime("%F %a", &day))));
+    }
+    out.push_str(":PROPERTIES:\n");
+    out.push_str(&format!(":ID:           {}\n", note.uuid));
+    out.push_str(&format!(":THECA_ID:     {}\n", note.id));
+    out.push_str(&format!(":LAST_TOUCHED: {}\n", note.last_touched));
+    out.push_str(":END:\n");
+    if !note.body.is_empty() {
+        // lines that org would read as headlines are escaped with a comma
+        for line in note.body.split('\n') {
+            if line.starts_with('*') || line.starts_with(',') {
+                out.push(',');
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+/// the contents of an org file for `notes`
+pub fn notes_to_org(notes: &[Item]) -> Result<String> {
+    let mut out = format!("{}\n\n", TODO_LINE);
+    for note in notes.iter() {
+        out.push_str(&try!(to_org(note)));
+    }
+    Ok(out)
+}
+
+// split the keyword, priority, title and tags out of a headline (without its
+// leading `* `), a `DONE` headline is tagged `done`
+fn parse_headline(line: &str, note: &mut ImportedNote) {
+    let mut rest = line.trim();
+    let mut status = Status::Blank;
+    let mut done = false;
+    for keyword in &["TODO", "STARTED", "DONE"] {
+        if rest == *keyword || rest.starts_with(&format!("{} ", keyword)) {
+            rest = rest[keyword.len()..].trim_left();
+            match *keyword {
+                "DONE" => done = true,
+                _ => status = Status::Started,
+            }
+            break;
+        }
+    }
+    if rest.starts_with("[#") && rest[2..].find(']') == Some(1) {
+        if rest.starts_with("[#A]") {
+            status = Status::Urgent;
+        }
+        rest = rest[4..].trim_left();
+    }
+
+    // tags are a `:a:b:` word at the end
+    if rest.ends_with(':') {
+        let start = rest.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &rest[start..];
+        if word.len() > 1 && word.starts_with(':') && !word.contains("::") {
+            note.tags = word.trim_matches(':').split(':').map(|t| t.to_string()).collect();
+            rest = rest[..start].trim_right();
+        }
+    }
+    if done && !note.tags.iter().any(|t| t == DONE_TAG) {
+        note.tags.push(DONE_TAG.to_string());
+    }
+    note.title = Some(rest.to_string());
+    note.status = Some(status);
+}
+
+fn is_headline(line: &str) -> bool {
+    line.starts_with("* ") || line == "*"
+}
+
+fn is_planning(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("DEADLINE:") || line.starts_with("SCHEDULED:") || line.starts_with("CLOSED:")
+}
+
+// read a property drawer from `lines`, which start after its `:PROPERTIES:`
+// line, returns how many lines it took up to its `:END:`
+fn parse_drawer(lines: &[&str], note: &mut ImportedNote) -> Result<usize> {
+    for (i, line) in lines.iter().enumerate() {
+        let property = line.trim();
+        if property == ":END:" {
+            return Ok(i + 1);
+        }
+        let (name, value) = match property.find(':') {
+            Some(0) => {
+                match property[1..].find(':') {
+                    Some(e) => (&property[1..e + 1], property[e + 2..].trim()),
+                    None => return specific_fail!(format!("bad property '{}'", property)),
+                }
+            }
+            _ => return specific_fail!(format!("bad property '{}'", property)),
+        };
+        match &*name.to_uppercase() {
+            "ID" => note.uuid = Some(value.to_lowercase()),
+            "THECA_ID" => {
+                note.id = match value.parse() {
+                    Ok(i) => Some(i),
+                    Err(_) => return specific_fail!(format!("invalid note id '{}'", value)),
+                }
+            }
+            "LAST_TOUCHED" => note.last_touched = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    specific_fail_str!("a property drawer isn't closed")
+}
+
+/// read the notes from the contents of an org file, each top level headline is
+/// a note and anything before the first one is ignored. returns the notes along
+/// with the line (counting from 0) their headline is on.
+pub fn from_org(text: &str) -> Result<Vec<(usize, ImportedNote)>> {
+    // every line of a note ends with a newline, including the last one
+    let text = if text.ends_with('\n') {
+        &text[..text.len() - 1]
+    } else {
+        text
+    };
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut notes = vec![];
+    let mut i = match lines.iter().position(|l| is_headline(l)) {
+        Some(i) => i,
+        None => return Ok(notes),
+    };
+    while i < lines.len() {
+        let start = i;
+        let mut note = ImportedNote {
+            uuid: None,
+            id: None,
+            title: None,
+            status: None,
+            last_touched: None,
+            tags: vec![],
+            due: None,
+            body: None,
+        };
+        parse_headline(&lines[i][1..], &mut note);
+        i += 1;
+
+        // planning (DEADLINE, SCHEDULED) and the property drawer come straight
+        // after the headline
+        while i < lines.len() {
+            let line = lines[i].trim();
+            if is_planning(line) {
+                if let Some(d) = line.find("DEADLINE: <") {
+                    note.due = Some(line[d + 11..].chars().take(10).collect());
+                }
+                i += 1;
+            } else if line == ":PROPERTIES:" {
+                i += 1 + try!(parse_drawer(&lines[i + 1..], &mut note));
+            } else {
+                break;
+            }
+        }
+
+        // the section runs up to the next headline
+        let mut body = vec![];
+        while i < lines.len() && !is_headline(lines[i]) {
+            let line = lines[i];
+            body.push(if line.starts_with(",*") || line.starts_with(",,") {
+                &line[1..]
+            } else {
+                line
+            });
+            i += 1;
+        }
+        note.body = Some(body.join("\n"));
+        notes.push((start, note));
+    }
+    Ok(notes)
+}
+
+/// add the uuids of `notes`, pairs of the line of a headline and the note that
+/// was made from it, to the property drawers of their headlines in the org
+/// file `text`. headlines without a drawer get one holding just the `ID`.
+pub fn add_ids(text: &str, notes: &[(usize, &Item)]) -> String {
+    let mut lines: Vec<String> = text.split('\n').map(|l| l.to_string()).collect();
+    // from the bottom up so the lines of the notes still to do don't move
+    let mut notes = notes.to_vec();
+    notes.sort_by(|a, b| b.0.cmp(&a.0));
+    for (head, note) in notes {
+        let mut i = head + 1;
+        while i < lines.len() && is_planning(&lines[i]) {
+            i += 1;
+        }
+        let id = format!(":ID:           {}", note.uuid);
+        if i < lines.len() && lines[i].trim() == ":PROPERTIES:" {
+            lines.insert(i + 1, id);
+        } else {
+            lines.insert(i, ":END:".to_string());
+            lines.insert(i, id);
+            lines.insert(i, ":PROPERTIES:".to_string());
+        }
+    }
+    lines.join("\n")
+}
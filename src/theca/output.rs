@@ -0,0 +1,538 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// output.rs
+//   the formatters lists of notes, single notes and profile info are printed
+//   with, and the user templates used by `--template`.
+
+// std imports
+use std::iter::repeat;
+
+// random things
+use rustc_serialize::json::as_pretty_json;
+use term::Attr::Bold;
+use term::stdout;
+
+// theca imports
+use BoolFlags;
+use csv::{header, note_row, row, to_profiles_table, to_table};
+use item::{Item, Status};
+use lineformat::LineFormat;
+use matcher::Matcher;
+use config::color_output;
+use utils::{format_field, pretty_line, localize_due_string, localize_last_touched_string};
+use errors::{Result, Error};
+
+/// What `theca info` prints about a profile
+#[derive(RustcEncodable, Clone, Debug)]
+pub struct Info {
+    pub name: String,
+    pub encrypted: bool,
+    /// the key derivation of an encrypted profile
+    pub kdf: Option<String>,
+    pub notes: usize,
+    pub none: usize,
+    pub started: usize,
+    pub urgent: usize,
+    /// the last touched times of the oldest and newest notes
+    pub oldest: String,
+    pub newest: String,
+}
+
+impl Info {
+    // the value of a field for a template
+    fn field(&self, name: &str) -> Option<String> {
+        Some(match name {
+            "name" => self.name.clone(),
+            "encrypted" => self.encrypted.to_string(),
+            "kdf" => self.kdf.clone().unwrap_or_else(String::new),
+            "notes" => self.notes.to_string(),
+            "none" => self.none.to_string(),
+            "started" => self.started.to_string(),
+            "urgent" => self.urgent.to_string(),
+            "oldest" => self.oldest.clone(),
+            "newest" => self.newest.clone(),
+            _ => return None,
+        })
+    }
+}
+
+/// the fields a template can use for a note
+pub static NOTE_FIELDS: [&'static str; 9] = ["id",
+                                             "uuid",
+                                             "title",
+                                             "status",
+                                             "tags",
+                                             "due",
+                                             "last_touched",
+                                             "body",
+                                             "revision"];
+
+/// the fields a template can use for `theca info`
+pub static INFO_FIELDS: [&'static str; 9] = ["name",
+                                             "encrypted",
+                                             "kdf",
+                                             "notes",
+                                             "none",
+                                             "started",
+                                             "urgent",
+                                             "oldest",
+                                             "newest"];
+
+// the value of a field of a note for a template
+fn note_field(note: &Item, name: &str) -> Result<Option<String>> {
+    Ok(Some(match name {
+        "id" => note.id.to_string(),
+        "uuid" => note.uuid.clone(),
+        "title" => note.title.clone(),
+        "status" => note.status.to_string(),
+        "tags" => note.tags.join(", "),
+        "due" => {
+            match note.due {
+                Some(ref d) => try!(localize_due_string(d)),
+                None => String::new(),
+            }
+        }
+        "last_touched" => try!(localize_last_touched_string(&note.last_touched)),
+        "body" => note.body.clone(),
+        "revision" => note.revision().to_string(),
+        _ => return Ok(None),
+    }))
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Part {
+    Text(String),
+    Field(String),
+}
+
+/// A user template like `*{title}* ({status})\n{body}`, fields are named in
+/// braces, `{{` and `}}` are literal braces and `\n`, `\t` and `\\` are a line
+/// break, a tab and a backslash
+#[derive(Clone, PartialEq, Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Template> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(n) => name.push(n),
+                            None => {
+                                return specific_fail!(format!("the field {{{} in the template \
+                                                               isn't closed",
+                                                              name))
+                            }
+                        }
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(text));
+                        text = String::new();
+                    }
+                    parts.push(Part::Field(name.trim().to_lowercase()));
+                }
+                '}' => return specific_fail_str!("a } in the template has to be written as }}"),
+                '\\' => {
+                    match chars.next() {
+                        Some('n') => text.push('\n'),
+                        Some('t') => text.push('\t'),
+                        Some(n) => text.push(n),
+                        None => text.push('\\'),
+                    }
+                }
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template { parts: parts })
+    }
+
+    // fill in the template with `value`, which gives the value of a field or
+    // None if there is no such field
+    fn render<F>(&self, fields: &[&str], value: F) -> Result<String>
+        where F: Fn(&str) -> Result<Option<String>>
+    {
+        let mut out = String::new();
+        for part in self.parts.iter() {
+            match *part {
+                Part::Text(ref t) => out.push_str(t),
+                Part::Field(ref name) => {
+                    match try!(value(name)) {
+                        Some(v) => out.push_str(&v),
+                        None => {
+                            return specific_fail!(format!("there is no field {{{}}} to use in \
+                                                           the template, use one of {}",
+                                                          name,
+                                                          fields.join(", ")))
+                        }
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// the template filled in for `note`
+    pub fn render_note(&self, note: &Item) -> Result<String> {
+        self.render(&NOTE_FIELDS, |name| note_field(note, name))
+    }
+
+    /// the template filled in for `note` found in `profile`, which also has the
+    /// field {profile}
+    pub fn render_hit(&self, profile: &str, note: &Item) -> Result<String> {
+        let mut fields = NOTE_FIELDS.to_vec();
+        fields.push("profile");
+        self.render(&fields, |name| {
+            if name == "profile" {
+                Ok(Some(profile.to_string()))
+            } else {
+                note_field(note, name)
+            }
+        })
+    }
+
+    /// the template filled in for the info about a profile
+    pub fn render_info(&self, info: &Info) -> Result<String> {
+        self.render(&INFO_FIELDS, |name| Ok(info.field(name)))
+    }
+}
+
+/// Something that prints lists of notes, single notes and profile info in an
+/// output format
+pub trait Formatter {
+    /// print a list of notes
+    fn list(&self, notes: &[Item]) -> Result<()>;
+    /// print a list without any notes, `message` says why to a person reading it
+    fn empty(&self, message: &str) -> Result<()>;
+    /// print a single note
+    fn note(&self, note: &Item) -> Result<()>;
+    /// print the info about a profile
+    fn info(&self, info: &Info) -> Result<()>;
+    /// print the notes found in several profiles, with the name of the profile
+    /// each is in
+    fn hits(&self, hits: &[(String, Vec<Item>)]) -> Result<()>;
+}
+
+/// the formatter picked by the command line, `template` is the template from
+/// `--template` (after looking it up in the config file) and `highlight` what
+/// the column layout highlights in the notes it lists
+pub fn formatter(flags: &BoolFlags,
+                 template: &str,
+                 highlight: Option<Matcher>)
+                 -> Result<Box<Formatter>> {
+    if !template.is_empty() {
+        Ok(Box::new(TemplateFormatter { template: try!(Template::parse(template)) }))
+    } else if flags.json {
+        Ok(Box::new(Json))
+    } else if flags.csv || flags.tsv {
+        Ok(Box::new(Table { sep: if flags.csv { ',' } else { '\t' } }))
+    } else {
+        Ok(Box::new(Text {
+            condensed: flags.condensed,
+            search_body: flags.search_body,
+            highlight: highlight,
+        }))
+    }
+}
+
+/// print the header of the column layout of a list
+pub fn print_header(line_format: &LineFormat) -> Result<()> {
+    let mut t = match stdout() {
+        Some(t) => t,
+        None => return specific_fail_str!("could not retrieve standard output."),
+    };
+    let column_seperator: String = repeat(' ')
+                                       .take(line_format.colsep)
+                                       .collect();
+    let header_seperator: String = repeat('-')
+                                       .take(line_format.line_width())
+                                       .collect();
+    let tty = color_output();
+    let status = if line_format.status_width == 0 {
+        "".to_string()
+    } else {
+        format_field(&"status".to_string(), line_format.status_width, false) + &*column_seperator
+    };
+    let tags = if line_format.tags_width == 0 {
+        "".to_string()
+    } else {
+        format_field(&"tags".to_string(), line_format.tags_width, false) + &*column_seperator
+    };
+    let due = if line_format.due_width == 0 {
+        "".to_string()
+    } else {
+        format_field(&"due".to_string(), line_format.due_width, false) + &*column_seperator
+    };
+    if tty {
+        try!(t.attr(Bold));
+    }
+    try!(write!(t,
+                "{1}{0}{2}{0}{3}{4}{5}{6}\n{7}\n",
+                column_seperator,
+                format_field(&"id".to_string(), line_format.id_width, false),
+                format_field(&"title".to_string(), line_format.title_width, false),
+                status,
+                tags,
+                due,
+                format_field(&"last touched".to_string(),
+                             line_format.touched_width,
+                             false),
+                header_seperator));
+    if tty {
+        try!(t.reset());
+    }
+    Ok(())
+}
+
+/// The column layout for lists and the field by field view of a note, bold
+/// when printing to a terminal
+pub struct Text {
+    pub condensed: bool,
+    /// print the bodies of the notes in a list below them
+    pub search_body: bool,
+    /// highlight what this matches in the titles and bodies of the notes
+    pub highlight: Option<Matcher>,
+}
+
+impl Formatter for Text {
+    fn list(&self, notes: &[Item]) -> Result<()> {
+        let line_format = try!(LineFormat::new(notes, self.condensed, self.search_body));
+        if !self.condensed {
+            try!(print_header(&line_format));
+        }
+        for n in notes.iter() {
+            try!(n.print(&line_format, self.search_body, self.highlight.as_ref()));
+        }
+        Ok(())
+    }
+
+    fn empty(&self, message: &str) -> Result<()> {
+        println!("{}", message);
+        Ok(())
+    }
+
+    fn note(&self, note: &Item) -> Result<()> {
+        let tty = color_output();
+        // the condensed view is a `name: value` line per field, the expanded
+        // one underlines each name and leaves a blank line after the value
+        let field = |name: &str, value: &str| {
+            if self.condensed {
+                pretty_line(&format!("{}: ", name), &format!("{}\n", value), tty)
+            } else {
+                let line: String = repeat('-').take(name.len()).collect();
+                pretty_line(&format!("{}\n{}\n", name, line), &format!("{}\n\n", value), tty)
+            }
+        };
+        try!(field("id", &note.id.to_string()));
+        // the uuid is only of use to scripts, keep it out of the condensed view
+        if !self.condensed {
+            try!(field("uuid", &note.uuid));
+        }
+        try!(field("title", &note.title));
+        if note.status != Status::Blank {
+            if self.condensed {
+                try!(field("status", &note.status.to_string()));
+            } else {
+                try!(field("status", &format!("{:?}", note.status)));
+            }
+        }
+        if !note.tags.is_empty() {
+            try!(field("tags", &note.tags.join(", ")));
+        }
+        if let Some(ref due) = note.due {
+            try!(field("due", &try!(localize_due_string(due))));
+        }
+        try!(field("last touched", &try!(localize_last_touched_string(&note.last_touched))));
+        if !note.body.is_empty() {
+            try!(field("body", &note.body));
+        }
+        Ok(())
+    }
+
+    fn info(&self, info: &Info) -> Result<()> {
+        let tty = color_output();
+        try!(pretty_line("name: ", &format!("{}\n", info.name), tty));
+        try!(pretty_line("encrypted: ", &format!("{}\n", info.encrypted), tty));
+        if let Some(ref kdf) = info.kdf {
+            try!(pretty_line("kdf: ", &format!("{}\n", kdf), tty));
+        }
+        try!(pretty_line("notes: ", &format!("{}\n", info.notes), tty));
+        try!(pretty_line("statuses: ",
+                         &format!("none: {}, started: {}, urgent: {}\n",
+                                  info.none,
+                                  info.started,
+                                  info.urgent),
+                         tty));
+        try!(pretty_line("note ages: ",
+                         &format!("oldest: {}, newest: {}\n", info.oldest, info.newest),
+                         tty));
+        Ok(())
+    }
+
+    fn hits(&self, hits: &[(String, Vec<Item>)]) -> Result<()> {
+        // a list per profile under its name
+        let tty = color_output();
+        for (i, &(ref profile, ref notes)) in hits.iter().enumerate() {
+            if i > 0 {
+                println!("");
+            }
+            try!(pretty_line(&format!("# {}\n", profile), "", tty));
+            try!(self.list(notes));
+        }
+        Ok(())
+    }
+}
+
+/// A note found searching several profiles, as it is printed by `--json`
+#[derive(RustcEncodable)]
+struct Hit<'a> {
+    profile: &'a str,
+    note: &'a Item,
+}
+
+/// Pretty printed JSON, a list of notes is an array
+pub struct Json;
+
+impl Formatter for Json {
+    fn list(&self, notes: &[Item]) -> Result<()> {
+        println!("{}", as_pretty_json(&notes.to_vec()));
+        Ok(())
+    }
+
+    fn empty(&self, _: &str) -> Result<()> {
+        println!("[]");
+        Ok(())
+    }
+
+    fn note(&self, note: &Item) -> Result<()> {
+        println!("{}", as_pretty_json(note));
+        Ok(())
+    }
+
+    fn info(&self, info: &Info) -> Result<()> {
+        println!("{}", as_pretty_json(info));
+        Ok(())
+    }
+
+    fn hits(&self, hits: &[(String, Vec<Item>)]) -> Result<()> {
+        let hits: Vec<Hit> = hits.iter()
+                                 .flat_map(|&(ref profile, ref notes)| {
+                                     notes.iter().map(move |n| {
+                                         Hit {
+                                             profile: profile,
+                                             note: n,
+                                         }
+                                     })
+                                 })
+                                 .collect();
+        println!("{}", as_pretty_json(&hits));
+        Ok(())
+    }
+}
+
+/// A CSV or TSV table with a header row
+pub struct Table {
+    /// `,` for CSV and a tab for TSV
+    pub sep: char,
+}
+
+impl Formatter for Table {
+    fn list(&self, notes: &[Item]) -> Result<()> {
+        print!("{}", try!(to_table(notes, self.sep)));
+        Ok(())
+    }
+
+    fn empty(&self, _: &str) -> Result<()> {
+        print!("{}", header(self.sep));
+        Ok(())
+    }
+
+    fn note(&self, note: &Item) -> Result<()> {
+        print!("{}{}", header(self.sep), try!(note_row(note, self.sep)));
+        Ok(())
+    }
+
+    fn info(&self, info: &Info) -> Result<()> {
+        let names: Vec<String> = INFO_FIELDS.iter().map(|f| f.to_string()).collect();
+        let values: Vec<String> = INFO_FIELDS.iter()
+                                             .map(|f| info.field(f).unwrap_or_else(String::new))
+                                             .collect();
+        print!("{}{}", row(&names, self.sep), row(&values, self.sep));
+        Ok(())
+    }
+
+    fn hits(&self, hits: &[(String, Vec<Item>)]) -> Result<()> {
+        print!("{}", try!(to_profiles_table(hits, self.sep)));
+        Ok(())
+    }
+}
+
+/// A user template filled in for each note, a line break is added after a note
+/// unless the template ends with one
+pub struct TemplateFormatter {
+    pub template: Template,
+}
+
+fn print_rendered(s: &str) {
+    if s.ends_with('\n') {
+        print!("{}", s);
+    } else {
+        println!("{}", s);
+    }
+}
+
+impl Formatter for TemplateFormatter {
+    fn list(&self, notes: &[Item]) -> Result<()> {
+        for note in notes.iter() {
+            print_rendered(&try!(self.template.render_note(note)));
+        }
+        Ok(())
+    }
+
+    fn empty(&self, _: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn note(&self, note: &Item) -> Result<()> {
+        print_rendered(&try!(self.template.render_note(note)));
+        Ok(())
+    }
+
+    fn info(&self, info: &Info) -> Result<()> {
+        print_rendered(&try!(self.template.render_info(info)));
+        Ok(())
+    }
+
+    fn hits(&self, hits: &[(String, Vec<Item>)]) -> Result<()> {
+        for &(ref profile, ref notes) in hits.iter() {
+            for note in notes.iter() {
+                print_rendered(&try!(self.template.render_hit(profile, note)));
+            }
+        }
+        Ok(())
+    }
+}
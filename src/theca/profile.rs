@@ -1,39 +1,179 @@
 // std lib imports
-use std::io::{stdin, Read, Write};
+use std::io::{stdin, Read};
 use std::fs::{File, create_dir};
+use std::collections::BTreeMap;
 
 // random things
-use regex::Regex;
-use rustc_serialize::Encodable;
+use rustc_serialize::{Encodable, Decodable, Decoder};
 use rustc_serialize::json::{decode, as_pretty_json, Encoder};
-use time::{now, strftime};
+use time::{now, strftime, Tm, Duration};
 
 // theca imports
 use utils::c::istty;
 use utils::{drop_to_editor, pretty_line, get_yn_input, sorted_print, localize_last_touched_string,
-            parse_last_touched, find_profile_folder, profile_fingerprint};
+            parse_last_touched, find_profile_folder, profile_fingerprint, format_field,
+            start_of_day, add_days, agenda_print, profile_kdf, display_width,
+            write_atomic, new_uuid, print_diff};
 use errors::{Result, Error};
-use crypt::{encrypt, decrypt, password_to_key};
-use item::{Status, Item};
+use crypt::{encrypt_profile, decrypt_profile, Kdf, LEGACY_PBKDF2_ROUNDS};
+use item::{Status, Item, Revision};
+use diff::unified_diff;
+use lock::ProfileLock;
+use merge::{merge_interactive, merge_trash};
+use journal::{self, Entry, Link, LogLine, merge_journal};
+use config::color_output;
+use output::{Formatter, Info};
+use index::{self, Index};
+use query::Query;
+use matcher::Matcher;
 
 pub use libc::{STDIN_FILENO, STDOUT_FILENO, STDERR_FILENO};
 
-use {parse_cmds, Args, BoolFlags};
+use {Args, BoolFlags};
 
 /// datetime formating string
 pub static DATEFMT: &'static str = "%F %T %z";
 /// short datetime formating string for printing
 pub static DATEFMT_SHORT: &'static str = "%F %T";
+/// date formating string for printing due dates
+pub static DATEFMT_DAY: &'static str = "%F";
+/// shortest uuid prefix that can be used to refer to a note
+pub const UUID_PREFIX_MIN_LEN: usize = 4;
+/// numbers shorter than this are note ids rather than uuid prefixes
+pub const UUID_PREFIX_MIN_DIGITS: usize = 8;
+/// days notes are kept in the trash for when no retention period is set
+pub const TRASH_DAYS: i64 = 30;
 
 /// Main container of a theca profile file
-#[derive(RustcDecodable, RustcEncodable, Clone)]
+#[derive(RustcEncodable, Clone)]
 pub struct Profile {
     pub encrypted: bool,
     pub notes: Vec<Item>,
+    pub trash: Vec<Trashed>,
+    pub journal: Vec<Entry>,
+}
+
+// manually decoded so profiles written before the trash and journal existed
+// still load
+impl Decodable for Profile {
+    fn decode<D: Decoder>(decoder: &mut D) -> ::std::result::Result<Profile, D::Error> {
+        decoder.read_struct("Profile", 4usize, |d| {
+            Ok(Profile {
+                encrypted: try!(d.read_struct_field("encrypted", 0usize, Decodable::decode)),
+                notes: try!(d.read_struct_field("notes", 1usize, Decodable::decode)),
+                trash: try!(d.read_struct_field("trash", 2usize, Option::<Vec<Trashed>>::decode))
+                           .unwrap_or_else(Vec::new),
+                journal: try!(d.read_struct_field("journal",
+                                                  3usize,
+                                                  Option::<Vec<Entry>>::decode))
+                             .unwrap_or_else(Vec::new),
+            })
+        })
+    }
+}
+
+/// A deleted note waiting in the trash
+#[derive(RustcDecodable, RustcEncodable, Clone, Debug)]
+pub struct Trashed {
+    /// when the note was deleted
+    pub deleted: String,
+    pub note: Item,
+}
+
+/// Notes with a due date grouped by how soon they are due
+#[derive(RustcEncodable)]
+pub struct Agenda {
+    pub overdue: Vec<Item>,
+    pub today: Vec<Item>,
+    pub this_week: Vec<Item>,
+    pub later: Vec<Item>,
+}
+
+/// The notes in a profile file as they were when it was loaded, used to find and
+/// merge changes made to the file before it is saved again
+#[derive(Clone, Default)]
+pub struct Snapshot {
+    /// `profile_fingerprint` of the file, 0 if it didn't exist
+    pub fingerprint: u64,
+    pub notes: Vec<Item>,
+    pub trash: Vec<Trashed>,
+    pub journal: Vec<Entry>,
+    key: String,
+    encrypted: bool,
+}
+
+impl Snapshot {
+    /// a snapshot of `profile` right after it was saved with `args`
+    pub fn after_save(profile: &Profile, args: &Args) -> Result<Snapshot> {
+        let mut profile_path = try!(find_profile_folder(&args.flag_profile_folder));
+        profile_path.push(&(args.flag_profile.to_string() + ".json"));
+        Ok(Snapshot {
+            fingerprint: try!(profile_fingerprint(profile_path)),
+            notes: profile.notes.clone(),
+            trash: profile.trash.clone(),
+            journal: profile.journal.clone(),
+            key: args.flag_key.clone(),
+            encrypted: profile.encrypted,
+        })
+    }
+}
+
+/// warn that editing a note from an encrypted profile writes its body to a
+/// temporary file, returns whether to carry on
+pub fn confirm_editor_tempfile() -> Result<bool> {
+    let message = format!("{0}\n\n{1}\n{2}\n\n{0}\n{3}\n",
+                          "## [WARNING] ##",
+                          "continuing will write the body of the decrypted note to a temporary",
+                          "file, increasing the possibilty it could be recovered later.",
+                          "Are you sure you want to continue?\n");
+    get_yn_input(&message)
+}
+
+/// parse a trash retention period in days, an empty string means the default and
+/// 0 keeps notes in the trash forever
+pub fn parse_trash_days(days: &str) -> Result<i64> {
+    if days.is_empty() {
+        return Ok(TRASH_DAYS);
+    }
+    match days.parse::<i64>() {
+        Ok(d) if d >= 0 => Ok(d),
+        _ => {
+            specific_fail!(format!("invalid trash retention period '{}', it should be a number \
+                                    of days",
+                                   days))
+        }
+    }
+}
+
+// what an `<id>` argument refers to
+enum NoteRef {
+    Id(usize),
+    Uuid(String),
+}
+
+fn parse_note_ref(id: &str) -> Result<NoteRef> {
+    // short numbers are always note ids, even if no note has that id yet
+    if id.len() < UUID_PREFIX_MIN_DIGITS && id.chars().all(|c| c.is_digit(10)) {
+        return match id.parse::<usize>() {
+            Ok(i) => Ok(NoteRef::Id(i)),
+            Err(_) => specific_fail!(format!("invalid note id '{}'", id)),
+        };
+    }
+    let prefix = id.to_lowercase();
+    if prefix.len() < UUID_PREFIX_MIN_LEN || !prefix.chars().all(|c| c.is_digit(16) || c == '-') {
+        return specific_fail!(format!("'{}' isn't a note id or a uuid prefix (of at least {} \
+                                       characters)",
+                                      id,
+                                      UUID_PREFIX_MIN_LEN));
+    }
+    Ok(NoteRef::Uuid(prefix))
 }
 
 impl Profile {
-    fn from_scratch(profile_folder: &str, encrypted: bool, yes: bool) -> Result<(Profile, u64)> {
+    fn from_scratch(profile_folder: &str,
+                    encrypted: bool,
+                    yes: bool)
+                    -> Result<(Profile, Snapshot)> {
         let profile_path = try!(find_profile_folder(profile_folder));
         // if the folder doesn't exist, make it yo!
         if !profile_path.exists() {
@@ -49,15 +189,17 @@ impl Profile {
         Ok((Profile {
             encrypted: encrypted,
             notes: vec![],
+            trash: vec![],
+            journal: vec![],
         },
-            0u64))
+            Snapshot::default()))
     }
 
     fn from_existing_profile(profile_name: &str,
                              profile_folder: &str,
                              key: &str,
                              encrypted: bool)
-                             -> Result<(Profile, u64)> {
+                             -> Result<(Profile, Snapshot)> {
         // set profile folder
         let mut profile_path = try!(find_profile_folder(profile_folder));
 
@@ -70,8 +212,7 @@ impl Profile {
             let mut contents_buf = vec![];
             try!(file.read_to_end(&mut contents_buf));
             let contents = if encrypted {
-                let key = password_to_key(&key[..]);
-                try!(String::from_utf8(try!(decrypt(&*contents_buf, &*key))))
+                try!(String::from_utf8(try!(decrypt_profile(&*contents_buf, key, &profile_path))))
             } else {
                 try!(String::from_utf8(contents_buf))
             };
@@ -81,8 +222,15 @@ impl Profile {
                     return specific_fail!(format!("invalid JSON in {}", profile_path.display()))
                 }
             };
-            let fingerprint = try!(profile_fingerprint(profile_path));
-            Ok((decoded, fingerprint))
+            let snapshot = Snapshot {
+                fingerprint: try!(profile_fingerprint(profile_path)),
+                notes: decoded.notes.clone(),
+                trash: decoded.trash.clone(),
+                journal: decoded.journal.clone(),
+                key: key.to_string(),
+                encrypted: encrypted,
+            };
+            Ok((decoded, snapshot))
         } else if profile_path.exists() {
             specific_fail!(format!("{} is not a file.", profile_path.display()))
         } else {
@@ -97,7 +245,7 @@ impl Profile {
                new_profile: bool,
                encrypted: bool,
                yes: bool)
-               -> Result<(Profile, u64)> {
+               -> Result<(Profile, Snapshot)> {
         if new_profile {
             Profile::from_scratch(profile_folder, encrypted, yes)
         } else {
@@ -105,21 +253,142 @@ impl Profile {
         }
     }
 
-    /// remove all notes from the profile
-    pub fn clear(&mut self, yes: bool) -> Result<()> {
+    /// remove all notes from the profile, moving them to the trash unless `purge`
+    /// is set
+    pub fn clear(&mut self, yes: bool, purge: bool) -> Result<()> {
         if !yes {
-            let message = "are you sure you want to delete all the notes in this profile?\n";
+            let message = if purge {
+                "are you sure you want to permanently delete all the notes in this profile?\n"
+            } else {
+                "are you sure you want to delete all the notes in this profile?\n"
+            };
             if !try!(get_yn_input(&message)) {
                 return specific_fail_str!("ok bye ♥");
             }
         }
-        self.notes.truncate(0);
+        if purge {
+            self.notes.truncate(0);
+        } else {
+            let deleted = try!(strftime(DATEFMT, &now()));
+            for n in self.notes.drain(..) {
+                self.trash.push(Trashed {
+                    deleted: deleted.clone(),
+                    note: n,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// permanently delete everything in the trash
+    pub fn empty_trash(&mut self, yes: bool) -> Result<()> {
+        if self.trash.is_empty() {
+            println!("the trash is already empty");
+            return Ok(());
+        }
+        if !yes {
+            let message = format!("are you sure you want to permanently delete the {} notes in \
+                                   the trash?\n",
+                                  self.trash.len());
+            if !try!(get_yn_input(&message)) {
+                return specific_fail_str!("ok bye ♥");
+            }
+        }
+        println!("permanently deleted {} notes", self.trash.len());
+        self.trash.truncate(0);
+        Ok(())
+    }
+
+    /// permanently delete notes that have been in the trash for more than `days`
+    /// days (along with what the journal kept of them), returning how many were
+    /// removed
+    pub fn purge_trash(&mut self, days: i64, now: &Tm) -> usize {
+        if days <= 0 {
+            return 0;
+        }
+        let cutoff = *now - Duration::days(days);
+        let (kept, purged): (Vec<Trashed>, Vec<Trashed>) =
+            self.trash.drain(..).partition(|t| {
+                match parse_last_touched(&t.deleted) {
+                    Ok(d) => d > cutoff,
+                    Err(_) => true,
+                }
+            });
+        self.trash = kept;
+        let uuids: Vec<String> = purged.iter().map(|t| t.note.uuid.clone()).collect();
+        journal::forget(&mut self.journal, &uuids);
+        purged.len()
+    }
+
+    /// move a note from the trash back into the profile
+    pub fn restore_note(&mut self, id: &str) -> Result<()> {
+        let matches: Vec<usize> = match try!(parse_note_ref(id)) {
+            NoteRef::Id(i) => {
+                (0..self.trash.len()).filter(|&e| self.trash[e].note.id == i).collect()
+            }
+            NoteRef::Uuid(prefix) => {
+                (0..self.trash.len())
+                    .filter(|&e| self.trash[e].note.uuid.starts_with(&*prefix))
+                    .collect()
+            }
+        };
+        let pos = match matches.len() {
+            0 => return specific_fail!(format!("note {} isn't in the trash", id)),
+            1 => matches[0],
+            _ => {
+                return specific_fail!(format!("{} matches {} notes in the trash, use a uuid \
+                                               prefix instead",
+                                              id,
+                                              matches.len()))
+            }
+        };
+        let note = self.trash.remove(pos).note;
+        let old_id = note.id;
+        let new_id = self.insert_note(note);
+        if old_id == new_id {
+            println!("restored note {}", new_id);
+        } else {
+            println!("restored note {} as note {}", old_id, new_id);
+        }
         Ok(())
     }
 
+    /// put a note back into the profile in id order, giving it a new id if its id
+    /// has been given to another note, returns the id it ended up with
+    pub fn insert_note(&mut self, mut note: Item) -> usize {
+        if self.notes.iter().any(|n| n.id == note.id) {
+            note.id = self.notes.iter().map(|n| n.id).max().unwrap_or(0) + 1;
+        }
+        let id = note.id;
+        let position = self.notes.iter().position(|n| n.id > note.id);
+        match position {
+            Some(i) => self.notes.insert(i, note),
+            None => self.notes.push(note),
+        }
+        id
+    }
+
+    /// print the notes in the trash
+    pub fn list_trash(&mut self, limit: usize, flags: BoolFlags, out: &Formatter) -> Result<()> {
+        let mut notes: Vec<Item> = self.trash.iter().map(|t| t.note.clone()).collect();
+        // the JSON for the trash has when each note was deleted
+        if flags.json {
+            let mut trash = self.trash.clone();
+            if limit > 0 {
+                trash.truncate(limit);
+            }
+            println!("{}", as_pretty_json(&trash));
+            return Ok(());
+        }
+        if notes.is_empty() {
+            return out.empty("the trash is empty");
+        }
+        sorted_print(&mut notes, limit, flags, None, &[], None, None, None, out)
+    }
+
     // FIXME (this as well as transfer_note, shouldn't *need* to take all of `args`)
     /// save the profile back to file (either plaintext or encrypted)
-    pub fn save_to_file(&mut self, args: &Args, fingerprint: &u64) -> Result<()> {
+    pub fn save_to_file(&mut self, args: &Args, snapshot: &Snapshot) -> Result<()> {
         // set profile folder
         let mut profile_path = try!(find_profile_folder(&args.flag_profile_folder));
 
@@ -138,41 +407,37 @@ impl Profile {
             }
         }
 
-        if fingerprint > &0u64 {
-            let new_fingerprint = try!(profile_fingerprint(&profile_path));
-            if &new_fingerprint != fingerprint && !args.flag_yes {
-                let message = format!("changes have been made to the profile '{}' on disk since \
-                                       it was loaded, would you like to attempt to merge them?\n",
-                                      args.flag_profile);
-                if !try!(get_yn_input(&message)) {
-                    return specific_fail_str!("ok bye ♥");
-                }
-                let mut new_args = args.clone();
-                if args.flag_editor {
-                    new_args.flag_editor = false;
-                    new_args.flag_body[0] = match self.notes.last() {
-                        Some(n) => n.body.clone(),
-                        None => "".to_string(),
-                    };
-                }
-                let (mut changed_profile, changed_fingerprint) = try!(
-                    Profile::new(
-                        &new_args.flag_profile,
-                        &new_args.flag_profile_folder,
-                        &new_args.flag_key,
-                        new_args.cmd_new_profile,
-                        new_args.flag_encrypted,
-                        new_args.flag_yes
-                    )
-                );
-                try!(parse_cmds(&mut changed_profile, &mut new_args, &changed_fingerprint));
-                try!(changed_profile.save_to_file(&new_args, &0u64));
-                return Ok(());
-            }
+        // merge in any changes made to the profile on disk since it was loaded
+        if snapshot.fingerprint > 0 &&
+           try!(profile_fingerprint(&profile_path)) != snapshot.fingerprint {
+            let (on_disk, _) = try!(Profile::from_existing_profile(&args.flag_profile,
+                                                                   &args.flag_profile_folder,
+                                                                   &snapshot.key,
+                                                                   snapshot.encrypted));
+            self.notes = try!(merge_interactive(&snapshot.notes,
+                                                &self.notes,
+                                                &on_disk.notes,
+                                                args.flag_yes));
+            self.trash = merge_trash(&snapshot.trash, &self.trash, &on_disk.trash, &self.notes);
+            self.journal = merge_journal(&snapshot.journal, &self.journal, &on_disk.journal);
+            println!("merged changes made to the profile '{}' on disk", args.flag_profile);
         }
 
-        // open file
-        let mut file = try!(File::create(profile_path));
+        // pick the key derivation function from the profile on disk, an explicit
+        // --kdf-cost wins, otherwise keep the parameters the profile already uses
+        // (upgrading PBKDF2 and legacy profiles to the default scrypt parameters)
+        let kdf = if !args.flag_kdf_cost.is_empty() {
+            try!(Kdf::from_cost_arg(&args.flag_kdf_cost))
+        } else {
+            match try!(profile_kdf(&profile_path)) {
+                Some(k @ Kdf::Scrypt { .. }) => k,
+                _ => Kdf::default_scrypt(),
+            }
+        };
+
+        // drop notes that have been in the trash for longer than the retention period
+        let trash_days = try!(parse_trash_days(&args.flag_trash_days));
+        self.purge_trash(trash_days, &now());
 
         // encode to buffer
         let mut json_prof = String::new();
@@ -183,14 +448,15 @@ impl Profile {
 
         // encrypt json if its an encrypted profile
         let buffer = if self.encrypted {
-            let key = password_to_key(&*args.flag_key);
-            try!(encrypt(&json_prof.into_bytes(), &*key))
+            try!(encrypt_profile(&json_prof.into_bytes(), &*args.flag_key, kdf, &profile_path))
         } else {
             json_prof.into_bytes()
         };
 
-        // write buffer to file
-        try!(file.write_all(&buffer));
+        // write buffer to file, the profile on disk is only replaced once the new
+        // version has been completely written
+        try!(write_atomic(&profile_path, &buffer, args.flag_backup));
+        try!(index::update_saved(self, &profile_path));
 
         Ok(())
     }
@@ -205,18 +471,24 @@ impl Profile {
                                           args.arg_name[0]));
         }
 
+        let id = try!(self.find_id(&args.arg_id[0]));
         let mut trans_args = args.clone();
         trans_args.flag_profile = args.arg_name[0].clone();
-        let (mut trans_profile, trans_fingerprint) = try!(Profile::new(&args.arg_name[0],
-                                                                       &args.flag_profile_folder,
-                                                                       &args.flag_key,
-                                                                       args.cmd_new_profile,
-                                                                       args.flag_encrypted,
-                                                                       args.flag_yes));
+        let _trans_lock = try!(ProfileLock::acquire(&args.arg_name[0],
+                                                    &args.flag_profile_folder,
+                                                    args.flag_lock_timeout));
+        let (mut trans_profile, trans_snapshot) = try!(Profile::new(&args.arg_name[0],
+                                                                    &args.flag_profile_folder,
+                                                                    &args.flag_key,
+                                                                    args.cmd_new_profile,
+                                                                    args.flag_encrypted,
+                                                                    args.flag_yes));
+        let before = self.clone();
+        let trans_before = trans_profile.clone();
 
         if self.notes
                .iter()
-               .find(|n| n.id == args.arg_id[0])
+               .find(|n| n.id == id)
                .map(|n| {
                    trans_profile.add_note(&n.title,
                                           &[n.body.clone()],
@@ -224,34 +496,163 @@ impl Profile {
                                           false,
                                           false,
                                           false)
+                                .map(|_| {
+                                    if let Some(t) = trans_profile.notes.last_mut() {
+                                        t.uuid = n.uuid.clone();
+                                        t.tags = n.tags.clone();
+                                        t.due = n.due.clone();
+                                        t.history = n.history.clone();
+                                    }
+                                })
                })
                .is_some() {
             if self.notes
                    .iter()
-                   .position(|n| n.id == args.arg_id[0])
+                   .position(|n| n.id == id)
                    .map(|e| self.notes.remove(e))
                    .is_some() {
-                try!(trans_profile.save_to_file(&trans_args, &trans_fingerprint))
+                // journal both halves so undoing either one puts the note back
+                let ours = try!(Entry::between(&format!("transfer {} to {}", id, args.arg_name[0]),
+                                               &before,
+                                               self));
+                let theirs = try!(Entry::between(&format!("transfer {} from {}",
+                                                          id,
+                                                          args.flag_profile),
+                                                 &trans_before,
+                                                 &trans_profile));
+                if let (Some(mut ours), Some(mut theirs)) = (ours, theirs) {
+                    ours.link = Some(Link {
+                        profile: args.arg_name[0].clone(),
+                        entry: theirs.uuid.clone(),
+                    });
+                    theirs.link = Some(Link {
+                        profile: args.flag_profile.clone(),
+                        entry: ours.uuid.clone(),
+                    });
+                    journal::record(&mut self.journal, ours);
+                    journal::record(&mut trans_profile.journal, theirs);
+                }
+                try!(trans_profile.save_to_file(&trans_args, &trans_snapshot))
             } else {
                 return specific_fail!(format!("couldn't remove note {} in {}, aborting nothing \
                                                will be saved",
-                                              args.arg_id[0],
+                                              id,
                                               args.flag_profile));
             }
         } else {
             return specific_fail!(format!("could not transfer note {} from {} -> {}",
-                                          args.arg_id[0],
+                                          id,
                                           args.flag_profile,
                                           args.arg_name[0]));
         }
         println!("transfered [{}: note {} -> {}: note {}]",
                  args.flag_profile,
-                 args.arg_id[0],
+                 id,
                  args.arg_name[0],
                  trans_profile.notes.last().map_or(0, |n| n.id));
         Ok(())
     }
 
+    /// undo the last command recorded in the journal
+    pub fn undo(&mut self, args: &Args) -> Result<()> {
+        self.step_journal(args, false)
+    }
+
+    /// redo the last command that was undone
+    pub fn redo(&mut self, args: &Args) -> Result<()> {
+        self.step_journal(args, true)
+    }
+
+    // FIXME (like transfer_note, shouldn't *need* to take all of `args`)
+    fn step_journal(&mut self, args: &Args, forward: bool) -> Result<()> {
+        let pos = if forward {
+            journal::redo_pos(&self.journal)
+        } else {
+            journal::undo_pos(&self.journal)
+        };
+        let pos = match pos {
+            Some(p) => p,
+            None if forward => return specific_fail_str!("there is nothing to redo"),
+            None => return specific_fail_str!("there is nothing to undo"),
+        };
+        let entry = self.journal[pos].clone();
+        try!(journal::apply(self, &entry, forward, args.flag_yes));
+        self.journal[pos].undone = !forward;
+
+        // a transfer also changed another profile
+        if let Some(ref link) = entry.link {
+            let mut other_args = args.clone();
+            other_args.flag_profile = link.profile.clone();
+            other_args.cmd_new_profile = false;
+            let _other_lock = try!(ProfileLock::acquire(&link.profile,
+                                                        &args.flag_profile_folder,
+                                                        args.flag_lock_timeout));
+            let (mut other, other_snapshot) = try!(Profile::new(&link.profile,
+                                                                &args.flag_profile_folder,
+                                                                &args.flag_key,
+                                                                false,
+                                                                args.flag_encrypted,
+                                                                args.flag_yes));
+            match other.journal.iter().position(|e| e.uuid == link.entry) {
+                Some(p) if other.journal[p].undone == forward => {
+                    let other_entry = other.journal[p].clone();
+                    try!(journal::apply(&mut other, &other_entry, forward, args.flag_yes));
+                    other.journal[p].undone = !forward;
+                    try!(other.save_to_file(&other_args, &other_snapshot));
+                }
+                Some(_) => {}
+                None => {
+                    println!("the other half of '{}' is no longer in the journal of '{}', only \
+                              '{}' was changed",
+                             entry.command,
+                             link.profile,
+                             args.flag_profile)
+                }
+            }
+        }
+
+        if forward {
+            println!("redid '{}'", entry.command);
+        } else {
+            println!("undid '{}'", entry.command);
+        }
+        Ok(())
+    }
+
+    /// print the commands in the journal, newest first
+    pub fn print_log(&self, limit: usize, json: bool) -> Result<()> {
+        let mut lines: Vec<LogLine> = self.journal.iter().rev().map(|e| e.log_line()).collect();
+        if limit > 0 {
+            lines.truncate(limit);
+        }
+        if json {
+            println!("{}", as_pretty_json(&lines));
+            return Ok(());
+        }
+        if lines.is_empty() {
+            println!("nothing has been recorded in the journal yet");
+            return Ok(());
+        }
+        let width = lines.iter().map(|l| l.id.to_string().len()).max().unwrap_or(0).max(2);
+        let times = try!(lines.iter()
+                              .map(|l| localize_last_touched_string(&l.time))
+                              .collect::<Result<Vec<String>>>());
+        let time_width = times.iter().map(|t| t.chars().count()).max().unwrap_or(0).max(4);
+        try!(pretty_line(&format!("{}  {}  command\n",
+                                  format_field(&"id".to_string(), width, false),
+                                  format_field(&"time".to_string(), time_width, false)),
+                         "",
+                         color_output()));
+        for (l, time) in lines.iter().zip(times.iter()) {
+            println!("{}  {}  {}{}",
+                     format_field(&l.id.to_string(), width, false),
+                     format_field(time, time_width, false),
+                     l.command,
+                     if l.undone { " (undone)" } else { "" });
+        }
+        Ok(())
+    }
+
     /// add a item to the profile
     pub fn add_note(&mut self,
                     title: &str,
@@ -285,10 +686,14 @@ impl Profile {
         };
         self.notes.push(Item {
             id: new_id + 1,
+            uuid: new_uuid(),
             title: title,
             status: status.unwrap_or(Status::Blank),
             body: body,
             last_touched: try!(strftime(DATEFMT, &now())),
+            tags: vec![],
+            due: None,
+            history: vec![],
         });
         if print_msg {
             println!("note {} added", new_id + 1);
@@ -296,20 +701,83 @@ impl Profile {
         Ok(())
     }
 
-    /// delete an item from the profile
-    pub fn delete_note(&mut self, id: &[usize]) {
+    /// move notes from the profile to the trash
+    pub fn delete_note(&mut self, id: &[usize]) -> Result<()> {
+        let deleted = try!(strftime(DATEFMT, &now()));
         for nid in id.iter() {
-            let remove = self.notes
-                             .iter()
-                             .position(|n| &n.id == nid)
-                             .map(|e| self.notes.remove(e))
-                             .is_some();
-            if remove {
+            if self.trash_note(*nid, &deleted) {
                 println!("deleted note {}", nid);
             } else {
                 println!("note {} doesn't exist", nid);
             }
         }
+        Ok(())
+    }
+
+    /// move the note `id` to the trash, returns false if it doesn't exist
+    pub fn trash_note(&mut self, id: usize, deleted: &str) -> bool {
+        match self.notes.iter().position(|n| n.id == id) {
+            Some(e) => {
+                let note = self.notes.remove(e);
+                self.trash.push(Trashed {
+                    deleted: deleted.to_string(),
+                    note: note,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// find the id of the note `id` refers to, either a note id or a unique prefix
+    /// of a note uuid
+    pub fn find_id(&self, id: &str) -> Result<usize> {
+        let prefix = match try!(parse_note_ref(id)) {
+            NoteRef::Id(i) => return Ok(i),
+            NoteRef::Uuid(p) => p,
+        };
+        let matches: Vec<usize> = self.notes
+                                      .iter()
+                                      .filter(|n| n.uuid.starts_with(&*prefix))
+                                      .map(|n| n.id)
+                                      .collect();
+        match matches.len() {
+            0 => specific_fail!(format!("no note has a uuid starting with '{}'", id)),
+            1 => Ok(matches[0]),
+            _ => {
+                specific_fail!(format!("uuid prefix '{}' is ambiguous, it matches notes {}",
+                                       id,
+                                       matches.iter()
+                                              .map(|i| i.to_string())
+                                              .collect::<Vec<String>>()
+                                              .join(", ")))
+            }
+        }
+    }
+
+    /// add and remove tags on an item in the profile
+    pub fn tag_note(&mut self, id: usize, add: &[String], remove: &[String]) -> Result<()> {
+        let item_pos: usize = match self.notes.iter().position(|n| n.id == id) {
+            Some(i) => i,
+            None => return specific_fail!(format!("note {} doesn't exist", id)),
+        };
+        self.notes[item_pos].update_tags(add, remove);
+        self.notes[item_pos].last_touched = try!(strftime(DATEFMT, &now()));
+        Ok(())
+    }
+
+    /// set or clear the due date of an item in the profile
+    pub fn set_due(&mut self, id: usize, due: Option<Tm>) -> Result<()> {
+        let item_pos: usize = match self.notes.iter().position(|n| n.id == id) {
+            Some(i) => i,
+            None => return specific_fail!(format!("note {} doesn't exist", id)),
+        };
+        self.notes[item_pos].due = match due {
+            Some(d) => Some(try!(strftime(DATEFMT, &d))),
+            None => None,
+        };
+        self.notes[item_pos].last_touched = try!(strftime(DATEFMT, &now()));
+        Ok(())
     }
 
     /// edit an item in the profile
@@ -326,6 +794,7 @@ impl Profile {
             Some(i) => i,
             None => return specific_fail!(format!("note {} doesn't exist", id)),
         };
+        let old = self.notes[item_pos].clone();
         let use_editor = flags.editor;
         let encrypted = flags.encrypted;
         let yes = flags.yes;
@@ -355,17 +824,8 @@ impl Profile {
                 buf.to_owned()
             } else if use_editor {
                 if istty(STDOUT_FILENO) && istty(STDIN_FILENO) {
-                    if encrypted && !yes {
-                        let message = format!("{0}\n\n{1}\n{2}\n\n{0}\n{3}\n",
-                                              "## [WARNING] ##",
-                                              "continuing will write the body of the decrypted \
-                                               note to a temporary",
-                                              "file, increasing the possibilty it could be \
-                                               recovered later.",
-                                              "Are you sure you want to continue?\n");
-                        if !try!(get_yn_input(&message)) {
-                            return specific_fail_str!("ok bye ♥");
-                        }
+                    if encrypted && !yes && !try!(confirm_editor_tempfile()) {
+                        return specific_fail_str!("ok bye ♥");
                     }
                     let new_body = try!(drop_to_editor(&self.notes[item_pos].body));
                     if self.notes[item_pos].body != new_body {
@@ -381,14 +841,201 @@ impl Profile {
             }
         };
 
-        // update last_touched
+        // keep the old version and update last_touched
+        self.notes[item_pos].record_revision(&old);
         self.notes[item_pos].last_touched = try!(strftime(DATEFMT, &now()));
         println!("edited note {}", self.notes[item_pos].id);
         Ok(())
     }
 
+    fn note_pos(&self, id: usize) -> Result<usize> {
+        match self.notes.iter().position(|n| n.id == id) {
+            Some(i) => Ok(i),
+            None => specific_fail!(format!("note {} doesn't exist", id)),
+        }
+    }
+
+    /// the note `id` as it was at revision `rev`, along with the revision number
+    fn note_at_revision(&self, id: usize, rev: &str) -> Result<(usize, Item)> {
+        let note = &self.notes[try!(self.note_pos(id))];
+        let rev = match rev.trim_left_matches('r').parse::<usize>() {
+            Ok(r) => r,
+            Err(_) => return specific_fail!(format!("invalid revision '{}'", rev)),
+        };
+        match note.at_revision(rev) {
+            Some(n) => Ok((rev, n)),
+            None => {
+                specific_fail!(format!("note {} has no revision {}, revisions {} to {} are kept",
+                                       id,
+                                       rev,
+                                       note.oldest_revision(),
+                                       note.revision()))
+            }
+        }
+    }
+
+    /// print the revisions of a note, newest first
+    pub fn note_history(&self, id: usize, json: bool) -> Result<()> {
+        let note = &self.notes[try!(self.note_pos(id))];
+        let revisions: Vec<Revision> = (note.oldest_revision()..note.revision() + 1)
+                                           .rev()
+                                           .filter_map(|r| note.at_revision(r).map(|n| (r, n)))
+                                           .map(|(r, n)| {
+                                               Revision {
+                                                   rev: r,
+                                                   last_touched: n.last_touched,
+                                                   title: Some(n.title),
+                                                   status: Some(n.status),
+                                                   body: Some(n.body),
+                                               }
+                                           })
+                                           .collect();
+        if json {
+            println!("{}", as_pretty_json(&revisions));
+            return Ok(());
+        }
+
+        let width = note.revision().to_string().len().max(3);
+        let times = try!(revisions.iter()
+                                  .map(|r| localize_last_touched_string(&r.last_touched))
+                                  .collect::<Result<Vec<String>>>());
+        let time_width = times.iter().map(|t| t.chars().count()).max().unwrap_or(0).max(12);
+        try!(pretty_line(&format!("{}  {}  changed\n",
+                                  format_field(&"rev".to_string(), width, false),
+                                  format_field(&"last touched".to_string(), time_width, false)),
+                         "",
+                         color_output()));
+        for (r, time) in revisions.iter().zip(times.iter()) {
+            // the fields each revision changed are kept with the one before it
+            let changed = match note.history.iter().find(|h| h.rev + 1 == r.rev) {
+                Some(h) => h.changed().join(", "),
+                None if r.rev == 1 => "created".to_string(),
+                None => String::new(),
+            };
+            println!("{}  {}  {}",
+                     format_field(&r.rev.to_string(), width, false),
+                     format_field(time, time_width, false),
+                     changed);
+        }
+        Ok(())
+    }
+
+    /// print a unified diff of the body of a note between two revisions, by default
+    /// the previous and current ones, or the given one and the current one
+    pub fn diff_note(&self, id: usize, revs: &[String]) -> Result<()> {
+        let note = &self.notes[try!(self.note_pos(id))];
+        let current = note.revision().to_string();
+        let (from, to) = match revs.len() {
+            0 => {
+                if note.revision() == note.oldest_revision() {
+                    return specific_fail!(format!("note {} has no earlier revisions", id));
+                }
+                ((note.revision() - 1).to_string(), current)
+            }
+            1 => (revs[0].clone(), current),
+            _ => (revs[0].clone(), revs[1].clone()),
+        };
+        let (from, old) = try!(self.note_at_revision(id, &from));
+        let (to, new) = try!(self.note_at_revision(id, &to));
+        let diff = unified_diff(&old.body,
+                                &new.body,
+                                &format!("note {} (rev {})", id, from),
+                                &format!("note {} (rev {})", id, to));
+        if diff.is_empty() {
+            println!("the body of note {} is the same in revisions {} and {}", id, from, to);
+            return Ok(());
+        }
+        print_diff(&diff, color_output())
+    }
+
+    /// set the title, status and body of a note back to how they were at an
+    /// earlier revision, the current version is kept in the history
+    pub fn revert_note(&mut self, id: usize, rev: &str) -> Result<()> {
+        let (rev, reverted) = try!(self.note_at_revision(id, rev));
+        let pos = try!(self.note_pos(id));
+        let old = self.notes[pos].clone();
+        {
+            let note = &mut self.notes[pos];
+            note.title = reverted.title;
+            note.status = reverted.status;
+            note.body = reverted.body;
+        }
+        if self.notes[pos].record_revision(&old) {
+            self.notes[pos].last_touched = try!(strftime(DATEFMT, &now()));
+            println!("reverted note {} to revision {}", id, rev);
+        } else {
+            println!("note {} is already the same as revision {}", id, rev);
+        }
+        Ok(())
+    }
+
+    /// count how many notes carry each tag
+    pub fn tag_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for t in self.notes.iter().flat_map(|n| n.tags.iter()) {
+            *counts.entry(t.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// print every tag used in the profile along with how many notes use it
+    pub fn list_tags(&self, json: bool) -> Result<()> {
+        let counts = self.tag_counts();
+        if json {
+            println!("{}", as_pretty_json(&counts));
+        } else if counts.is_empty() {
+            println!("this profile has no tags");
+        } else {
+            let width = counts.keys().map(|t| display_width(t)).max().unwrap_or(0);
+            for (tag, count) in &counts {
+                println!("{}  {}", format_field(tag, width, false), count);
+            }
+        }
+        Ok(())
+    }
+
+    /// group the notes that have a due date by how soon they are due relative to `now`
+    pub fn agenda(&self, now: &Tm) -> Result<Agenda> {
+        let today = start_of_day(now);
+        let tomorrow = add_days(&today, 1);
+        let next_week = add_days(&today, 7);
+
+        let mut due_notes = vec![];
+        for n in self.notes.iter() {
+            if let Some(ref d) = n.due {
+                due_notes.push((try!(parse_last_touched(d)), n.clone()));
+            }
+        }
+        due_notes.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut agenda = Agenda {
+            overdue: vec![],
+            today: vec![],
+            this_week: vec![],
+            later: vec![],
+        };
+        for (due, n) in due_notes {
+            if due < today {
+                agenda.overdue.push(n);
+            } else if due < tomorrow {
+                agenda.today.push(n);
+            } else if due < next_week {
+                agenda.this_week.push(n);
+            } else {
+                agenda.later.push(n);
+            }
+        }
+        Ok(agenda)
+    }
+
+    /// print the notes that have a due date grouped by how soon they are due
+    pub fn print_agenda(&self, flags: BoolFlags) -> Result<()> {
+        let agenda = try!(self.agenda(&now()));
+        agenda_print(&agenda, flags)
+    }
+
     /// print information about the profile
-    pub fn stats(&mut self, name: &str) -> Result<()> {
+    pub fn stats(&mut self, name: &str, kdf: Option<Kdf>, out: &Formatter) -> Result<()> {
         let no_s = self.notes.iter().filter(|n| n.status == Status::Blank).count();
         let started_s = self.notes
                             .iter()
@@ -398,7 +1045,6 @@ impl Profile {
                            .iter()
                            .filter(|n| n.status == Status::Urgent)
                            .count();
-        let tty = istty(STDOUT_FILENO);
         let min = match self.notes
                             .iter()
                             .min_by_key(|n| match parse_last_touched(&*n.last_touched) {
@@ -417,97 +1063,61 @@ impl Profile {
             Some(n) => try!(localize_last_touched_string(&*n.last_touched)),
             None => return specific_fail_str!("last_touched is not properly formated"),
         };
-        try!(pretty_line("name: ", &format!("{}\n", name), tty));
-        try!(pretty_line("encrypted: ", &format!("{}\n", self.encrypted), tty));
-        try!(pretty_line("notes: ", &format!("{}\n", self.notes.len()), tty));
-        try!(pretty_line("statuses: ",
-                         &format!("none: {}, started: {}, urgent: {}\n",
-                                  no_s,
-                                  started_s,
-                                  urgent_s),
-                         tty));
-        try!(pretty_line("note ages: ",
-                         &format!("oldest: {}, newest: {}\n", min, max),
-                         tty));
-        Ok(())
+        let kdf = if self.encrypted {
+            Some(match kdf {
+                Some(k) => format!("{}", k),
+                None => {
+                    format!("{} (legacy format)",
+                            Kdf::Pbkdf2 { rounds: LEGACY_PBKDF2_ROUNDS })
+                }
+            })
+        } else {
+            None
+        };
+        out.info(&Info {
+            name: name.to_string(),
+            encrypted: self.encrypted,
+            kdf: kdf,
+            notes: self.notes.len(),
+            none: no_s,
+            started: started_s,
+            urgent: urgent_s,
+            oldest: min,
+            newest: max,
+        })
     }
 
     /// print a full item
-    pub fn view_note(&mut self, id: usize, json: bool, condensed: bool) -> Result<()> {
-        let id = id;
-        let note_pos = match self.notes.iter().position(|n| n.id == id) {
-            Some(i) => i,
-            None => return specific_fail!(format!("note {} doesn't exist", id)),
-        };
-        if json {
-            println!("{}", as_pretty_json(&self.notes[note_pos].clone()));
-        } else {
-            let tty = istty(STDOUT_FILENO);
-
-            if condensed {
-                try!(pretty_line("id: ", &format!("{}\n", self.notes[note_pos].id), tty));
-                try!(pretty_line("title: ", &format!("{}\n", self.notes[note_pos].title), tty));
-                if self.notes[note_pos].status != Status::Blank {
-                    try!(pretty_line("status: ",
-                                     &format!("{}\n", self.notes[note_pos].status),
-                                     tty));
-                }
-                try!(pretty_line("last touched: ",
-                                 &format!("{}\n",
-                                          try!(
-                                localize_last_touched_string(
-                                    &*self.notes[note_pos].last_touched
-                                )
-                            )),
-                                 tty));
-            } else {
-                try!(pretty_line("id\n--\n", &format!("{}\n\n", self.notes[note_pos].id), tty));
-                try!(pretty_line("title\n-----\n",
-                                 &format!("{}\n\n", self.notes[note_pos].title),
-                                 tty));
-                if self.notes[note_pos].status != Status::Blank {
-                    try!(pretty_line("status\n------\n",
-                                     &format!("{:?}\n\n", self.notes[note_pos].status),
-                                     tty));
-                }
-                try!(pretty_line("last touched\n------------\n",
-                                 &format!("{}\n\n",
-                                          try!(
-                                localize_last_touched_string(
-                                    &*self.notes[note_pos].last_touched
-                                )
-                            )),
-                                 tty));
-            };
-
-            // body
-            if !self.notes[note_pos].body.is_empty() {
-                if condensed {
-                    try!(pretty_line("body: ", &format!("{}\n", self.notes[note_pos].body), tty));
-                } else {
-                    try!(pretty_line("body\n----\n",
-                                     &format!("{}\n\n", self.notes[note_pos].body),
-                                     tty));
-                };
-            }
+    pub fn view_note(&mut self, id: usize, out: &Formatter) -> Result<()> {
+        match self.notes.iter().find(|n| n.id == id) {
+            Some(n) => out.note(n),
+            None => specific_fail!(format!("note {} doesn't exist", id)),
         }
-        Ok(())
     }
 
     /// print all notes in the profile
     pub fn list_notes(&mut self,
                       limit: usize,
                       flags: BoolFlags,
-                      status: Option<Status>)
+                      status: Option<Status>,
+                      tags: &[String],
+                      due_before: Option<Tm>,
+                      due_after: Option<Tm>,
+                      query: Option<&Query>,
+                      out: &Formatter)
                       -> Result<()> {
-        if !self.notes.is_empty() {
-            try!(sorted_print(&mut self.notes.clone(), limit, flags, status));
-        } else if flags.json {
-            println!("[]");
-        } else {
-            println!("this profile is empty");
+        if self.notes.is_empty() {
+            return out.empty("this profile is empty");
         }
-        Ok(())
+        sorted_print(&mut self.notes.clone(),
+                     limit,
+                     flags,
+                     status,
+                     tags,
+                     due_before,
+                     due_after,
+                     query,
+                     out)
     }
 
     /// print notes search for in the profile
@@ -515,40 +1125,73 @@ impl Profile {
                         pattern: &str,
                         limit: usize,
                         flags: BoolFlags,
-                        status: Option<Status>)
+                        status: Option<Status>,
+                        tags: &[String],
+                        query: Option<&Query>,
+                        out: &Formatter)
                         -> Result<()> {
-        let notes: Vec<Item> = if flags.regex {
-            let re = match Regex::new(&pattern[..]) {
-                Ok(r) => r,
-                Err(e) => return specific_fail!(format!("regex error: {}.", e)),
-            };
-            self.notes
-                .iter()
-                .filter(|n| if flags.search_body {
-                    re.is_match(&*n.body)
-                } else {
-                    re.is_match(&*n.title)
-                })
-                .cloned()
-                .collect()
-        } else {
-            self.notes
+        if pattern.is_empty() && query.is_none() {
+            return specific_fail_str!("search for a pattern, a --query or both");
+        }
+        let mut notes = try!(self.find_notes(pattern, &flags));
+        if notes.is_empty() {
+            return out.empty("nothing found");
+        }
+        sorted_print(&mut notes, limit, flags, status, tags, None, None, query, out)
+    }
+
+    /// the notes whose titles (or bodies with `--search-body`) match `pattern`,
+    /// the best fuzzy matches first
+    pub fn find_notes(&self, pattern: &str, flags: &BoolFlags) -> Result<Vec<Item>> {
+        let matcher = try!(Matcher::new(pattern, flags));
+        let mut found: Vec<(usize, &Item)> = self.notes
+                                                 .iter()
+                                                 .filter_map(|n| {
+                                                     let text = if flags.search_body {
+                                                         &n.body
+                                                     } else {
+                                                         &n.title
+                                                     };
+                                                     matcher.score(text).map(|s| (s, n))
+                                                 })
+                                                 .collect();
+        // fuzzy matches are listed best first
+        if flags.fuzzy {
+            found.sort_by(|a, b| b.0.cmp(&a.0));
+        }
+        Ok(found.into_iter().map(|(_, n)| n.clone()).collect())
+    }
+
+    /// print the notes matching a full-text search for `pattern` over their titles
+    /// and bodies, the most relevant first
+    pub fn search_full_text(&mut self,
+                            index: &Index,
+                            pattern: &str,
+                            limit: usize,
+                            flags: BoolFlags,
+                            status: Option<Status>,
+                            tags: &[String],
+                            query: Option<&Query>,
+                            out: &Formatter)
+                            -> Result<()> {
+        let mut notes = try!(self.rank_notes(index, pattern, &flags));
+        if notes.is_empty() {
+            return out.empty("nothing found");
+        }
+        sorted_print(&mut notes, limit, flags, status, tags, None, None, query, out)
+    }
+
+    /// the notes matching a full-text search for `pattern` in `index`, the most
+    /// relevant first
+    pub fn rank_notes(&self, index: &Index, pattern: &str, flags: &BoolFlags) -> Result<Vec<Item>> {
+        if flags.regex || flags.fuzzy {
+            return specific_fail_str!("--full-text can't be used with --regex or --fuzzy");
+        }
+        let clauses = try!(index::parse_query(pattern));
+        Ok(index.search(&clauses)
                 .iter()
-                .filter(|n| if flags.search_body {
-                    n.body.contains(&pattern[..])
-                } else {
-                    n.title.contains(&pattern[..])
-                })
+                .filter_map(|&(ref uuid, _)| self.notes.iter().find(|n| n.uuid == *uuid))
                 .cloned()
-                .collect()
-        };
-        if !notes.is_empty() {
-            try!(sorted_print(&mut notes.clone(), limit, flags, status));
-        } else if flags.json {
-            println!("[]");
-        } else {
-            println!("nothing found");
-        }
-        Ok(())
+                .collect())
     }
 }
@@ -0,0 +1,427 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// query.rs
+//   the query language used by `--query` to pick which notes are listed or
+//   searched, like `status:urgent touched:>7d body:postgres -body:staging`.
+
+// random things
+use regex::Regex;
+use time::{at, now, Duration, Tm};
+
+// theca imports
+use item::{Item, Status};
+use markdown::parse_status;
+use utils::{add_days, parse_date_input, parse_last_touched, start_of_day};
+use errors::{Result, Error};
+
+/// the fields a term of a query can look at
+pub static QUERY_FIELDS: [&'static str; 6] = ["title", "body", "status", "tag", "touched", "due"];
+
+/// The text of a note a term looks in
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextField {
+    Title,
+    Body,
+    /// the title or the body, what a term without a field looks in
+    Any,
+}
+
+/// What a term looks for in some text
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    /// the text contains this (lowercased) text, ignoring case
+    Contains(String),
+    /// the text matches this regex
+    Regex(Regex),
+}
+
+/// The date of a note a term compares
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DateField {
+    Touched,
+    Due,
+}
+
+/// A single condition on a note
+#[derive(Clone, Debug)]
+pub enum Term {
+    Text(TextField, Pattern),
+    Status(Status),
+    Tag(String),
+    /// the date is on or after `from` (if there is one) and before `to` (if
+    /// there is one)
+    Date {
+        field: DateField,
+        from: Option<Tm>,
+        to: Option<Tm>,
+    },
+    /// the note has no due date
+    NoDue,
+}
+
+/// A parsed query
+#[derive(Clone, Debug)]
+pub enum Query {
+    /// every query has to match
+    And(Vec<Query>),
+    /// any of the queries has to match
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    Term(Term),
+}
+
+impl Pattern {
+    fn is_match(&self, text: &str) -> bool {
+        match *self {
+            Pattern::Contains(ref s) => text.to_lowercase().contains(&s[..]),
+            Pattern::Regex(ref re) => re.is_match(text),
+        }
+    }
+}
+
+impl Term {
+    fn matches(&self, note: &Item) -> bool {
+        match *self {
+            Term::Text(TextField::Title, ref p) => p.is_match(&note.title),
+            Term::Text(TextField::Body, ref p) => p.is_match(&note.body),
+            Term::Text(TextField::Any, ref p) => p.is_match(&note.title) || p.is_match(&note.body),
+            Term::Status(ref s) => note.status == *s,
+            Term::Tag(ref t) => note.tags.iter().any(|n| n.to_lowercase() == *t),
+            Term::Date { field, ref from, ref to } => {
+                let date = match field {
+                    DateField::Touched => Some(&note.last_touched),
+                    DateField::Due => note.due.as_ref(),
+                };
+                match date.map(|d| parse_last_touched(d)) {
+                    Some(Ok(d)) => from.map_or(true, |f| d >= f) && to.map_or(true, |t| d < t),
+                    _ => false,
+                }
+            }
+            Term::NoDue => note.due.is_none(),
+        }
+    }
+}
+
+impl Query {
+    /// parse `input`, relative dates in it are relative to `now`
+    pub fn parse(input: &str, now: &Tm) -> Result<Query> {
+        let mut parser = Parser {
+            chars: input.chars().collect(),
+            pos: 0,
+            now: *now,
+        };
+        let query = try!(parser.or());
+        parser.skip_space();
+        match parser.peek() {
+            None => Ok(query),
+            Some(')') => parser.fail("a ) that wasn't opened"),
+            Some(c) => parser.fail(&format!("unexpected '{}'", c)),
+        }
+    }
+
+    /// whether `note` matches the query
+    pub fn matches(&self, note: &Item) -> bool {
+        match *self {
+            Query::And(ref qs) => qs.iter().all(|q| q.matches(note)),
+            Query::Or(ref qs) => qs.iter().any(|q| q.matches(note)),
+            Query::Not(ref q) => !q.matches(note),
+            Query::Term(ref t) => t.matches(note),
+        }
+    }
+}
+
+/// parse an optional `--query` argument relative to the current time
+pub fn parse_query_arg(input: &str) -> Result<Option<Query>> {
+    if input.trim().is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(try!(Query::parse(input, &now()))))
+    }
+}
+
+// a recursive descent parser over the characters of a query
+//
+//   or      = and { "OR" and }
+//   and     = unary { ["AND"] unary }
+//   unary   = ("-" | "NOT") unary | "(" or ")" | term
+//   term    = [field ":"] [op] (word | "quoted" | /regex/)
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    now: Tm,
+}
+
+impl Parser {
+    fn fail<T>(&self, message: &str) -> Result<T> {
+        specific_fail!(format!("{} at position {} of the query", message, self.pos + 1))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn skip_space(&mut self) {
+        while self.peek().map_or(false, |c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    // whether the next word is the keyword `word`, which has to stand on its own
+    fn at_keyword(&self, word: &str) -> bool {
+        let end = self.pos + word.chars().count();
+        self.chars.len() >= end &&
+        self.chars[self.pos..end].iter().cloned().eq(word.chars()) &&
+        self.chars.get(end).map_or(true, |c| c.is_whitespace() || *c == '(')
+    }
+
+    fn or(&mut self) -> Result<Query> {
+        let mut parts = vec![try!(self.and())];
+        loop {
+            self.skip_space();
+            if !self.at_keyword("OR") {
+                break;
+            }
+            self.pos += 2;
+            parts.push(try!(self.and()));
+        }
+        Ok(if parts.len() == 1 {
+            parts.remove(0)
+        } else {
+            Query::Or(parts)
+        })
+    }
+
+    fn and(&mut self) -> Result<Query> {
+        let mut parts = vec![];
+        loop {
+            self.skip_space();
+            match self.peek() {
+                None | Some(')') => break,
+                _ if self.at_keyword("OR") => break,
+                _ if self.at_keyword("AND") => self.pos += 3,
+                _ => parts.push(try!(self.unary())),
+            }
+        }
+        match parts.len() {
+            0 => self.fail("expected a term"),
+            1 => Ok(parts.remove(0)),
+            _ => Ok(Query::And(parts)),
+        }
+    }
+
+    fn unary(&mut self) -> Result<Query> {
+        self.skip_space();
+        if self.at_keyword("NOT") {
+            self.pos += 3;
+            return Ok(Query::Not(Box::new(try!(self.unary()))));
+        }
+        match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                match self.peek() {
+                    Some(c) if !c.is_whitespace() => Ok(Query::Not(Box::new(try!(self.unary())))),
+                    _ => self.fail("expected a term after -"),
+                }
+            }
+            Some('(') => {
+                let open = self.pos;
+                self.pos += 1;
+                let query = try!(self.or());
+                self.skip_space();
+                if self.peek() != Some(')') {
+                    self.pos = open;
+                    return self.fail("a ( that isn't closed");
+                }
+                self.pos += 1;
+                Ok(query)
+            }
+            _ => self.term(),
+        }
+    }
+
+    // text up to the next unescaped `end`, which is skipped
+    fn delimited(&mut self, end: char, what: &str) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                None => {
+                    self.pos = start;
+                    return self.fail(&format!("a {} that isn't closed", what));
+                }
+                Some('\\') if self.chars.get(self.pos + 1) == Some(&end) => {
+                    value.push(end);
+                    self.pos += 2;
+                }
+                Some(c) if c == end => {
+                    self.pos += 1;
+                    return Ok(value);
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let mut value = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == ')' {
+                break;
+            }
+            value.push(c);
+            self.pos += 1;
+        }
+        value
+    }
+
+    fn term(&mut self) -> Result<Query> {
+        let start = self.pos;
+
+        // a field is a lowercase name followed by a colon
+        let name_len = self.chars[self.pos..]
+                           .iter()
+                           .take_while(|c| c.is_alphabetic() || **c == '_')
+                           .count();
+        let field = if name_len > 0 && self.chars.get(self.pos + name_len) == Some(&':') {
+            let name: String = self.chars[self.pos..self.pos + name_len].iter().cloned().collect();
+            self.pos += name_len + 1;
+            Some(name.to_lowercase())
+        } else {
+            None
+        };
+
+        let text_field = match field.as_ref().map(|f| &f[..]) {
+            None => Some(TextField::Any),
+            Some("title") => Some(TextField::Title),
+            Some("body") => Some(TextField::Body),
+            Some(f) if QUERY_FIELDS.contains(&f) => None,
+            Some(f) => {
+                self.pos = start;
+                return self.fail(&format!("there is no field '{}' to query, use one of {}",
+                                          f,
+                                          QUERY_FIELDS.join(", ")));
+            }
+        };
+
+        let value_start = self.pos;
+        let (value, regex) = match self.peek() {
+            Some('"') => (try!(self.delimited('"', "quote")), false),
+            Some('/') if text_field.is_some() => (try!(self.delimited('/', "regex")), true),
+            _ => (self.word(), false),
+        };
+        if value.is_empty() && !regex {
+            self.pos = value_start;
+            return match field {
+                Some(f) => self.fail(&format!("expected a value for {}", f)),
+                None => self.fail("expected a value"),
+            };
+        }
+
+        let term = match (text_field, field.as_ref().map(|f| &f[..])) {
+            (Some(t), _) if regex => {
+                match Regex::new(&value) {
+                    Ok(re) => Term::Text(t, Pattern::Regex(re)),
+                    Err(e) => {
+                        self.pos = value_start;
+                        return self.fail(&format!("regex error: {}", e));
+                    }
+                }
+            }
+            (Some(t), _) => Term::Text(t, Pattern::Contains(value.to_lowercase())),
+            (None, Some("status")) => {
+                match parse_status(&value) {
+                    Ok(s) => Term::Status(s),
+                    Err(e) => {
+                        self.pos = value_start;
+                        return self.fail(&e.desc);
+                    }
+                }
+            }
+            (None, Some("tag")) => Term::Tag(value.to_lowercase()),
+            (None, Some("due")) if value.to_lowercase() == "none" => Term::NoDue,
+            (None, Some(f)) => {
+                let field = if f == "due" {
+                    DateField::Due
+                } else {
+                    DateField::Touched
+                };
+                let (from, to) = match self.date_range(&value) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        self.pos = value_start;
+                        return self.fail(&e.desc);
+                    }
+                };
+                Term::Date {
+                    field: field,
+                    from: from,
+                    to: to,
+                }
+            }
+            (None, None) => unreachable!(),
+        };
+        Ok(Query::Term(term))
+    }
+
+    // the range of dates a comparison like `>7d`, `<=friday` or `2015-01-22`
+    // covers. a bare date is the whole day, `7d`, `12h` and `2w` are that long
+    // before now.
+    fn date_range(&self, value: &str) -> Result<(Option<Tm>, Option<Tm>)> {
+        let (op, value) = match ["<=", ">=", "<", ">", "="].iter().find(|o| value.starts_with(*o)) {
+            Some(o) => (*o, &value[o.len()..]),
+            None => ("=", value),
+        };
+        let (date, whole_day) = match ago(value) {
+            Some(d) => (at(self.now.to_timespec() - d), false),
+            None => {
+                let d = try!(parse_date_input(value, &self.now));
+                (d, d == start_of_day(&d))
+            }
+        };
+        let day_end = if whole_day {
+            add_days(&date, 1)
+        } else {
+            date
+        };
+        Ok(match op {
+            "<" => (None, Some(date)),
+            "<=" => (None, Some(day_end)),
+            ">" => (Some(day_end), None),
+            ">=" => (Some(date), None),
+            _ => (Some(start_of_day(&date)), Some(add_days(&date, 1))),
+        })
+    }
+}
+
+// a time span like 12h, 7d or 2w
+fn ago(value: &str) -> Option<Duration> {
+    let value = value.trim().to_lowercase();
+    if value.len() < 2 {
+        return None;
+    }
+    let (n, unit) = value.split_at(value.len() - 1);
+    // +3d and -3d are offsets from today, see `parse_date_input`
+    if !n.chars().all(|c| c.is_digit(10)) {
+        return None;
+    }
+    let n = match n.parse::<i64>() {
+        Ok(n) => n,
+        Err(_) => return None,
+    };
+    match unit {
+        "h" => Some(Duration::hours(n)),
+        "d" => Some(Duration::days(n)),
+        "w" => Some(Duration::weeks(n)),
+        _ => None,
+    }
+}
@@ -0,0 +1,262 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// readline.rs
+//   a small line editor for `theca shell`, with emacs style editing keys and
+//   the history of the session.
+
+// std imports
+use std::io::{stdin, stdout, Write};
+use std::io::Error as IoError;
+
+// theca imports
+use tui::{raw_termios, read_keys, Key};
+use utils::c::{self, istty};
+use utils::{display_width, termsize, STDIN_FILENO, STDOUT_FILENO};
+use errors::Result;
+
+/// how many lines are kept in the history
+pub const HISTORY_LEN: usize = 500;
+
+/// The line being edited and the position of the cursor in it
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct LineBuffer {
+    pub chars: Vec<char>,
+    pub cursor: usize,
+}
+
+impl LineBuffer {
+    /// a buffer holding `line` with the cursor at the end
+    pub fn new(line: &str) -> LineBuffer {
+        let chars: Vec<char> = line.chars().collect();
+        LineBuffer {
+            cursor: chars.len(),
+            chars: chars,
+        }
+    }
+
+    pub fn line(&self) -> String {
+        self.chars.iter().cloned().collect()
+    }
+
+    /// the range of chars that fits in `width` columns with the cursor still
+    /// on screen, starting as far left as it can
+    pub fn window(&self, width: usize) -> (usize, usize) {
+        let mut start = self.cursor;
+        let mut used = 0;
+        while start > 0 {
+            let w = char_width(self.chars[start - 1]);
+            if used + w > width {
+                break;
+            }
+            used += w;
+            start -= 1;
+        }
+        let mut end = start;
+        used = 0;
+        while end < self.chars.len() {
+            let w = char_width(self.chars[end]);
+            if used + w > width {
+                break;
+            }
+            used += w;
+            end += 1;
+        }
+        (start, end)
+    }
+
+    /// apply an editing key, returns false if `key` isn't one
+    pub fn apply(&mut self, key: Key) -> bool {
+        match key {
+            Key::Char(c) => {
+                self.chars.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            Key::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.chars.remove(self.cursor);
+                }
+            }
+            Key::Delete | Key::Ctrl('d') => {
+                if self.cursor < self.chars.len() {
+                    self.chars.remove(self.cursor);
+                }
+            }
+            Key::Left | Key::Ctrl('b') => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                }
+            }
+            Key::Right | Key::Ctrl('f') => {
+                if self.cursor < self.chars.len() {
+                    self.cursor += 1;
+                }
+            }
+            Key::Home | Key::Ctrl('a') => self.cursor = 0,
+            Key::End | Key::Ctrl('e') => self.cursor = self.chars.len(),
+            Key::Ctrl('k') => self.chars.truncate(self.cursor),
+            Key::Ctrl('u') => {
+                self.chars.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            Key::Ctrl('w') => {
+                // the word before the cursor and the spaces after it
+                let mut start = self.cursor;
+                while start > 0 && self.chars[start - 1] == ' ' {
+                    start -= 1;
+                }
+                while start > 0 && self.chars[start - 1] != ' ' {
+                    start -= 1;
+                }
+                self.chars.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Reads lines with editing and history when stdin is a terminal, or plain
+/// lines when it isn't
+#[derive(Default)]
+pub struct LineEditor {
+    pub history: Vec<String>,
+}
+
+impl LineEditor {
+    pub fn new() -> LineEditor {
+        LineEditor::default()
+    }
+
+    /// add `line` to the history, unless it is blank or the same as the last one
+    pub fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().map_or(false, |l| l == line) {
+            return;
+        }
+        self.history.push(line.to_string());
+        if self.history.len() > HISTORY_LEN {
+            let extra = self.history.len() - HISTORY_LEN;
+            self.history.drain(..extra);
+        }
+    }
+
+    /// read a line after showing `prompt`, returns None at the end of the input
+    pub fn read_line(&mut self, prompt: &str) -> Result<Option<String>> {
+        if !istty(STDIN_FILENO) || !istty(STDOUT_FILENO) {
+            let mut line = String::new();
+            if try!(stdin().read_line(&mut line)) == 0 {
+                return Ok(None);
+            }
+            return Ok(Some(line.trim_right_matches(|c| c == '\n' || c == '\r').to_string()));
+        }
+
+        let mut saved = c::Termios::new();
+        try_errno!(c::tcgetattr(STDIN_FILENO, &mut saved));
+        let mut raw = raw_termios(&saved);
+        // block until a key is pressed
+        raw.c_cc[c::VMIN] = 1;
+        raw.c_cc[c::VTIME] = 0;
+        try_errno!(c::tcsetattr(STDIN_FILENO, c::TCSANOW, &raw));
+        let line = self.edit(prompt);
+        try_errno!(c::tcsetattr(STDIN_FILENO, c::TCSANOW, &saved));
+
+        let line = try!(line);
+        if let Some(ref l) = line {
+            self.add_history(l);
+        }
+        Ok(line)
+    }
+
+    fn edit(&self, prompt: &str) -> Result<Option<String>> {
+        let mut buffer = LineBuffer::default();
+        // position in the history, and the line that was being typed before
+        // moving through it
+        let mut pos = self.history.len();
+        let mut draft = LineBuffer::default();
+        try!(redraw(prompt, &buffer));
+        loop {
+            for key in try!(read_keys()) {
+                match key {
+                    Key::Enter => {
+                        try!(write_out("\r\n"));
+                        return Ok(Some(buffer.line()));
+                    }
+                    Key::Ctrl('d') if buffer.chars.is_empty() => {
+                        try!(write_out("\r\n"));
+                        return Ok(None);
+                    }
+                    Key::Ctrl('c') => {
+                        try!(write_out("^C\r\n"));
+                        return Ok(Some(String::new()));
+                    }
+                    Key::Up | Key::Ctrl('p') => {
+                        if pos > 0 {
+                            if pos == self.history.len() {
+                                draft = buffer.clone();
+                            }
+                            pos -= 1;
+                            buffer = LineBuffer::new(&self.history[pos]);
+                        }
+                    }
+                    Key::Down | Key::Ctrl('n') => {
+                        if pos < self.history.len() {
+                            pos += 1;
+                            buffer = if pos == self.history.len() {
+                                draft.clone()
+                            } else {
+                                LineBuffer::new(&self.history[pos])
+                            };
+                        }
+                    }
+                    Key::Ctrl('l') => try!(write_out("\x1b[H\x1b[2J")),
+                    key => {
+                        buffer.apply(key);
+                    }
+                }
+            }
+            try!(redraw(prompt, &buffer));
+        }
+    }
+}
+
+fn char_width(c: char) -> usize {
+    display_width(&c.to_string())
+}
+
+fn write_out(s: &str) -> Result<()> {
+    let mut out = stdout();
+    try!(out.write_all(s.as_bytes()));
+    try!(out.flush());
+    Ok(())
+}
+
+// draw the prompt and as much of the line as fits, scrolling sideways to keep
+// the cursor on screen
+fn redraw(prompt: &str, buffer: &LineBuffer) -> Result<()> {
+    let cols = match termsize() {
+        0 => 80,
+        c => c,
+    };
+    let prompt_width = display_width(prompt);
+    let width = if cols > prompt_width + 1 {
+        cols - prompt_width - 1
+    } else {
+        1
+    };
+    let (start, end) = buffer.window(width);
+    let shown: String = buffer.chars[start..end].iter().cloned().collect();
+    let mut out = format!("\r{}{}\x1b[K\r", prompt, shown);
+    let col = prompt_width +
+              buffer.chars[start..buffer.cursor].iter().cloned().map(char_width).sum::<usize>();
+    if col > 0 {
+        out.push_str(&format!("\x1b[{}C", col));
+    }
+    write_out(&out)
+}
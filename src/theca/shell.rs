@@ -0,0 +1,296 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// shell.rs
+//   `theca shell`, which keeps a profile (and its key) open and runs the
+//   commands typed at its prompt against it.
+
+// std imports
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// random things
+use docopt::{self, Docopt};
+
+// theca imports
+use {Args, parse_cmds, print_config, resolve_settings, search_all_profiles, version};
+use alias::{split_args, Aliases};
+use crypt::{cache_keys, has_header};
+use lock::ProfileLock;
+use profile::{Profile, Snapshot};
+use readline::LineEditor;
+use tui;
+use utils::{find_profile_folder, get_password, profile_fingerprint};
+use errors::{Result, Error};
+
+/// help for the commands only the shell has
+static SHELL_HELP: &'static str = "Shell commands:
+    use <profile>    Switch to another profile in the profile folder.
+    list [options]   List the notes, like running theca without a command.
+    help             Show this message and the usage of theca.
+    exit, quit       Leave the shell (so does ctrl-d).
+
+Anything else is run as a theca command on the profile in use, like
+`add \"a title\" -s`, `edit 3 -u` or `search foo`, and changes are saved
+straight away.";
+
+struct Shell<'a> {
+    // the profile in use, along with its folder and key
+    args: Args,
+    profile: Profile,
+    snapshot: Snapshot,
+    // keys for the encrypted profiles used in this shell
+    keys: HashMap<String, String>,
+    aliases: &'a Aliases,
+    usage: &'a str,
+}
+
+// load the profile while holding its lock
+fn load(args: &Args) -> Result<(Profile, Snapshot)> {
+    let _lock = try!(ProfileLock::acquire(&args.flag_profile,
+                                          &args.flag_profile_folder,
+                                          args.flag_lock_timeout));
+    Profile::new(&args.flag_profile,
+                 &args.flag_profile_folder,
+                 &args.flag_key,
+                 false,
+                 args.flag_encrypted,
+                 args.flag_yes)
+}
+
+fn profile_path(args: &Args) -> Result<PathBuf> {
+    let mut path = try!(find_profile_folder(&args.flag_profile_folder));
+    path.push(&(args.flag_profile.to_string() + ".json"));
+    Ok(path)
+}
+
+// plaintext profiles are JSON, anything else has been encrypted
+fn is_encrypted(path: &Path) -> Result<bool> {
+    let mut contents = vec![];
+    try!(try!(File::open(path)).read_to_end(&mut contents));
+    Ok(has_header(&contents) ||
+       contents.iter().find(|b| !(**b as char).is_whitespace()) != Some(&b'{'))
+}
+
+/// run the shell on the profile from `args` until `exit` or the end of the
+/// input, `usage` is the usage message the commands are parsed with
+pub fn run(args: &Args, aliases: &Aliases, usage: &str) -> Result<()> {
+    // keep derived keys for the session so reading a profile back is cheap
+    cache_keys();
+    let (profile, snapshot) = try!(load(args));
+    let mut keys = HashMap::new();
+    if args.flag_encrypted {
+        keys.insert(args.flag_profile.clone(), args.flag_key.clone());
+    }
+    let mut shell = Shell {
+        args: args.clone(),
+        profile: profile,
+        snapshot: snapshot,
+        keys: keys,
+        aliases: aliases,
+        usage: usage,
+    };
+
+    let mut editor = LineEditor::new();
+    loop {
+        let prompt = format!("{}> ", shell.args.flag_profile);
+        let line = match try!(editor.read_line(&prompt)) {
+            Some(l) => l,
+            None => break,
+        };
+        match shell.run_line(&line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("{}", e.desc),
+        }
+    }
+    Ok(())
+}
+
+impl<'a> Shell<'a> {
+    // run a line typed at the prompt, returns false when it is time to leave
+    fn run_line(&mut self, line: &str) -> Result<bool> {
+        let words = try!(split_args(line));
+        match words.first().map(|w| &w[..]) {
+            None => return Ok(true),
+            Some("exit") | Some("quit") => return Ok(false),
+            Some("help") => {
+                println!("{}\n\n{}", SHELL_HELP, self.usage.trim());
+                return Ok(true);
+            }
+            Some("use") => {
+                if words.len() != 2 {
+                    return specific_fail_str!("usage: use <profile>");
+                }
+                try!(self.use_profile(&words[1]));
+                return Ok(true);
+            }
+            _ => {}
+        }
+
+        let mut argv = vec!["theca".to_string()];
+        argv.extend(words);
+        if argv[1] == "list" {
+            argv.remove(1);
+        }
+        let mut args = try!(self.parse(&try!(self.aliases.expand(&argv))));
+        if args.cmd_shell {
+            return specific_fail_str!("already in the shell");
+        }
+        if !args.flag_profile.is_empty() || !args.flag_profile_folder.is_empty() ||
+           (!args.cmd_new_profile && (args.flag_encrypted || !args.flag_key.is_empty())) {
+            return specific_fail_str!("the profile can't be changed for a single command, use \
+                                       `use <profile>` to switch to another profile");
+        }
+        args.flag_profile = self.args.flag_profile.clone();
+        args.flag_profile_folder = self.args.flag_profile_folder.clone();
+        try!(resolve_settings(&mut args));
+
+        if args.cmd_config {
+            try!(print_config(&args));
+        } else if args.cmd_q {
+            try!(self.aliases.list_queries(args.flag_json));
+        } else if args.cmd_new_profile {
+            try!(self.new_profile(args));
+        } else if args.cmd_search && args.flag_all_profiles {
+            try!(search_all_profiles(&args));
+        } else if args.cmd_tui {
+            args.flag_key = self.args.flag_key.clone();
+            args.flag_encrypted = self.args.flag_encrypted;
+            try!(tui::run(&args));
+            let (profile, snapshot) = try!(load(&self.args));
+            self.profile = profile;
+            self.snapshot = snapshot;
+        } else {
+            args.flag_key = self.args.flag_key.clone();
+            args.flag_encrypted = self.args.flag_encrypted;
+            try!(self.run_cmd(args));
+        }
+        Ok(true)
+    }
+
+    // parse a command line like theca would, without exiting on errors
+    fn parse(&self, argv: &[String]) -> Result<Args> {
+        let parsed = Docopt::new(self.usage)
+                         .and_then(|d| {
+                             d.argv(argv.iter().cloned())
+                              .version(Some(version()))
+                              .decode()
+                         });
+        match parsed {
+            Ok(args) => Ok(args),
+            Err(docopt::Error::WithProgramUsage(ref e, _)) => {
+                specific_fail!(format!("{} (`help` shows the usage)", e))
+            }
+            Err(docopt::Error::Help) => specific_fail!(self.usage.trim().to_string()),
+            Err(e) => Err(From::from(e)),
+        }
+    }
+
+    // run a command on the profile in use, saving it if the command changes it
+    fn run_cmd(&mut self, mut args: Args) -> Result<()> {
+        let _lock = try!(ProfileLock::acquire(&args.flag_profile,
+                                              &args.flag_profile_folder,
+                                              args.flag_lock_timeout));
+        let path = try!(profile_path(&args));
+
+        // pick up changes made by anything else since the last command
+        if try!(profile_fingerprint(&path)) != self.snapshot.fingerprint {
+            let (profile, snapshot) = try!(load(&self.args));
+            self.profile = profile;
+            self.snapshot = snapshot;
+        }
+
+        let before = self.profile.clone();
+        let result = parse_cmds(&mut self.profile, &mut args, &self.snapshot);
+        // a command can fail after the profile was saved, what's on disk is
+        // what the next command has to start from then
+        let saved = try!(profile_fingerprint(&path)) != self.snapshot.fingerprint;
+        if result.is_err() && !saved {
+            // don't save half of a command that failed with the next one
+            self.profile = before;
+        }
+
+        // encrypt-profile and decrypt-profile change the key
+        self.args.flag_encrypted = self.profile.encrypted;
+        self.args.flag_key = if self.profile.encrypted {
+            args.flag_key.clone()
+        } else {
+            String::new()
+        };
+        if self.profile.encrypted {
+            self.keys.insert(args.flag_profile.clone(), args.flag_key.clone());
+        } else {
+            self.keys.remove(&args.flag_profile);
+        }
+
+        if saved && result.is_err() {
+            let (profile, snapshot) = try!(load(&self.args));
+            self.profile = profile;
+            self.snapshot = snapshot;
+        } else if saved {
+            self.snapshot = try!(Snapshot::after_save(&self.profile, &args));
+        }
+        result
+    }
+
+    // create a new profile, the profile in use stays the same
+    fn new_profile(&mut self, mut args: Args) -> Result<()> {
+        if !args.flag_key.is_empty() {
+            args.flag_encrypted = true;
+        }
+        if args.flag_encrypted && args.flag_key.is_empty() {
+            args.flag_key = try!(get_password());
+        }
+        let name = args.arg_name.first().cloned().unwrap_or_else(|| args.flag_profile.clone());
+        let _lock = try!(ProfileLock::acquire(&name,
+                                              &args.flag_profile_folder,
+                                              args.flag_lock_timeout));
+        let (mut profile, snapshot) = try!(Profile::new(&args.flag_profile,
+                                                        &args.flag_profile_folder,
+                                                        &args.flag_key,
+                                                        true,
+                                                        args.flag_encrypted,
+                                                        args.flag_yes));
+        try!(parse_cmds(&mut profile, &mut args, &snapshot));
+        if args.flag_encrypted {
+            self.keys.insert(name, args.flag_key.clone());
+        }
+        Ok(())
+    }
+
+    // switch to the profile `name` in the same profile folder, asking for its
+    // key the first time an encrypted profile is used
+    fn use_profile(&mut self, name: &str) -> Result<()> {
+        let mut args = self.args.clone();
+        args.flag_profile = name.to_string();
+        let path = try!(profile_path(&args));
+        if !path.is_file() {
+            return specific_fail!(format!("there is no profile called '{}'", name));
+        }
+        args.flag_encrypted = try!(is_encrypted(&path));
+        args.flag_key = if !args.flag_encrypted {
+            String::new()
+        } else if let Some(key) = self.keys.get(name) {
+            key.clone()
+        } else {
+            try!(get_password())
+        };
+        let (profile, snapshot) = try!(load(&args));
+        if args.flag_encrypted {
+            self.keys.insert(name.to_string(), args.flag_key.clone());
+        }
+        self.args = args;
+        self.profile = profile;
+        self.snapshot = snapshot;
+        println!("using profile '{}'", name);
+        Ok(())
+    }
+}
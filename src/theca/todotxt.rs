@@ -0,0 +1,226 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// todotxt.rs
+//   notes as todo.txt tasks, used by `export --format todotxt` and
+//   `import --format todotxt`. see https://github.com/todotxt/todo.txt for the
+//   format.
+
+// theca imports
+use item::{Item, Status};
+use utils::localize_due_string;
+use errors::Result;
+
+/// the tag of the notes for tasks that have been done (`x`)
+pub static DONE_TAG: &'static str = "done";
+
+/// A task read from a line of a todo.txt file
+#[derive(Clone, PartialEq, Debug)]
+pub struct Task {
+    pub done: bool,
+    /// dates are YYYY-MM-DD, as they are in the file
+    pub completed: Option<String>,
+    pub priority: Option<char>,
+    pub created: Option<String>,
+    /// the task without its projects, contexts, due date or priority
+    pub text: String,
+    pub projects: Vec<String>,
+    pub contexts: Vec<String>,
+    pub due: Option<String>,
+    /// the uuid of the note the task was exported from, from `uuid:`
+    pub uuid: Option<String>,
+    /// the body of the note, from `body:`
+    pub body: String,
+}
+
+impl Task {
+    /// the tags of a note for the task, projects as they are, contexts with
+    /// their `@` and `done` if the task has been done
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags = self.projects.clone();
+        tags.extend(self.contexts.iter().map(|c| format!("@{}", c)));
+        if self.done && !tags.iter().any(|t| t == DONE_TAG) {
+            tags.push(DONE_TAG.to_string());
+        }
+        tags
+    }
+
+    /// the status of a note for the task, priority A is urgent and any other
+    /// priority is started
+    pub fn status(&self) -> Status {
+        match self.priority {
+            Some('A') => Status::Urgent,
+            Some(_) => Status::Started,
+            None => Status::Blank,
+        }
+    }
+}
+
+fn priority(status: Status) -> Option<char> {
+    match status {
+        Status::Urgent => Some('A'),
+        Status::Started => Some('B'),
+        Status::Blank => None,
+    }
+}
+
+fn is_date(s: &str) -> bool {
+    s.len() == 10 &&
+    s.char_indices().all(|(i, c)| {
+        if i == 4 || i == 7 {
+            c == '-'
+        } else {
+            c.is_digit(10)
+        }
+    })
+}
+
+// the body of a note as the value of `body:`, which can't have whitespace in
+// it, so whitespace and % are percent encoded
+fn encode_body(body: &str) -> String {
+    let mut out = String::new();
+    for c in body.chars() {
+        match c {
+            '%' | ' ' | '\t' | '\n' | '\r' => out.push_str(&format!("%{:02X}", c as u8)),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn decode_body(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(i) = rest.find('%') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let code = if rest.len() >= 3 && rest.is_char_boundary(3) {
+            u8::from_str_radix(&rest[1..3], 16).ok()
+        } else {
+            None
+        };
+        match code {
+            Some(b) if b < 0x80 => {
+                out.push(b as char);
+                rest = &rest[3..];
+            }
+            _ => {
+                out.push('%');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn is_priority(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 3 && b[0] == b'(' && b[2] == b')' && b'A' <= b[1] && b[1] <= b'Z'
+}
+
+/// read a task from a line of a todo.txt file, None for blank lines
+pub fn parse_line(line: &str) -> Option<Task> {
+    let mut words = line.split_whitespace().peekable();
+    if words.peek().is_none() {
+        return None;
+    }
+    let mut task = Task {
+        done: false,
+        completed: None,
+        priority: None,
+        created: None,
+        text: String::new(),
+        projects: vec![],
+        contexts: vec![],
+        due: None,
+        uuid: None,
+        body: String::new(),
+    };
+
+    // `x COMPLETED CREATED` or `(P) CREATED` at the start
+    if words.peek() == Some(&"x") {
+        words.next();
+        task.done = true;
+        if words.peek().map_or(false, |w| is_date(w)) {
+            task.completed = words.next().map(|w| w.to_string());
+        }
+    } else if words.peek().map_or(false, |w| is_priority(w)) {
+        task.priority = words.next().and_then(|w| w.chars().nth(1));
+    }
+    if words.peek().map_or(false, |w| is_date(w)) {
+        task.created = words.next().map(|w| w.to_string());
+    }
+
+    let mut text = vec![];
+    for word in words {
+        if word.len() > 1 && word.starts_with('+') {
+            task.projects.push(word[1..].to_string());
+        } else if word.len() > 1 && word.starts_with('@') {
+            task.contexts.push(word[1..].to_string());
+        } else if word.starts_with("due:") && word.len() > 4 {
+            task.due = Some(word[4..].to_string());
+        } else if word.starts_with("uuid:") && word.len() == 41 {
+            task.uuid = Some(word[5..].to_string());
+        } else if word.starts_with("body:") && word.len() > 5 {
+            task.body = decode_body(&word[5..]);
+        } else if task.done && word.starts_with("pri:") &&
+                  is_priority(&format!("({})", &word[4..])) {
+            // completed tasks keep their priority as `pri:P`
+            task.priority = word[4..].chars().next();
+        } else {
+            text.push(word);
+        }
+    }
+    task.text = text.join(" ");
+    Some(task)
+}
+
+/// a note as a line of a todo.txt file, notes tagged `done` are tasks that
+/// have been done on the day they were last touched. the uuid and body of the
+/// note are kept in `uuid:` and `body:` so importing the line again updates
+/// the note.
+pub fn to_line(note: &Item) -> Result<String> {
+    let mut words = vec![];
+    let priority = priority(note.status);
+    let touched = try!(localize_due_string(&note.last_touched));
+    let done = note.tags.iter().any(|t| t == DONE_TAG);
+    if done {
+        words.push("x".to_string());
+        words.push(touched.clone());
+    } else if let Some(p) = priority {
+        words.push(format!("({})", p));
+    }
+    // the date goes before the text so the text can never be read as a
+    // priority or a completion mark
+    words.push(touched);
+    if !note.title.is_empty() {
+        words.push(note.title.clone());
+    }
+    for tag in note.tags.iter().filter(|t| *t != DONE_TAG) {
+        let token = tag.split_whitespace().collect::<Vec<_>>().join("-");
+        if token.len() > 1 && token.starts_with('@') {
+            words.push(token);
+        } else if !token.is_empty() {
+            words.push(format!("+{}", token));
+        }
+    }
+    if let Some(ref due) = note.due {
+        words.push(format!("due:{}", try!(localize_due_string(due))));
+    }
+    if let (true, Some(p)) = (done, priority) {
+        words.push(format!("pri:{}", p));
+    }
+    if !note.uuid.is_empty() {
+        words.push(format!("uuid:{}", note.uuid));
+    }
+    if !note.body.is_empty() {
+        words.push(format!("body:{}", encode_body(&note.body)));
+    }
+    Ok(words.join(" "))
+}
@@ -0,0 +1,757 @@
+//  _   _
+// | |_| |__   ___  ___ __ _
+// | __| '_ \ / _ \/ __/ _` |
+// | |_| | | |  __/ (_| (_| |
+//  \__|_| |_|\___|\___\__,_|
+//
+// licensed under the MIT license <http://opensource.org/licenses/MIT>
+//
+// tui.rs
+//   the full screen interface started by `theca tui`, a list of notes next to
+//   a preview of the selected note.
+
+// std imports
+use std::cmp::{min, Ordering};
+use std::io::{stdout, ErrorKind, Write};
+use std::io::Error as IoError;
+use std::time::{Duration, Instant};
+
+// random things
+use libc;
+use unicode_segmentation::UnicodeSegmentation;
+use time::{now, strftime};
+
+// theca imports
+use Args;
+use config::color_output;
+use item::{Item, Status};
+use journal::{self, Entry};
+use lock::ProfileLock;
+use profile::{Profile, Snapshot, DATEFMT, confirm_editor_tempfile};
+use utils::c::{self, istty};
+use utils::{cmp_last_touched, display_width, drop_to_editor, find_profile_folder,
+            format_field, localize_due_string, localize_last_touched_string,
+            profile_fingerprint, STDIN_FILENO, STDOUT_FILENO};
+use errors::{Result, Error};
+
+/// seconds between saving changes made in the tui
+pub const AUTOSAVE_SECS: u64 = 30;
+
+/// seconds between checking if the profile was changed by something else
+const RELOAD_SECS: u64 = 2;
+
+/// help shown in the status line
+static HELP: &'static str = "j/k move  / search  s status  e edit  d delete  w save  q quit";
+
+/// A key press read from the terminal
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Key {
+    Char(char),
+    Ctrl(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Backspace,
+    Delete,
+    Esc,
+}
+
+/// turn the bytes read from the terminal into keys, escape sequences for keys
+/// that aren't used are dropped
+pub fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        i += 1;
+        let key = match b {
+            0x1b if i < bytes.len() && (bytes[i] == b'[' || bytes[i] == b'O') => {
+                // the sequence ends with the first byte between @ and ~
+                let start = i + 1;
+                let end = match bytes[start..].iter().position(|&b| b >= 0x40 && b <= 0x7e) {
+                    Some(p) => start + p,
+                    None => break,
+                };
+                i = end + 1;
+                match &bytes[start..end + 1] {
+                    b"A" => Some(Key::Up),
+                    b"B" => Some(Key::Down),
+                    b"C" => Some(Key::Right),
+                    b"D" => Some(Key::Left),
+                    b"3~" => Some(Key::Delete),
+                    b"H" | b"1~" | b"7~" => Some(Key::Home),
+                    b"F" | b"4~" | b"8~" => Some(Key::End),
+                    b"5~" => Some(Key::PageUp),
+                    b"6~" => Some(Key::PageDown),
+                    _ => None,
+                }
+            }
+            0x1b => Some(Key::Esc),
+            b'\r' | b'\n' => Some(Key::Enter),
+            0x7f | 0x08 => Some(Key::Backspace),
+            0 => None,
+            b if b < 0x20 => Some(Key::Ctrl((b'a' + b - 1) as char)),
+            b if b < 0x80 => Some(Key::Char(b as char)),
+            b => {
+                // the rest of a utf-8 character
+                let len = if b >= 0xf0 {
+                    4
+                } else if b >= 0xe0 {
+                    3
+                } else {
+                    2
+                };
+                let end = min(i - 1 + len, bytes.len());
+                let c = String::from_utf8_lossy(&bytes[i - 1..end]).chars().next();
+                i = end;
+                c.map(Key::Char)
+            }
+        };
+        if let Some(k) = key {
+            keys.push(k);
+        }
+    }
+    keys
+}
+
+/// wrap `text` into lines of at most `width` columns, breaking at spaces
+/// where possible
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = if width == 0 { 1 } else { width };
+    let mut lines = vec![];
+    for line in text.lines() {
+        let line: String = line.replace('\t', "    ")
+                               .chars()
+                               .filter(|c| !c.is_control())
+                               .collect();
+        let graphemes: Vec<&str> = UnicodeSegmentation::graphemes(&line[..], true).collect();
+        let mut rest = &graphemes[..];
+        while display_width(&rest.concat()) > width {
+            // the most graphemes that fit in `width` columns, at least one
+            let mut fits = 0;
+            let mut used = 0;
+            for g in rest {
+                used += display_width(g);
+                if used > width {
+                    break;
+                }
+                fits += 1;
+            }
+            let fits = if fits == 0 { 1 } else { fits };
+            let split = match rest[..min(fits + 1, rest.len())].iter().rposition(|&g| g == " ") {
+                Some(p) if p > 0 => p,
+                _ => fits,
+            };
+            lines.push(rest[..split].concat());
+            rest = &rest[split..];
+            if rest.first() == Some(&" ") {
+                rest = &rest[1..];
+            }
+        }
+        lines.push(rest.concat());
+    }
+    lines
+}
+
+/// whether the title, body or tags of `note` contain `query`, ignoring case
+pub fn matches(note: &Item, query: &str) -> bool {
+    let query = query.to_lowercase();
+    note.title.to_lowercase().contains(&query) || note.body.to_lowercase().contains(&query) ||
+    note.tags.iter().any(|t| t.to_lowercase().contains(&query))
+}
+
+/// the status `s` moves a note to, Blank -> Started -> Urgent -> Blank
+pub fn next_status(status: Status) -> Status {
+    match status {
+        Status::Blank => Status::Started,
+        Status::Started => Status::Urgent,
+        Status::Urgent => Status::Blank,
+    }
+}
+
+// `s` cut or padded to `width` columns
+fn fit(s: &str, width: usize) -> String {
+    let s: String = s.chars().filter(|c| !c.is_control()).collect();
+    format_field(&s, width, false)
+}
+
+fn write_out(s: &str) -> Result<()> {
+    let mut out = stdout();
+    try!(out.write_all(s.as_bytes()));
+    try!(out.flush());
+    Ok(())
+}
+
+// rows and columns of the terminal
+fn screen_size() -> (usize, usize) {
+    let ws = unsafe { c::dimensions() };
+    if ws.ws_row == 0 || ws.ws_col == 0 {
+        (24, 80)
+    } else {
+        (ws.ws_row as usize, ws.ws_col as usize)
+    }
+}
+
+/// the settings for reading keys from the terminal as they are pressed, reads
+/// wait up to a tenth of a second for a key
+pub fn raw_termios(saved: &c::Termios) -> c::Termios {
+    let mut raw = *saved;
+    raw.c_lflag &= !(c::ECHO | c::ICANON | c::ISIG | c::IEXTEN);
+    raw.c_iflag &= !(c::IXON | c::ICRNL);
+    raw.c_cc[c::VMIN] = 0;
+    raw.c_cc[c::VTIME] = 1;
+    raw
+}
+
+/// read the keys pressed since the last read, the terminal has to be set up
+/// with `raw_termios`
+pub fn read_keys() -> Result<Vec<Key>> {
+    let mut buf = [0u8; 64];
+    let n = unsafe { libc::read(STDIN_FILENO, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+    if n < 0 {
+        let e = IoError::last_os_error();
+        if e.kind() == ErrorKind::Interrupted {
+            return Ok(vec![]);
+        }
+        return Err(From::from(e));
+    }
+    Ok(parse_keys(&buf[..n as usize]))
+}
+
+// the terminal in raw mode on the alternate screen, put back how it was when
+// dropped
+struct Terminal {
+    saved: c::Termios,
+    active: bool,
+}
+
+impl Terminal {
+    fn new() -> Result<Terminal> {
+        let mut saved = c::Termios::new();
+        try_errno!(c::tcgetattr(STDIN_FILENO, &mut saved));
+        let mut terminal = Terminal {
+            saved: saved,
+            active: false,
+        };
+        try!(terminal.resume());
+        Ok(terminal)
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        try_errno!(c::tcsetattr(STDIN_FILENO, c::TCSANOW, &raw_termios(&self.saved)));
+        try!(write_out("\x1b[?1049h\x1b[?25l"));
+        self.active = true;
+        Ok(())
+    }
+
+    // back to the normal screen, for the editor and questions
+    fn suspend(&mut self) -> Result<()> {
+        if self.active {
+            try!(write_out("\x1b[?25h\x1b[?1049l"));
+            try_errno!(c::tcsetattr(STDIN_FILENO, c::TCSANOW, &self.saved));
+            self.active = false;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.suspend();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Normal,
+    Search,
+    Delete(usize),
+}
+
+struct Tui {
+    args: Args,
+    profile: Profile,
+    snapshot: Snapshot,
+    terminal: Terminal,
+    mode: Mode,
+    query: String,
+    // uuid of the selected note
+    selected: Option<String>,
+    offset: usize,
+    message: String,
+    dirty: bool,
+    last_save: Instant,
+    last_check: Instant,
+    size: (usize, usize),
+}
+
+// load the profile while holding its lock
+fn load(args: &Args) -> Result<(Profile, Snapshot)> {
+    let _lock = try!(ProfileLock::acquire(&args.flag_profile,
+                                          &args.flag_profile_folder,
+                                          args.flag_lock_timeout));
+    Profile::new(&args.flag_profile,
+                 &args.flag_profile_folder,
+                 &args.flag_key,
+                 false,
+                 args.flag_encrypted,
+                 args.flag_yes)
+}
+
+/// run the full screen interface on the profile from `args`, changes are saved
+/// when it is closed and every AUTOSAVE_SECS seconds
+pub fn run(args: &Args) -> Result<()> {
+    if !istty(STDIN_FILENO) || !istty(STDOUT_FILENO) {
+        return specific_fail_str!("theca tui has to be run in a terminal");
+    }
+    let (profile, snapshot) = try!(load(args));
+    let mut tui = Tui {
+        args: args.clone(),
+        profile: profile,
+        snapshot: snapshot,
+        terminal: try!(Terminal::new()),
+        mode: Mode::Normal,
+        query: String::new(),
+        selected: None,
+        offset: 0,
+        message: String::new(),
+        dirty: false,
+        last_save: Instant::now(),
+        last_check: Instant::now(),
+        size: screen_size(),
+    };
+    tui.main_loop()
+}
+
+impl Tui {
+    fn main_loop(&mut self) -> Result<()> {
+        let mut redraw = true;
+        loop {
+            if redraw {
+                try!(self.draw());
+                redraw = false;
+            }
+            for key in try!(read_keys()) {
+                match self.handle(key) {
+                    Ok(true) => {}
+                    Ok(false) => return Ok(()),
+                    Err(e) => self.message = e.desc,
+                }
+                redraw = true;
+            }
+            match self.tick() {
+                Ok(changed) => redraw = redraw || changed,
+                Err(e) => {
+                    self.message = e.desc;
+                    redraw = true;
+                }
+            }
+            if screen_size() != self.size {
+                redraw = true;
+            }
+        }
+    }
+
+    // positions in profile.notes of the notes to list, in order
+    fn visible(&self) -> Vec<usize> {
+        let notes = &self.profile.notes;
+        let mut visible: Vec<usize> = (0..notes.len())
+                                          .filter(|&i| {
+                                              self.query.is_empty() ||
+                                              matches(&notes[i], &self.query)
+                                          })
+                                          .collect();
+        if self.args.flag_datesort {
+            visible.sort_by(|&a, &b| {
+                cmp_last_touched(&notes[a].last_touched, &notes[b].last_touched)
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        if self.args.flag_reverse {
+            visible.reverse();
+        }
+        visible
+    }
+
+    // position of the selected note in `visible`, the first note if the
+    // selected one isn't listed
+    fn selected_pos(&self, visible: &[usize]) -> Option<usize> {
+        if visible.is_empty() {
+            return None;
+        }
+        let notes = &self.profile.notes;
+        Some(visible.iter()
+                    .position(|&i| Some(&notes[i].uuid) == self.selected.as_ref())
+                    .unwrap_or(0))
+    }
+
+    fn select(&mut self, visible: &[usize], pos: usize) {
+        self.selected = visible.get(pos).map(|&i| self.profile.notes[i].uuid.clone());
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let visible = self.visible();
+        if let Some(pos) = self.selected_pos(&visible) {
+            let pos = pos as isize + delta;
+            let pos = if pos < 0 {
+                0
+            } else {
+                min(pos as usize, visible.len() - 1)
+            };
+            self.select(&visible, pos);
+        }
+    }
+
+    // position in profile.notes of the selected note
+    fn current(&self) -> Option<usize> {
+        let visible = self.visible();
+        self.selected_pos(&visible).map(|p| visible[p])
+    }
+
+    fn list_height(&self) -> usize {
+        self.size.0.saturating_sub(2)
+    }
+
+    // returns false when it is time to quit
+    fn handle(&mut self, key: Key) -> Result<bool> {
+        self.message.clear();
+        match self.mode {
+            Mode::Search => {
+                match key {
+                    Key::Char(c) => self.query.push(c),
+                    Key::Backspace => {
+                        self.query.pop();
+                    }
+                    Key::Enter => self.mode = Mode::Normal,
+                    Key::Esc => {
+                        self.query.clear();
+                        self.mode = Mode::Normal;
+                    }
+                    Key::Up => self.move_by(-1),
+                    Key::Down => self.move_by(1),
+                    Key::Ctrl('c') => return self.quit(),
+                    _ => {}
+                }
+            }
+            Mode::Delete(id) => {
+                self.mode = Mode::Normal;
+                if key == Key::Char('y') {
+                    try!(self.delete(id));
+                }
+            }
+            Mode::Normal => {
+                let page = self.list_height() as isize;
+                match key {
+                    Key::Char('q') | Key::Ctrl('c') => return self.quit(),
+                    Key::Char('Q') => return Ok(false),
+                    Key::Char('j') | Key::Down => self.move_by(1),
+                    Key::Char('k') | Key::Up => self.move_by(-1),
+                    Key::PageDown | Key::Ctrl('f') => self.move_by(page),
+                    Key::PageUp | Key::Ctrl('b') => self.move_by(-page),
+                    Key::Char('g') | Key::Home => self.move_by(isize::min_value() / 2),
+                    Key::Char('G') | Key::End => self.move_by(isize::max_value() / 2),
+                    Key::Char('/') => self.mode = Mode::Search,
+                    Key::Esc => self.query.clear(),
+                    Key::Char('s') => try!(self.cycle_status()),
+                    Key::Char('e') | Key::Enter => try!(self.edit_body()),
+                    Key::Char('d') => {
+                        if let Some(pos) = self.current() {
+                            self.mode = Mode::Delete(self.profile.notes[pos].id);
+                        }
+                    }
+                    Key::Char('w') => {
+                        try!(self.save(true));
+                        self.message = "saved".to_string();
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn quit(&mut self) -> Result<bool> {
+        if self.dirty {
+            if let Err(e) = self.save(true) {
+                self.message = format!("couldn't save: {} (Q quits without saving)", e.desc);
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // change the note at `pos` and record it in the note history and journal
+    fn change_note<F>(&mut self, pos: usize, command: &str, change: F) -> Result<()>
+        where F: FnOnce(&mut Item)
+    {
+        let before = self.profile.clone();
+        {
+            let note = &mut self.profile.notes[pos];
+            change(note);
+            note.record_revision(&before.notes[pos]);
+            note.last_touched = try!(strftime(DATEFMT, &now()));
+        }
+        self.record(command, &before)
+    }
+
+    fn record(&mut self, command: &str, before: &Profile) -> Result<()> {
+        if let Some(entry) = try!(Entry::between(command, before, &self.profile)) {
+            journal::record(&mut self.profile.journal, entry);
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn cycle_status(&mut self) -> Result<()> {
+        let pos = match self.current() {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let id = self.profile.notes[pos].id;
+        let status = next_status(self.profile.notes[pos].status);
+        let flag = match status {
+            Status::Blank => "--none",
+            Status::Started => "--started",
+            Status::Urgent => "--urgent",
+        };
+        try!(self.change_note(pos, &format!("edit {} {}", id, flag), |n| n.status = status));
+        self.message = match status {
+            Status::Blank => format!("cleared the status of note {}", id),
+            _ => format!("marked note {} as {}", id, status),
+        };
+        Ok(())
+    }
+
+    fn edit_body(&mut self) -> Result<()> {
+        let pos = match self.current() {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        try!(self.terminal.suspend());
+        let body = if self.profile.encrypted && !self.args.flag_yes {
+            match confirm_editor_tempfile() {
+                Ok(true) => drop_to_editor(&self.profile.notes[pos].body).map(Some),
+                Ok(false) => Ok(None),
+                Err(e) => Err(e),
+            }
+        } else {
+            drop_to_editor(&self.profile.notes[pos].body).map(Some)
+        };
+        try!(self.terminal.resume());
+        let id = self.profile.notes[pos].id;
+        match try!(body) {
+            Some(ref body) if body != &self.profile.notes[pos].body => {
+                try!(self.change_note(pos,
+                                      &format!("edit {} --editor", id),
+                                      |n| n.body = body.clone()));
+                self.message = format!("edited note {}", id);
+            }
+            _ => self.message = format!("note {} wasn't changed", id),
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, id: usize) -> Result<()> {
+        let visible = self.visible();
+        let pos = self.selected_pos(&visible).unwrap_or(0);
+        let before = self.profile.clone();
+        let deleted = try!(strftime(DATEFMT, &now()));
+        if self.profile.trash_note(id, &deleted) {
+            try!(self.record(&format!("del {}", id), &before));
+            self.message = format!("moved note {} to the trash", id);
+            // select the note that took its place
+            let visible = self.visible();
+            let pos = min(pos, visible.len().saturating_sub(1));
+            self.select(&visible, pos);
+        }
+        Ok(())
+    }
+
+    // save with the same conflict handling as the other commands, asking about
+    // conflicts outside of the tui if `interactive` is set
+    fn save(&mut self, interactive: bool) -> Result<()> {
+        let mut quiet = self.args.clone();
+        quiet.flag_yes = true;
+        match self.save_with(&quiet) {
+            Ok(()) => Ok(()),
+            Err(_) if interactive && !self.args.flag_yes => {
+                try!(self.terminal.suspend());
+                let args = self.args.clone();
+                let result = self.save_with(&args);
+                try!(self.terminal.resume());
+                result
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save_with(&mut self, args: &Args) -> Result<()> {
+        let _lock = try!(ProfileLock::acquire(&args.flag_profile,
+                                              &args.flag_profile_folder,
+                                              args.flag_lock_timeout));
+        try!(self.profile.save_to_file(args, &self.snapshot));
+        self.snapshot = try!(Snapshot::after_save(&self.profile, args));
+        self.dirty = false;
+        self.last_save = Instant::now();
+        Ok(())
+    }
+
+    // save every AUTOSAVE_SECS seconds and pick up changes made by other
+    // commands, returns whether anything changed
+    fn tick(&mut self) -> Result<bool> {
+        if self.dirty {
+            if self.last_save.elapsed() < Duration::from_secs(AUTOSAVE_SECS) {
+                return Ok(false);
+            }
+            if let Err(e) = self.save(false) {
+                // wait before trying again
+                self.last_save = Instant::now();
+                return specific_fail!(format!("couldn't save automatically, press w to save \
+                                               ({})",
+                                              e.desc));
+            }
+            self.message = "saved".to_string();
+            return Ok(true);
+        }
+        if self.last_check.elapsed() < Duration::from_secs(RELOAD_SECS) {
+            return Ok(false);
+        }
+        self.last_check = Instant::now();
+        let mut path = try!(find_profile_folder(&self.args.flag_profile_folder));
+        path.push(&(self.args.flag_profile.to_string() + ".json"));
+        if try!(profile_fingerprint(path)) == self.snapshot.fingerprint {
+            return Ok(false);
+        }
+        let (profile, snapshot) = try!(load(&self.args));
+        self.profile = profile;
+        self.snapshot = snapshot;
+        self.message = "reloaded the profile, it was changed by another command".to_string();
+        Ok(true)
+    }
+
+    // the lines of the preview pane for `note`
+    fn preview(&self, note: &Item, width: usize) -> Result<Vec<String>> {
+        let color = color_output();
+        let mut lines = vec![];
+        for line in wrap(&note.title, width) {
+            if color {
+                lines.push(format!("\x1b[1m{}\x1b[0m", fit(&line, width)));
+            } else {
+                lines.push(fit(&line, width));
+            }
+        }
+        if note.status != Status::Blank {
+            lines.push(fit(&format!("status: {}", note.status), width));
+        }
+        if !note.tags.is_empty() {
+            lines.push(fit(&format!("tags: {}", note.tags.join(", ")), width));
+        }
+        if let Some(ref due) = note.due {
+            lines.push(fit(&format!("due: {}", try!(localize_due_string(due))), width));
+        }
+        lines.push(fit(&format!("last touched: {}",
+                                try!(localize_last_touched_string(&note.last_touched))),
+                       width));
+        lines.push(fit("", width));
+        lines.extend(wrap(&note.body, width).iter().map(|l| fit(l, width)));
+        Ok(lines)
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        self.size = screen_size();
+        let (rows, cols) = self.size;
+        let height = self.list_height();
+        let visible = self.visible();
+        let pos = self.selected_pos(&visible);
+
+        // keep the list filled and the selected note on screen
+        if self.offset + height > visible.len() {
+            self.offset = visible.len().saturating_sub(height);
+        }
+        if let Some(p) = pos {
+            if p < self.offset {
+                self.offset = p;
+            } else if height > 0 && p >= self.offset + height {
+                self.offset = p + 1 - height;
+            }
+        }
+
+        let color = color_output();
+        let list_width = if cols >= 60 { cols * 2 / 5 } else { cols };
+        let preview_width = cols.saturating_sub(list_width + 3);
+        let preview = match pos {
+            Some(p) if preview_width > 0 => {
+                try!(self.preview(&self.profile.notes[visible[p]], preview_width))
+            }
+            _ => vec![],
+        };
+        let id_width = visible.iter()
+                              .map(|&i| self.profile.notes[i].id.to_string().len())
+                              .max()
+                              .unwrap_or(1);
+
+        let mut frame = String::from("\x1b[H");
+        let mut header = format!(" theca: {} ({} notes)",
+                                 self.args.flag_profile,
+                                 self.profile.notes.len());
+        if self.dirty {
+            header.push_str(" [modified]");
+        }
+        if !self.query.is_empty() && self.mode != Mode::Search {
+            header.push_str(&format!(" [/{}]", self.query));
+        }
+        frame.push_str(&format!("\x1b[7m{}\x1b[0m\r\n", fit(&header, cols)));
+
+        for row in 0..height {
+            let line = self.offset + row;
+            match visible.get(line) {
+                Some(&i) => {
+                    let note = &self.profile.notes[i];
+                    let status = match note.status {
+                        Status::Blank => ' ',
+                        Status::Started => 'S',
+                        Status::Urgent => 'U',
+                    };
+                    let text = fit(&format!(" {:>3$} {} {}", note.id, status, note.title, id_width),
+                                   list_width);
+                    if Some(line) == pos {
+                        frame.push_str(&format!("\x1b[7m{}\x1b[0m", text));
+                    } else if color && note.status == Status::Urgent {
+                        frame.push_str(&format!("\x1b[31m{}\x1b[0m", text));
+                    } else if color && note.status == Status::Started {
+                        frame.push_str(&format!("\x1b[33m{}\x1b[0m", text));
+                    } else {
+                        frame.push_str(&text);
+                    }
+                }
+                None => frame.push_str(&fit("", list_width)),
+            }
+            if preview_width > 0 {
+                frame.push_str(" | ");
+                if let Some(l) = preview.get(row) {
+                    frame.push_str(l);
+                }
+            }
+            frame.push_str("\x1b[K\r\n");
+        }
+
+        let status = match self.mode {
+            Mode::Search => format!("/{}", self.query),
+            Mode::Delete(id) => format!("move note {} to the trash? [y/n]", id),
+            Mode::Normal if !self.message.is_empty() => self.message.clone(),
+            Mode::Normal => HELP.to_string(),
+        };
+        frame.push_str(&fit(&status, cols.saturating_sub(1)));
+        frame.push_str("\x1b[K");
+        if rows < 2 {
+            frame = String::from("\x1b[H\x1b[2J");
+        }
+        write_out(&frame)
+    }
+}
@@ -10,17 +10,18 @@
 //   various utility functions for doings things we need to do.
 
 // std imports
-use std::fs::{read_dir, File};
+use std::fs::{read_dir, remove_file, rename, copy, set_permissions, File, OpenOptions,
+              Permissions};
 use std::io::{Write, Read};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::env::{var, home_dir};
 use std::cmp::Ordering;
-use std::iter::repeat;
 use std::time::UNIX_EPOCH;
+use std::iter::repeat;
 
 // time imports
-use time::get_time;
+use time::{get_time, now, Duration};
 use time::{strftime, strptime, at, Tm};
 
 // term imports
@@ -33,6 +34,17 @@ use rustc_serialize::json::{as_pretty_json, decode};
 // tempdir imports
 use tempdir::TempDir;
 
+// rand imports
+use rand::{thread_rng, Rng};
+
+// crypto imports
+use crypto::sha2::Sha256;
+use crypto::digest::Digest;
+
+// unicode imports
+use unicode_width::UnicodeWidthStr;
+use unicode_segmentation::UnicodeSegmentation;
+
 use std::io::stdin;
 use std::io::Error as IoError;
 
@@ -40,8 +52,12 @@ use std::io::Error as IoError;
 use BoolFlags;
 use errors::{Result, Error};
 use lineformat::LineFormat;
-use profile::{DATEFMT, DATEFMT_SHORT, Profile};
+use profile::{DATEFMT, DATEFMT_DAY, Profile, Agenda};
+use config::{color_output, date_format, editor};
 use item::{Item, Status};
+use crypt::{has_header, header_kdf, Kdf};
+use output::{print_header, Formatter};
+use query::Query;
 
 pub use libc::{STDIN_FILENO, STDOUT_FILENO, STDERR_FILENO};
 
@@ -94,6 +110,13 @@ pub mod c {
         unsafe { tcsetattr(fd, optional_actions, termios_p as *const _) }
     }
     pub const ECHO: c_uint = 8;
+    pub const ISIG: c_uint = 1;
+    pub const ICANON: c_uint = 2;
+    pub const IEXTEN: c_uint = 0o100000;
+    pub const ICRNL: c_uint = 0o400;
+    pub const IXON: c_uint = 0o2000;
+    pub const VTIME: usize = 5;
+    pub const VMIN: usize = 6;
     pub const TCSANOW: c_int = 0;
     #[cfg(any(target_os = "linux", target_os = "android"))]
     static TIOCGWINSZ: c_ulong = 0x5413;
@@ -153,13 +176,11 @@ pub fn drop_to_editor(contents: &str) -> Result<String> {
     let mut tmpfile = try!(File::create(&tmppath));
     // let mut tmpfile = try!(File::open_mode(&tmppath, Open, ReadWrite));
     try!(tmpfile.write_all(contents.as_bytes()));
-    let editor = match var("VISUAL") {
-        Ok(v) => v,
-        Err(_) => {
-            match var("EDITOR") {
-                Ok(v) => v,
-                Err(_) => return specific_fail_str!("neither $VISUAL nor $EDITOR is set."),
-            }
+    let editor = match var("VISUAL").or_else(|_| var("EDITOR")).ok().or_else(editor) {
+        Some(v) => v,
+        None => {
+            return specific_fail_str!("neither $VISUAL nor $EDITOR is set, and there is no \
+                                       editor in the config file.")
         }
     };
     // lets start `editor` and edit the file at `tmppath`
@@ -183,8 +204,13 @@ pub fn drop_to_editor(contents: &str) -> Result<String> {
 }
 
 pub fn get_password() -> Result<String> {
+    get_password_with_prompt("Key: ")
+}
+
+/// read a key from stdin after printing `prompt`, without echoing it
+pub fn get_password_with_prompt(prompt: &str) -> Result<String> {
     let mut stdout = try!(get_stdout());
-    try!(write!(stdout, "Key: "));
+    try!(write!(stdout, "{}", prompt));
     try!(stdout.flush());
     let tty = c::istty(STDIN_FILENO);
     if tty {
@@ -258,66 +284,115 @@ pub fn pretty_line(bold: &str, plain: &str, tty: bool) -> Result<()> {
     Ok(())
 }
 
-pub fn format_field(value: &str, width: usize, truncate: bool) -> String {
-    if value.len() > width && width > 3 && truncate {
-        format!("{: <1$.1$}...", value, width - 3)
-    } else {
-        format!("{: <1$.1$}", value, width)
+/// print a unified diff, colouring removed and added lines when printing to a tty
+pub fn print_diff(diff: &str, tty: bool) -> Result<()> {
+    let mut t = try!(get_stdout());
+    for line in diff.lines() {
+        if tty {
+            if line.starts_with("---") || line.starts_with("+++") {
+                try!(t.attr(Bold));
+            } else if line.starts_with("@@") {
+                try!(t.fg(term::color::CYAN));
+            } else if line.starts_with('-') {
+                try!(t.fg(term::color::RED));
+            } else if line.starts_with('+') {
+                try!(t.fg(term::color::GREEN));
+            }
+        }
+        try!(write!(t, "{}", line));
+        if tty {
+            try!(t.reset());
+        }
+        try!(write!(t, "\n"));
     }
+    Ok(())
 }
 
-fn print_header(line_format: &LineFormat) -> Result<()> {
-    let mut t = match stdout() {
-        Some(t) => t,
-        None => return specific_fail_str!("could not retrieve standard output."),
-    };
-    let column_seperator: String = repeat(' ')
-                                       .take(line_format.colsep)
-                                       .collect();
-    let header_seperator: String = repeat('-')
-                                       .take(line_format.line_width())
-                                       .collect();
-    let tty = c::istty(STDOUT_FILENO);
-    let status = if line_format.status_width == 0 {
-        "".to_string()
+/// the number of terminal columns `value` takes up, wide characters (like CJK)
+/// take two and combining marks none
+pub fn display_width(value: &str) -> usize {
+    UnicodeWidthStr::width(value)
+}
+
+/// `value` padded with spaces to `width` columns, or cut to fit in them without
+/// splitting a character from its combining marks (ending in ... if `truncate`)
+pub fn format_field(value: &str, width: usize, truncate: bool) -> String {
+    let value_width = display_width(value);
+    let mut field = String::new();
+    let mut used = 0;
+    if value_width <= width {
+        field.push_str(value);
+        used = value_width;
     } else {
-        format_field(&"status".to_string(), line_format.status_width, false) + &*column_seperator
-    };
-    if tty {
-        try!(t.attr(Bold));
-    }
-    try!(write!(t,
-                "{1}{0}{2}{0}{3}{4}\n{5}\n",
-                column_seperator,
-                format_field(&"id".to_string(), line_format.id_width, false),
-                format_field(&"title".to_string(), line_format.title_width, false),
-                status,
-                format_field(&"last touched".to_string(),
-                             line_format.touched_width,
-                             false),
-                header_seperator));
-    if tty {
-        try!(t.reset());
+        let (room, ellipsis) = if truncate && width > 3 {
+            (width - 3, "...")
+        } else {
+            (width, "")
+        };
+        for g in UnicodeSegmentation::graphemes(value, true) {
+            let w = display_width(g);
+            if used + w > room {
+                break;
+            }
+            field.push_str(g);
+            used += w;
+        }
+        field.push_str(ellipsis);
+        used += ellipsis.len();
     }
-    Ok(())
+    // a wide character that didn't fit leaves a column to pad
+    field.extend(repeat(' ').take(width - used));
+    field
 }
 
+/// filter, sort and limit `notes` and print them with `out`
 pub fn sorted_print(notes: &mut Vec<Item>,
                     limit: usize,
                     flags: BoolFlags,
-                    status: Option<Status>)
+                    status: Option<Status>,
+                    tags: &[String],
+                    due_before: Option<Tm>,
+                    due_after: Option<Tm>,
+                    query: Option<&Query>,
+                    out: &Formatter)
                     -> Result<()> {
-    let condensed = flags.condensed;
-    let json = flags.json;
-    let datesort = flags.datesort;
+    filter_notes(notes, limit, &flags, status, tags, due_before, due_after, query);
+    out.list(notes)
+}
+
+/// keep the notes with `status`, `tags`, due in the range and matching `query`,
+/// then sort them and keep the first `limit` (0 keeps them all). notes ranked by
+/// a fuzzy or full-text search keep their order instead of being sorted by date
+pub fn filter_notes(notes: &mut Vec<Item>,
+                    limit: usize,
+                    flags: &BoolFlags,
+                    status: Option<Status>,
+                    tags: &[String],
+                    due_before: Option<Tm>,
+                    due_after: Option<Tm>,
+                    query: Option<&Query>) {
+    let datesort = flags.datesort && !flags.fuzzy && !flags.full_text;
     let reverse = flags.reverse;
-    let search_body = flags.search_body;
 
     // TODO: instead of collecting this, leave as an iterator? using .take(limit) instead of the
     // limit checking code below it?
     if let Some(status) = status {
         notes.retain(|n| n.status == status);
     }
+    if !tags.is_empty() {
+        notes.retain(|n| n.has_tags(tags));
+    }
+    if due_before.is_some() || due_after.is_some() {
+        notes.retain(|n| match n.due.as_ref().map(|d| parse_last_touched(d)) {
+            Some(Ok(due)) => {
+                due_before.map_or(true, |b| due < b) && due_after.map_or(true, |a| due > a)
+            }
+            _ => false,
+        });
+    }
+    if let Some(query) = query {
+        notes.retain(|n| query.matches(n));
+    }
     let limit = if limit != 0 && notes.len() >= limit {
         limit
     } else {
@@ -334,18 +409,37 @@ pub fn sorted_print(notes: &mut Vec<Item>,
         notes.reverse();
     }
 
-    if json {
-        println!("{}", as_pretty_json(&notes[0..limit].to_vec()))
-    } else {
-        let line_format = try!(LineFormat::new(&notes[0..limit], condensed, search_body));
-        if !condensed && !json {
-            try!(print_header(&line_format));
+    notes.truncate(limit);
+}
+
+pub fn agenda_print(agenda: &Agenda, flags: BoolFlags) -> Result<()> {
+    if flags.json {
+        println!("{}", as_pretty_json(agenda));
+        return Ok(());
+    }
+    let groups = [("overdue", &agenda.overdue),
+                  ("today", &agenda.today),
+                  ("this week", &agenda.this_week),
+                  ("later", &agenda.later)];
+    let notes: Vec<Item> = groups.iter().flat_map(|g| g.1.iter()).cloned().collect();
+    if notes.is_empty() {
+        println!("nothing is due");
+        return Ok(());
+    }
+    let line_format = try!(LineFormat::new(&notes, flags.condensed, false));
+    if !flags.condensed {
+        try!(print_header(&line_format));
+    }
+    let tty = color_output();
+    for &(name, group) in groups.iter() {
+        if group.is_empty() {
+            continue;
         }
-        for n in notes[0..limit].iter() {
-            try!(n.print(&line_format, search_body));
+        try!(pretty_line(&format!("{}:\n", name), "", tty));
+        for n in group.iter() {
+            try!(n.print(&line_format, false, None));
         }
-    };
-
+    }
     Ok(())
 }
 
@@ -354,7 +448,119 @@ pub fn profile_fingerprint<P: AsRef<Path>>(path: P) -> Result<u64> {
     let metadata = try!(path.metadata());
     let modified = try!(metadata.modified());
     let since_epoch = try!(modified.duration_since(UNIX_EPOCH));
-    Ok(since_epoch.as_secs())
+    // nanoseconds, so changes made within the same second are still noticed
+    Ok(since_epoch.as_secs() * 1000000000 + since_epoch.subsec_nanos() as u64)
+}
+
+/// the key derivation function recorded in the header of an encrypted
+/// profile, `None` for plaintext and legacy encrypted profiles
+pub fn profile_kdf<P: AsRef<Path>>(path: P) -> Result<Option<Kdf>> {
+    let path = path.as_ref();
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let mut contents_buf = vec![];
+    try!(try!(File::open(path)).read_to_end(&mut contents_buf));
+    Ok(header_kdf(&contents_buf))
+}
+
+// format 16 bytes as a uuid with the given version
+fn format_uuid(mut bytes: [u8; 16], version: u8) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | (version << 4);
+    // RFC 4122 variant
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}",
+            hex[0..4].concat(),
+            hex[4..6].concat(),
+            hex[6..8].concat(),
+            hex[8..10].concat(),
+            hex[10..16].concat())
+}
+
+/// a new random (version 4) uuid for a note
+pub fn new_uuid() -> String {
+    let mut bytes = [0u8; 16];
+    thread_rng().fill_bytes(&mut bytes);
+    format_uuid(bytes, 4)
+}
+
+/// a name based (version 5 style) uuid for a note written before notes had
+/// uuids, derived from the parts of the note that identify it
+pub fn legacy_uuid(id: usize, title: &str, last_touched: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.input_str(&format!("{}\n{}\n{}", id, title, last_touched));
+    let mut digest = [0u8; 32];
+    hasher.result(&mut digest);
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    format_uuid(bytes, 5)
+}
+
+/// path of the backup kept of the last saved version of a profile
+pub fn backup_path(path: &Path) -> PathBuf {
+    let name = match path.file_name() {
+        Some(n) => n.to_string_lossy().into_owned(),
+        None => String::new(),
+    };
+    path.with_file_name(format!("{}.bak", name))
+}
+
+// create a new file at `path` containing `data` and make sure it hits the disk
+fn write_synced(path: &Path, data: &[u8], permissions: Option<Permissions>) -> Result<()> {
+    let mut file = try!(OpenOptions::new().write(true).create_new(true).open(path));
+    if let Some(p) = permissions {
+        try!(set_permissions(path, p));
+    }
+    try!(file.write_all(data));
+    try!(file.sync_all());
+    Ok(())
+}
+
+/// replace the contents of `path` with `data` without ever leaving a partially
+/// written file behind, the data is written and synced to a temporary file in
+/// the same folder which is then renamed over `path`. if `backup` is set the
+/// current contents of `path` are kept in `path.bak`.
+pub fn write_atomic(path: &Path, data: &[u8], backup: bool) -> Result<()> {
+    let folder = match path.parent() {
+        Some(f) if !f.as_os_str().is_empty() => f.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let name = match path.file_name() {
+        Some(n) => n.to_string_lossy().into_owned(),
+        None => return specific_fail!(format!("{} is not a file.", path.display())),
+    };
+    let existing = path.metadata().ok();
+
+    // the temporary file lives in the same folder so the rename can't cross
+    // filesystems
+    let suffix: String = thread_rng().gen_ascii_chars().take(8).collect();
+    let temp_path = folder.join(format!(".{}.{}.tmp", name, suffix));
+    let permissions = existing.as_ref().map(|m| m.permissions());
+    if let Err(e) = write_synced(&temp_path, data, permissions) {
+        let _ = remove_file(&temp_path);
+        return Err(e);
+    }
+
+    if backup && existing.is_some() {
+        if let Err(e) = copy(path, backup_path(path)) {
+            let _ = remove_file(&temp_path);
+            return Err(From::from(e));
+        }
+    }
+
+    if let Err(e) = rename(&temp_path, path) {
+        let _ = remove_file(&temp_path);
+        return Err(From::from(e));
+    }
+
+    // make sure the rename itself hits the disk, not every filesystem supports
+    // syncing a directory so errors are ignored
+    if let Ok(dir) = File::open(&folder) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
 }
 
 pub fn find_profile_folder(profile_folder: &str) -> Result<PathBuf> {
@@ -374,7 +580,108 @@ pub fn parse_last_touched(lt: &str) -> Result<Tm> {
 
 pub fn localize_last_touched_string(lt: &str) -> Result<String> {
     let t = try!(parse_last_touched(lt));
-    Ok(try!(strftime(DATEFMT_SHORT, &t)))
+    Ok(try!(strftime(&date_format(), &t)))
+}
+
+pub fn localize_due_string(due: &str) -> Result<String> {
+    let t = try!(parse_last_touched(due));
+    Ok(try!(strftime(DATEFMT_DAY, &t)))
+}
+
+/// midnight (local time) at the start of the day `t` falls on
+pub fn start_of_day(t: &Tm) -> Tm {
+    let mut day = *t;
+    day.tm_hour = 0;
+    day.tm_min = 0;
+    day.tm_sec = 0;
+    day.tm_nsec = 0;
+    // let mktime work out whether daylight saving applies at midnight
+    day.tm_isdst = -1;
+    at(day.to_timespec())
+}
+
+/// midnight (local time) `days` days after the day `t` falls on
+pub fn add_days(t: &Tm, days: i64) -> Tm {
+    // aim for midday so daylight saving changes can't push us into the wrong day
+    start_of_day(&at(start_of_day(t).to_timespec() + Duration::days(days) + Duration::hours(12)))
+}
+
+static WEEKDAYS: [&'static str; 7] = ["sunday", "monday", "tuesday", "wednesday", "thursday",
+                                      "friday", "saturday"];
+
+/// parse a user supplied date relative to `base`, either absolute (2015-01-22,
+/// 2015-01-22 19:43) or relative (today, tomorrow, +3d, +2w, friday, next friday)
+pub fn parse_date_input(input: &str, base: &Tm) -> Result<Tm> {
+    let input = input.trim().to_lowercase();
+    let today = start_of_day(base);
+
+    match &*input {
+        "today" => return Ok(today),
+        "tomorrow" => return Ok(add_days(&today, 1)),
+        "yesterday" => return Ok(add_days(&today, -1)),
+        _ => {}
+    }
+
+    // day or week offsets (+3d, -1w)
+    if input.starts_with('+') || input.starts_with('-') {
+        let offset = input[1..].trim_right_matches(|c: char| c.is_alphabetic());
+        let days = match offset.parse::<i64>() {
+            Ok(n) => {
+                match &input[1 + offset.len()..] {
+                    "" | "d" | "day" | "days" => n,
+                    "w" | "week" | "weeks" => n * 7,
+                    _ => return specific_fail!(format!("invalid date offset '{}'", input)),
+                }
+            }
+            Err(_) => return specific_fail!(format!("invalid date offset '{}'", input)),
+        };
+        return Ok(add_days(&today,
+                           if input.starts_with('-') {
+                               -days
+                           } else {
+                               days
+                           }));
+    }
+
+    // the next occurence of a weekday (friday, next friday, fri)
+    let weekday = input.trim_left_matches("next ").trim();
+    if weekday.len() >= 3 {
+        if let Some(wday) = WEEKDAYS.iter().position(|d| d.starts_with(weekday)) {
+            let ahead = match (wday as i64 - today.tm_wday as i64 + 7) % 7 {
+                0 => 7,
+                n => n,
+            };
+            return Ok(add_days(&today, ahead));
+        }
+    }
+
+    // absolute dates
+    if let Ok(t) = strptime(&input, DATEFMT) {
+        return Ok(at(t.to_timespec()));
+    }
+    for fmt in &["%Y-%m-%d %H:%M", "%Y-%m-%d"] {
+        if let Ok(t) = strptime(&input, fmt) {
+            let mut date = today;
+            date.tm_year = t.tm_year;
+            date.tm_mon = t.tm_mon;
+            date.tm_mday = t.tm_mday;
+            date.tm_hour = t.tm_hour;
+            date.tm_min = t.tm_min;
+            date.tm_isdst = -1;
+            return Ok(at(date.to_timespec()));
+        }
+    }
+
+    specific_fail!(format!("couldn't understand the date '{}'", input))
+}
+
+/// parse an optional date argument relative to the current time
+pub fn parse_date_arg(input: &str) -> Result<Option<Tm>> {
+    if input.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(try!(parse_date_input(input, &now()))))
+    }
 }
 
 pub fn cmp_last_touched(a: &str, b: &str) -> Result<Ordering> {
@@ -385,7 +692,7 @@ pub fn cmp_last_touched(a: &str, b: &str) -> Result<Ordering> {
 
 pub fn validate_profile_from_path(profile_path: &PathBuf) -> (bool, bool) {
     // return (is_a_profile, encrypted(?))
-    if profile_path.extension().unwrap() == "json" {
+    if profile_path.extension().map_or(false, |e| e == "json") {
         match File::open(profile_path) {
             Ok(mut f) => {
                 let mut contents_buf: Vec<u8> = vec![];
@@ -394,6 +701,10 @@ pub fn validate_profile_from_path(profile_path: &PathBuf) -> (bool, bool) {
                     // nopnopnopppppp
                     Err(_) => return (false, false),
                 };
+                if has_header(&contents_buf) {
+                    // definitely encrypted
+                    return (true, true);
+                }
                 match String::from_utf8(contents_buf) {
                     Ok(s) => {
                         // well it's a .json and valid utf-8 at least
@@ -424,18 +735,31 @@ pub fn path_to_profile_name(profile_path: &PathBuf) -> Result<String> {
     Ok(just_f.to_str().unwrap().to_string())
 }
 
-pub fn profiles_in_folder(folder: &Path) -> Result<()> {
+/// the names of the profiles in `folder`, sorted, and whether each one is
+/// encrypted
+pub fn profile_names(folder: &Path) -> Result<Vec<(String, bool)>> {
+    let mut names = vec![];
     if folder.is_dir() {
-        println!("# profiles in {}", folder.display());
         for file in try!(read_dir(folder)) {
             let file = try!(file);
             let is_prof = validate_profile_from_path(&file.path());
             if is_prof.0 {
-                let mut msg = try!(path_to_profile_name(&file.path()));
-                if is_prof.1 {
-                    msg = format!("{} [encrypted]", msg);
-                }
-                println!("    {}", msg);
+                names.push((try!(path_to_profile_name(&file.path())), is_prof.1));
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+pub fn profiles_in_folder(folder: &Path) -> Result<()> {
+    if folder.is_dir() {
+        println!("# profiles in {}", folder.display());
+        for (name, encrypted) in try!(profile_names(folder)) {
+            if encrypted {
+                println!("    {} [encrypted]", name);
+            } else {
+                println!("    {}", name);
             }
         }
     }
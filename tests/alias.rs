@@ -0,0 +1,85 @@
+extern crate theca;
+
+use theca::alias::{command_pos, split_args, Aliases};
+
+fn argv(args: &[&str]) -> Vec<String> {
+    let mut argv = vec!["theca".to_string()];
+    argv.extend(args.iter().map(|a| a.to_string()));
+    argv
+}
+
+#[test]
+fn test_split_args() {
+    assert_eq!(split_args("search --regex -u 'deploy'").unwrap(),
+               vec!["search", "--regex", "-u", "deploy"]);
+    assert_eq!(split_args("  add \"a \\\"quoted\\\" title\"  -b it\\'s ").unwrap(),
+               vec!["add", "a \"quoted\" title", "-b", "it's"]);
+    assert_eq!(split_args("'' x''y").unwrap(), vec!["", "xy"]);
+    assert!(split_args("").unwrap().is_empty());
+    assert!(split_args("search 'deploy").is_err());
+}
+
+#[test]
+fn test_command_pos() {
+    assert_eq!(command_pos(&argv(&["-p", "work", "-c", "search", "x"])), Some(4));
+    assert_eq!(command_pos(&argv(&["--profile-folder=/tmp", "deploys"])), Some(2));
+    assert_eq!(command_pos(&argv(&["-c", "-l", "3"])), None);
+    assert_eq!(command_pos(&argv(&["--", "add"])), None);
+}
+
+#[test]
+fn test_parse_aliases() {
+    let aliases = Aliases::parse("[aliases]\n\
+                                  deploys = \"-p work search --regex -u 'deploy'\"\n\
+                                  w = [\"-p\", \"work\"]\n\
+                                  \n\
+                                  [queries]\n\
+                                  urgent = \"-u -d\"\n\
+                                  deploying = \"search --search-body deploy\"\n")
+                      .unwrap();
+    assert_eq!(aliases.aliases["deploys"],
+               vec!["-p", "work", "search", "--regex", "-u", "deploy"]);
+    assert_eq!(aliases.aliases["w"], vec!["-p", "work"]);
+    assert_eq!(aliases.queries["urgent"], vec!["-u", "-d"]);
+    assert_eq!(aliases.queries["deploying"], vec!["search", "--search-body", "deploy"]);
+    assert_eq!(Aliases::parse("").unwrap(), Aliases::default());
+}
+
+#[test]
+fn test_parse_bad_aliases() {
+    let error = |s: &str| Aliases::parse(s).unwrap_err().desc;
+    assert_eq!(error("[aliases]\nadd = \"del\""), "alias 'add' would hide the add command");
+    assert_eq!(error("[aliases]\n12 = \"-c\""), "'12' can't be used as an alias");
+    assert_eq!(error("[aliases]\nx = 1"), "aliases.x should be a string or a list of strings");
+    assert_eq!(error("[queries]\nx = \"-p work del 1\""),
+               "saved query 'x' should list or search notes");
+    assert_eq!(error("[shortcuts]\nx = \"-c\""), "unknown section 'shortcuts'");
+}
+
+#[test]
+fn test_expand_aliases() {
+    let aliases = Aliases::parse("[aliases]\n\
+                                  w = \"-p work\"\n\
+                                  wc = \"w -c\"\n\
+                                  deploys = \"wc search deploy\"\n\
+                                  loop = \"other\"\n\
+                                  other = \"-c loop\"\n\
+                                  [queries]\n\
+                                  urgent = \"-u -d\"\n")
+                      .unwrap();
+    assert_eq!(aliases.expand(&argv(&["deploys", "-r"])).unwrap(),
+               argv(&["-p", "work", "-c", "search", "deploy", "-r"]));
+    assert_eq!(aliases.expand(&argv(&["-l", "2", "wc"])).unwrap(),
+               argv(&["-l", "2", "-p", "work", "-c"]));
+    // only the command is expanded
+    assert_eq!(aliases.expand(&argv(&["add", "w"])).unwrap(), argv(&["add", "w"]));
+    assert_eq!(aliases.expand(&argv(&["-c"])).unwrap(), argv(&["-c"]));
+    assert_eq!(aliases.expand(&argv(&["loop"])).unwrap_err().desc,
+               "alias 'loop' expands to itself");
+
+    assert_eq!(aliases.expand(&argv(&["q", "urgent", "-p", "work"])).unwrap(),
+               argv(&["-u", "-d", "-p", "work"]));
+    assert_eq!(aliases.expand(&argv(&["q", "-j"])).unwrap(), argv(&["q", "-j"]));
+    assert_eq!(aliases.expand(&argv(&["q", "nope"])).unwrap_err().desc,
+               "there is no saved query called 'nope'");
+}
@@ -0,0 +1,81 @@
+extern crate theca;
+
+use theca::config::{Color, Config, Settings};
+
+#[test]
+fn test_parse_config() {
+    let config = Config::parse("profile = \"work\"\n\
+                                profile_folder = \"/tmp/notes\"\n\
+                                datesort = true\n\
+                                editor = \"vim\"\n\
+                                color = \"never\"\n\
+                                date_format = \"%d/%m/%Y\"\n\
+                                trash_days = 7\n")
+                     .unwrap();
+    assert_eq!(config.path, None);
+    assert_eq!(config.profile, Some("work".to_string()));
+    assert_eq!(config.profile_folder, Some("/tmp/notes".to_string()));
+    assert_eq!(config.settings,
+               Settings {
+                   datesort: Some(true),
+                   editor: Some("vim".to_string()),
+                   color: Some(Color::Never),
+                   date_format: Some("%d/%m/%Y".to_string()),
+                   trash_days: Some(7),
+                   ..Settings::default()
+               });
+    assert!(config.profiles.is_empty());
+}
+
+#[test]
+fn test_parse_config_profiles() {
+    let config = Config::parse("condensed = true\n\
+                                \n\
+                                [profiles.work]\n\
+                                condensed = false\n\
+                                reverse = true\n\
+                                \n\
+                                [profiles.home]\n\
+                                backup = true\n")
+                     .unwrap();
+    assert_eq!(config.settings.condensed, Some(true));
+    assert_eq!(config.profiles.len(), 2);
+    assert_eq!(config.profiles["work"],
+               Settings {
+                   condensed: Some(false),
+                   reverse: Some(true),
+                   ..Settings::default()
+               });
+    assert_eq!(config.profiles["home"].backup, Some(true));
+}
+
+#[test]
+fn test_parse_config_templates() {
+    let config = Config::parse("[templates]\n\
+                                slack = \"*{title}* ({status})\"\n")
+                     .unwrap();
+    assert_eq!(config.template("slack"), "*{title}* ({status})");
+    assert_eq!(config.template("{id} {title}"), "{id} {title}");
+    assert_eq!(Config::parse("[templates]\nslack = 1").unwrap_err().desc,
+               "templates.slack should be a string");
+}
+
+#[test]
+fn test_parse_empty_config() {
+    assert_eq!(Config::parse("").unwrap(), Config::default());
+}
+
+#[test]
+fn test_parse_bad_config() {
+    let error = |s: &str| Config::parse(s).unwrap_err().desc;
+    assert_eq!(error("foo = 1"), "unknown setting 'foo'");
+    assert_eq!(error("datesort = \"yes\""), "datesort should be true or false");
+    assert_eq!(error("trash_days = -1"), "trash_days should be a number of days");
+    assert_eq!(error("color = \"blue\""),
+               "invalid color setting 'blue', it should be auto, always or never");
+    assert_eq!(error("[profiles.work]\nprofile = \"home\""),
+               "profiles.work.profile can't be set for a single profile");
+    assert_eq!(error("[profiles.work]\ncondensed = 1"),
+               "profiles.work.condensed should be true or false");
+    assert_eq!(error("datesort = "), "line 1, column 12: expected a value");
+}
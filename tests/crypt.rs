@@ -0,0 +1,128 @@
+extern crate theca;
+
+use theca::crypt::{cache_keys, encrypt_profile, decrypt_profile, has_header, header_kdf, Header,
+                   Kdf, SCRYPT_MIN_COST, SCRYPT_MAX_COST};
+use std::path::Path;
+
+fn from_hex(s: &str) -> Vec<u8> {
+    (0..s.len() / 2).map(|i| u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap()).collect()
+}
+
+fn path() -> &'static Path {
+    Path::new("default.json")
+}
+
+// cheapest allowed scrypt parameters so the tests stay quick
+fn test_kdf() -> Kdf {
+    Kdf::scrypt_with_cost(SCRYPT_MIN_COST).unwrap()
+}
+
+#[test]
+fn test_encrypt_round_trip() {
+    let plaintext = b"{\"encrypted\": true, \"notes\": []}";
+    let ciphertext = encrypt_profile(plaintext, "DEBUG", test_kdf(), path()).unwrap();
+    assert!(has_header(&ciphertext));
+    assert_eq!(decrypt_profile(&ciphertext, "DEBUG", path()).unwrap(), plaintext.to_vec());
+}
+
+#[test]
+fn test_encrypt_random_salt_and_nonce() {
+    let plaintext = b"{\"encrypted\": true, \"notes\": []}";
+    let a = encrypt_profile(plaintext, "DEBUG", test_kdf(), path()).unwrap();
+    let b = encrypt_profile(plaintext, "DEBUG", test_kdf(), path()).unwrap();
+    assert!(a != b);
+    let (a_header, _) = Header::from_bytes(&a).unwrap();
+    let (b_header, _) = Header::from_bytes(&b).unwrap();
+    assert_eq!(a_header.kdf, test_kdf());
+    assert!(a_header.salt != b_header.salt);
+    assert!(a_header.nonce != b_header.nonce);
+}
+
+#[test]
+fn test_decrypt_wrong_key() {
+    let ciphertext = encrypt_profile(b"{\"encrypted\": true, \"notes\": []}",
+                                     "DEBUG",
+                                     test_kdf(),
+                                     path())
+                         .unwrap();
+    let err = decrypt_profile(&ciphertext, "NOT DEBUG", path()).unwrap_err();
+    assert!(err.desc.starts_with("invalid encryption key"));
+}
+
+#[test]
+fn test_decrypt_tampered() {
+    let mut ciphertext = encrypt_profile(b"{\"encrypted\": true, \"notes\": []}",
+                                     "DEBUG",
+                                     test_kdf(),
+                                     path())
+                             .unwrap();
+    let last = ciphertext.len() - 20;
+    ciphertext[last] ^= 1;
+    assert!(decrypt_profile(&ciphertext, "DEBUG", path()).is_err());
+    assert!(decrypt_profile(&ciphertext[..20], "DEBUG", path()).is_err());
+}
+
+#[test]
+fn test_decrypt_legacy_profile() {
+    // AES-256-CBC profile written before the header existed
+    let ciphertext = from_hex("000102030405060708090a0b0c0d0e0f115e159548b97bfd271f0bd0e2d966b7\
+                               7063dea04df615c8614b98b384d8747f");
+    assert!(!has_header(&ciphertext));
+    assert_eq!(decrypt_profile(&ciphertext, "DEBUG", path()).unwrap(),
+               b"{\"encrypted\":true,\"notes\":[]}".to_vec());
+}
+
+#[test]
+fn test_kdf_params_in_header() {
+    let plaintext = b"{\"encrypted\": true, \"notes\": []}";
+    let kdf = Kdf::scrypt_with_cost(11).unwrap();
+    let ciphertext = encrypt_profile(plaintext, "DEBUG", kdf, path()).unwrap();
+    assert_eq!(header_kdf(&ciphertext),
+               Some(Kdf::Scrypt {
+                   log_n: 11,
+                   r: 8,
+                   p: 1,
+               }));
+    assert_eq!(decrypt_profile(&ciphertext, "DEBUG", path()).unwrap(), plaintext.to_vec());
+
+    let pbkdf2 = Kdf::Pbkdf2 { rounds: 1000 };
+    let ciphertext = encrypt_profile(plaintext, "DEBUG", pbkdf2, path()).unwrap();
+    assert_eq!(header_kdf(&ciphertext), Some(pbkdf2));
+    assert_eq!(decrypt_profile(&ciphertext, "DEBUG", path()).unwrap(), plaintext.to_vec());
+}
+
+#[test]
+fn test_kdf_cost_bounds() {
+    assert!(Kdf::scrypt_with_cost(SCRYPT_MIN_COST - 1).is_err());
+    assert!(Kdf::scrypt_with_cost(SCRYPT_MAX_COST + 1).is_err());
+    assert!(Kdf::scrypt_with_cost(SCRYPT_MAX_COST).is_ok());
+}
+
+#[test]
+fn test_decrypt_rejects_bad_kdf_params() {
+    let mut ciphertext = encrypt_profile(b"{\"encrypted\": true, \"notes\": []}",
+                                         "DEBUG",
+                                         test_kdf(),
+                                         path())
+                             .unwrap();
+    // log_n is the byte after the kdf identifier
+    ciphertext[7] = 60;
+    let err = decrypt_profile(&ciphertext, "DEBUG", path()).unwrap_err();
+    assert!(err.desc.starts_with("invalid key derivation parameters"));
+}
+
+#[test]
+fn test_cached_keys_fresh_salt() {
+    let plaintext = b"{\"encrypted\": true, \"notes\": []}";
+    cache_keys();
+    let a = encrypt_profile(plaintext, "DEBUG", test_kdf(), path()).unwrap();
+    let b = encrypt_profile(plaintext, "DEBUG", test_kdf(), path()).unwrap();
+    let (a_header, _) = Header::from_bytes(&a).unwrap();
+    let (b_header, _) = Header::from_bytes(&b).unwrap();
+    // every save gets a fresh salt even while keys are cached
+    assert!(a_header.salt != b_header.salt);
+    assert!(a_header.nonce != b_header.nonce);
+    assert_eq!(decrypt_profile(&b, "DEBUG", path()).unwrap(), plaintext.to_vec());
+    assert_eq!(decrypt_profile(&a, "DEBUG", path()).unwrap(), plaintext.to_vec());
+    assert!(decrypt_profile(&b, "OTHER", path()).is_err());
+}
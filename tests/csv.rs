@@ -0,0 +1,151 @@
+extern crate theca;
+extern crate tempdir;
+
+use std::fs::File;
+use std::io::Write;
+
+use tempdir::TempDir;
+
+use theca::Profile;
+use theca::csv::{column_fields, parse_rows, row_note, separator, to_profiles_table, to_table};
+use theca::export::import_table;
+use theca::item::{Item, Status};
+
+fn note(id: usize, title: &str, body: &str) -> Item {
+    Item {
+        id: id,
+        uuid: format!("1715d61b-f3c4-41d7-8fb2-{:012}", id),
+        title: title.to_string(),
+        status: Status::Urgent,
+        body: body.to_string(),
+        last_touched: "2015-01-22 19:43:24 -0800".to_string(),
+        tags: vec!["work".to_string(), "ops team".to_string()],
+        due: None,
+        history: vec![],
+    }
+}
+
+fn strings(fields: &[&str]) -> Vec<String> {
+    fields.iter().map(|f| f.to_string()).collect()
+}
+
+#[test]
+fn test_separator() {
+    assert_eq!(separator("CSV").unwrap(), ',');
+    assert_eq!(separator("tsv").unwrap(), '\t');
+    assert!(separator("json").is_err());
+}
+
+#[test]
+fn test_to_table() {
+    let notes = vec![note(1, "a, \"b\"", "one\ntwo"), note(2, "plain", "")];
+    assert_eq!(to_table(&notes, ',').unwrap(),
+               "id,uuid,title,status,tags,due,last_touched,body\n\
+                1,1715d61b-f3c4-41d7-8fb2-000000000001,\"a, \"\"b\"\"\",Urgent,\"work,ops team\",,\
+                2015-01-22 19:43:24 -0800,\"one\ntwo\"\n\
+                2,1715d61b-f3c4-41d7-8fb2-000000000002,plain,Urgent,\"work,ops team\",,\
+                2015-01-22 19:43:24 -0800,\n");
+    let table = to_table(&[note(1, "tab\there", "one\ntwo\\")], '\t').unwrap();
+    assert_eq!(table.lines().nth(1).unwrap(),
+               "1\t1715d61b-f3c4-41d7-8fb2-000000000001\ttab\\there\tUrgent\twork,ops team\t\t\
+                2015-01-22 19:43:24 -0800\tone\\ntwo\\\\");
+}
+
+#[test]
+fn test_to_profiles_table() {
+    let hits = vec![("work".to_string(), vec![note(1, "plain", "")]),
+                    ("my, notes".to_string(), vec![note(2, "other", "")])];
+    assert_eq!(to_profiles_table(&hits, ',').unwrap(),
+               "profile,id,uuid,title,status,tags,due,last_touched,body\n\
+                work,1,1715d61b-f3c4-41d7-8fb2-000000000001,plain,Urgent,\"work,ops team\",,\
+                2015-01-22 19:43:24 -0800,\n\
+                \"my, notes\",2,1715d61b-f3c4-41d7-8fb2-000000000002,other,Urgent,\
+                \"work,ops team\",,2015-01-22 19:43:24 -0800,\n");
+}
+
+#[test]
+fn test_parse_rows() {
+    let notes = vec![note(1, "a, \"b\"", "one\r\ntwo"), note(2, " padded ", "")];
+    for sep in &[',', '\t'] {
+        let rows = parse_rows(&to_table(&notes, *sep).unwrap(), *sep).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1][2], "a, \"b\"");
+        assert_eq!(rows[1][7], "one\r\ntwo");
+        assert_eq!(rows[2][2], " padded ");
+        assert_eq!(rows[2][7], "");
+    }
+
+    // what a spreadsheet might save
+    let rows = parse_rows("\u{feff}Task,Notes\r\n\r\nx,\"multi\r\nline\"\r\ny,", ',').unwrap();
+    assert_eq!(rows,
+               vec![strings(&["Task", "Notes"]),
+                    strings(&["x", "multi\r\nline"]),
+                    strings(&["y", ""])]);
+    assert!(parse_rows("a,\"b\n", ',').is_err());
+}
+
+#[test]
+fn test_column_fields() {
+    let header = strings(&["Title", "Last touched", "Owner", "Notes"]);
+    assert_eq!(column_fields(&header, "").unwrap(),
+               vec![Some("title"), Some("last_touched"), None, None]);
+    assert_eq!(column_fields(&header, "notes=body, Title=").unwrap(),
+               vec![None, Some("last_touched"), None, Some("body")]);
+    assert!(column_fields(&header, "Notes").is_err());
+    assert!(column_fields(&header, "Notes=size").is_err());
+    assert!(column_fields(&header, "Missing=body").is_err());
+    assert!(column_fields(&header, "Notes=title").is_err());
+}
+
+#[test]
+fn test_row_note() {
+    let fields = vec![Some("title"), Some("status"), Some("tags"), Some("done"), None];
+    let n = row_note(&strings(&[" x ", "started", "a, b,", "yes", "ignored"]), &fields).unwrap();
+    assert_eq!(n.title, Some("x".to_string()));
+    assert_eq!(n.status, Some(Status::Started));
+    assert_eq!(n.tags, vec!["a", "b", "done"]);
+    assert_eq!(n.uuid, None);
+    assert!(row_note(&strings(&["x", "soon"]), &fields).is_err());
+}
+
+#[test]
+fn test_import_table() {
+    let dir = TempDir::new("theca").unwrap();
+    let path = dir.path().join("notes.csv");
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![note(1, "one", "body"), note(2, "two", "")],
+        trash: vec![],
+        journal: vec![],
+    };
+    let table = to_table(&p.notes, ',').unwrap();
+    File::create(&path).unwrap().write_all(table.as_bytes()).unwrap();
+
+    // importing what was exported changes nothing
+    let before = p.clone();
+    import_table(&mut p, &path, ',', "", false).unwrap();
+    assert_eq!(format!("{:?}", p.notes), format!("{:?}", before.notes));
+
+    let table = "Task,Notes,uuid\n\
+                 one,new body,1715d61b-f3c4-41d7-8fb2-000000000001\n\
+                 three,,\n";
+    File::create(&path).unwrap().write_all(table.as_bytes()).unwrap();
+    let columns = "Task=title,Notes=body";
+
+    // a dry run leaves the profile alone
+    import_table(&mut p, &path, ',', columns, true).unwrap();
+    assert_eq!(format!("{:?}", p.notes), format!("{:?}", before.notes));
+
+    import_table(&mut p, &path, ',', columns, false).unwrap();
+    assert_eq!(p.notes.len(), 3);
+    assert_eq!(p.notes[0].body, "new body");
+    assert_eq!(p.notes[2].title, "three");
+    assert!(import_table(&mut p, &path, ',', "uuid=", false).is_err());
+
+    // a table without a body column leaves the bodies alone
+    let table = "title,uuid\nthe one,1715d61b-f3c4-41d7-8fb2-000000000001\n";
+    File::create(&path).unwrap().write_all(table.as_bytes()).unwrap();
+    import_table(&mut p, &path, ',', "", false).unwrap();
+    assert_eq!(p.notes[0].title, "the one");
+    assert_eq!(p.notes[0].body, "new body");
+}
@@ -0,0 +1,35 @@
+extern crate theca;
+
+use theca::diff::unified_diff;
+
+#[test]
+fn test_diff_same() {
+    assert_eq!(unified_diff("a\nb", "a\nb", "old", "new"), "");
+}
+
+#[test]
+fn test_diff_change() {
+    let diff = unified_diff("a\nb\nc", "a\nB\nc\nd", "old", "new");
+    assert_eq!(diff,
+               "--- old\n+++ new\n@@ -1,3 +1,4 @@\n a\n-b\n+B\n c\n+d\n");
+}
+
+#[test]
+fn test_diff_empty() {
+    assert_eq!(unified_diff("", "a", "old", "new"),
+               "--- old\n+++ new\n@@ -0,0 +1 @@\n+a\n");
+    assert_eq!(unified_diff("a", "", "old", "new"),
+               "--- old\n+++ new\n@@ -1 +0,0 @@\n-a\n");
+}
+
+#[test]
+fn test_diff_hunks() {
+    let old: Vec<String> = (1..21).map(|i| i.to_string()).collect();
+    let mut new = old.clone();
+    new[1] = "two".to_string();
+    new[17] = "eighteen".to_string();
+    let diff = unified_diff(&old.join("\n"), &new.join("\n"), "old", "new");
+    assert_eq!(diff,
+               "--- old\n+++ new\n@@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n@@ -15,6 +15,6 @@\n \
+                15\n 16\n 17\n-18\n+eighteen\n 19\n 20\n");
+}
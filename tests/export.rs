@@ -0,0 +1,184 @@
+extern crate theca;
+extern crate tempdir;
+
+use std::fs::File;
+use std::io::{Read, Write};
+
+use tempdir::TempDir;
+
+use theca::Profile;
+use theca::item::{Item, Status};
+use theca::export::{export_markdown, import_dir, import_org, write_sources, Format};
+use theca::markdown::{add_uuid, file_name, from_markdown, to_markdown};
+use theca::org::notes_to_org;
+
+fn note(id: usize, title: &str, body: &str) -> Item {
+    Item {
+        id: id,
+        uuid: format!("1715d61b-f3c4-41d7-8fb2-{:012}", id),
+        title: title.to_string(),
+        status: Status::Urgent,
+        body: body.to_string(),
+        last_touched: "2015-01-22 19:43:24 -0800".to_string(),
+        tags: vec!["work".to_string(), "ops team".to_string()],
+        due: Some("2015-06-12 00:00:00 -0700".to_string()),
+        history: vec![],
+    }
+}
+
+#[test]
+fn test_format_from_arg() {
+    assert_eq!(Format::from_arg("").unwrap(), Format::Markdown);
+    assert_eq!(Format::from_arg("Markdown").unwrap(), Format::Markdown);
+    assert_eq!(Format::from_arg("todo.txt").unwrap(), Format::TodoTxt);
+    assert_eq!(Format::from_arg("org").unwrap(), Format::Org);
+    assert!(Format::from_arg("docx").is_err());
+}
+
+#[test]
+fn test_file_name() {
+    assert_eq!(file_name(&note(1, "Deploy the API: v2!", "")), "1-deploy-the-api-v2.md");
+    assert_eq!(file_name(&note(2, "ünïcode", "")), "2-ünïcode.md");
+    assert_eq!(file_name(&note(3, " -- ", "")), "3.md");
+}
+
+#[test]
+fn test_markdown_round_trip() {
+    let n = note(1, "a \"quoted\" title: yes", "first line\n\n---\nlast line");
+    let text = to_markdown(&n);
+    assert!(text.starts_with("---\nuuid: 1715d61b-f3c4-41d7-8fb2-000000000001\nid: 1\n"));
+    let md = from_markdown(&text).unwrap();
+    assert_eq!(md.uuid, Some(n.uuid.clone()));
+    assert_eq!(md.id, Some(1));
+    assert_eq!(md.title, Some(n.title.clone()));
+    assert_eq!(md.status, Some(Status::Urgent));
+    assert_eq!(md.last_touched, Some(n.last_touched.clone()));
+    assert_eq!(md.tags, n.tags);
+    assert_eq!(md.due, n.due);
+    assert_eq!(md.body, Some(n.body.clone()));
+}
+
+#[test]
+fn test_from_markdown_hand_written() {
+    let md = from_markdown("---\n\
+                            # written by hand\n\
+                            title: 'it''s a title'  # comment\n\
+                            status: Started\n\
+                            tags:\n  - one\n  - \"two\"\n\
+                            due: 2015-06-12\n\
+                            author: someone\n\
+                            ...\n\
+                            body\n")
+                 .unwrap();
+    assert_eq!(md.uuid, None);
+    assert_eq!(md.title, Some("it's a title".to_string()));
+    assert_eq!(md.status, Some(Status::Started));
+    assert_eq!(md.tags, vec!["one", "two"]);
+    assert_eq!(md.due, Some("2015-06-12".to_string()));
+    assert_eq!(md.body, Some("body\n".to_string()));
+
+    assert_eq!(from_markdown("tags: [a, b]\n").unwrap().tags, Vec::<String>::new());
+    assert_eq!(from_markdown("---\ntags: [a, 'b, c']\n---\n").unwrap().tags,
+               vec!["a", "b, c"]);
+    assert!(from_markdown("---\ntitle: x\n").is_err());
+    assert!(from_markdown("---\nstatus: done\n---\n").is_err());
+    assert!(from_markdown("---\ntitle: \"x\" y\n---\n").is_err());
+}
+
+#[test]
+fn test_add_uuid() {
+    let uuid = "1715d61b-f3c4-41d7-8fb2-1d52ea70db94";
+    assert_eq!(add_uuid("body\n", uuid), format!("---\nuuid: {}\n---\nbody\n", uuid));
+    assert_eq!(add_uuid("---\ntitle: x\nuuid: bad  # old\n---\nbody", uuid),
+               format!("---\ntitle: x\nuuid: {}\n---\nbody", uuid));
+    // a uuid in the body isn't the front matter's
+    assert_eq!(add_uuid("---\ntitle: x\n---\nuuid: in the body", uuid),
+               format!("---\nuuid: {}\ntitle: x\n---\nuuid: in the body", uuid));
+}
+
+#[test]
+fn test_import_dir() {
+    let dir = TempDir::new("theca").unwrap();
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![note(1, "one", "body"), note(2, "two", "")],
+        trash: vec![],
+        journal: vec![],
+    };
+    export_markdown(&p.notes, dir.path()).unwrap();
+
+    // importing what was exported changes nothing
+    let before = p.clone();
+    import_dir(&mut p, dir.path()).unwrap();
+    assert_eq!(format!("{:?}", p.notes), format!("{:?}", before.notes));
+
+    // changed files update their notes, new ones are added
+    let mut f = File::create(dir.path().join("1-one.md")).unwrap();
+    f.write_all(to_markdown(&note(1, "one", "new body")).as_bytes()).unwrap();
+    let mut f = File::create(dir.path().join("extra.md")).unwrap();
+    f.write_all(b"no front matter").unwrap();
+    let mut f = File::create(dir.path().join("kept.md")).unwrap();
+    f.write_all(b"---\r\nauthor: someone\r\n---\r\nline endings kept").unwrap();
+    let sources = import_dir(&mut p, dir.path()).unwrap();
+    assert_eq!(p.notes.len(), 4);
+    assert_eq!(p.notes[0].body, "new body");
+    assert_eq!(p.notes[0].revision(), 2);
+    assert_eq!(p.notes[2].title, "extra");
+    assert_eq!(p.notes[3].title, "kept");
+
+    // the new files are only given the uuid of their note once they are
+    // written, and nothing else in them changes
+    let read = |name: &str| {
+        let mut contents = String::new();
+        File::open(dir.path().join(name)).unwrap().read_to_string(&mut contents).unwrap();
+        contents
+    };
+    assert_eq!(read("extra.md"), "no front matter");
+    assert_eq!(sources.len(), 2);
+    assert_eq!(sources[0].0, dir.path().join("extra.md"));
+    write_sources(&sources).unwrap();
+    assert_eq!(read("extra.md"),
+               format!("---\nuuid: {}\n---\nno front matter", p.notes[2].uuid));
+    assert_eq!(read("kept.md"),
+               format!("---\r\nuuid: {}\nauthor: someone\r\n---\r\nline endings kept",
+                       p.notes[3].uuid));
+    let before = p.clone();
+    import_dir(&mut p, dir.path()).unwrap();
+    assert_eq!(format!("{:?}", p.notes), format!("{:?}", before.notes));
+}
+
+#[test]
+fn test_import_org() {
+    let dir = TempDir::new("theca").unwrap();
+    let path = dir.path().join("notes.org");
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![note(1, "one", "body"), note(2, "two", "")],
+        trash: vec![],
+        journal: vec![],
+    };
+    let org = notes_to_org(&p.notes).unwrap();
+    File::create(&path).unwrap().write_all(org.as_bytes()).unwrap();
+
+    // tags org can't hold keep their spelling, so nothing changes
+    let before = p.clone();
+    import_org(&mut p, &path).unwrap();
+    assert_eq!(format!("{:?}", p.notes), format!("{:?}", before.notes));
+
+    // DONE tags a note done, new headlines are added and given an id
+    let org = org.replace("* [#A] two", "* DONE [#A] two") + "* three\n";
+    File::create(&path).unwrap().write_all(org.as_bytes()).unwrap();
+    let sources = import_org(&mut p, &path).unwrap();
+    assert!(p.trash.is_empty());
+    assert_eq!(p.notes.len(), 3);
+    assert_eq!(p.notes[1].tags, vec!["work", "ops team", "done"]);
+    assert_eq!(p.notes[2].title, "three");
+    // the file is only given the new ID property once it is written
+    let mut contents = String::new();
+    File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, org);
+    write_sources(&sources).unwrap();
+    let before = p.clone();
+    import_org(&mut p, &path).unwrap();
+    assert_eq!(format!("{:?}", p.notes), format!("{:?}", before.notes));
+}
@@ -0,0 +1,127 @@
+extern crate theca;
+extern crate tempdir;
+
+use std::fs::File;
+
+use tempdir::TempDir;
+
+use theca::Profile;
+use theca::index::{index_path, parse_query, profile_index, tokenize, update_saved, Clause, Index};
+use theca::item::{Item, Status};
+
+fn note(id: usize, title: &str, body: &str) -> Item {
+    Item {
+        id: id,
+        uuid: format!("1715d61b-f3c4-41d7-8fb2-{:012}", id),
+        title: title.to_string(),
+        status: Status::Blank,
+        body: body.to_string(),
+        last_touched: "2015-01-22 19:43:24 -0800".to_string(),
+        tags: vec![],
+        due: None,
+        history: vec![],
+    }
+}
+
+fn ids(index: &Index, query: &str) -> Vec<String> {
+    index.search(&parse_query(query).unwrap())
+         .into_iter()
+         .map(|(uuid, _)| uuid[uuid.len() - 1..].to_string())
+         .collect()
+}
+
+fn notes() -> Vec<Item> {
+    vec![note(1, "Postgres migration plan", "move the staging database to postgres"),
+         note(2, "Deploy notes", "the staging deploy uses Postgres too, and a long body to \
+                                  make this note longer than the others are"),
+         note(3, "Groceries", "milk, eggs"),
+         note(4, "migration checklist", "postgres postgres postgres")]
+}
+
+#[test]
+fn test_tokenize() {
+    assert_eq!(tokenize("Don't  deploy v2.1 on Friday!"),
+               vec!["don", "t", "deploy", "v2", "1", "on", "friday"]);
+    assert_eq!(tokenize("ÜBER straße"), vec!["über", "straße"]);
+    assert!(tokenize(" -- ").is_empty());
+}
+
+#[test]
+fn test_parse_query() {
+    assert_eq!(parse_query(" deploy  \"Staging database\" migr* v2.1").unwrap(),
+               vec![Clause::Term("deploy".to_string()),
+                    Clause::Phrase(vec!["staging".to_string(), "database".to_string()]),
+                    Clause::Prefix("migr".to_string()),
+                    Clause::Phrase(vec!["v2".to_string(), "1".to_string()])]);
+    assert!(parse_query("\"not closed").is_err());
+    assert!(parse_query(" -- ").is_err());
+}
+
+#[test]
+fn test_search() {
+    let mut index = Index::new();
+    assert!(index.update(&notes()));
+    assert!(!index.update(&notes()));
+
+    // more occurrences in a shorter note rank higher
+    assert_eq!(ids(&index, "postgres"), vec!["4", "1", "2"]);
+    assert_eq!(ids(&index, "POSTGRES staging"), vec!["1", "2"]);
+    assert_eq!(ids(&index, "\"staging database\""), vec!["1"]);
+    assert_eq!(ids(&index, "\"database staging\""), Vec::<String>::new());
+    assert_eq!(ids(&index, "migr* checklist"), vec!["4"]);
+    assert_eq!(ids(&index, "gro*"), vec!["3"]);
+    // a phrase doesn't run from the title into the body
+    assert_eq!(ids(&index, "\"plan move\""), Vec::<String>::new());
+}
+
+#[test]
+fn test_update() {
+    let mut index = Index::new();
+    let mut notes = notes();
+    index.update(&notes);
+
+    notes[2].body = "milk, bread".to_string();
+    notes.remove(0);
+    assert!(index.update(&notes));
+    assert_eq!(ids(&index, "bread"), vec!["3"]);
+    assert!(ids(&index, "eggs").is_empty());
+    assert_eq!(ids(&index, "postgres"), vec!["4", "2"]);
+    assert!(!index.terms.contains_key("plan"));
+
+    let mut fresh = Index::new();
+    fresh.update(&notes);
+    assert_eq!(index, fresh);
+}
+
+#[test]
+fn test_saved_index() {
+    let dir = TempDir::new("theca").unwrap();
+    let profile_path = dir.path().join("default.json");
+    File::create(&profile_path).unwrap();
+    let mut p = Profile {
+        encrypted: false,
+        notes: notes(),
+        trash: vec![],
+        journal: vec![],
+    };
+
+    // nothing is written until the profile is searched
+    update_saved(&p, &profile_path).unwrap();
+    assert!(!index_path(&profile_path).exists());
+
+    let index = profile_index(&p, &profile_path).unwrap();
+    assert_eq!(index, Index::load(&index_path(&profile_path)));
+
+    p.notes.pop();
+    update_saved(&p, &profile_path).unwrap();
+    let mut fresh = Index::new();
+    fresh.update(&p.notes);
+    assert_eq!(Index::load(&index_path(&profile_path)), fresh);
+
+    // the words of an encrypted profile are never written to disk
+    p.encrypted = true;
+    update_saved(&p, &profile_path).unwrap();
+    assert!(!index_path(&profile_path).exists());
+    assert_eq!(profile_index(&p, &profile_path).unwrap(), fresh);
+    assert!(!index_path(&profile_path).exists());
+}
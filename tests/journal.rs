@@ -0,0 +1,178 @@
+extern crate theca;
+
+use theca::{BoolFlags, Profile};
+use theca::item::Status;
+use theca::journal::{apply, merge_journal, record, redo_pos, undo_pos, Entry};
+
+fn profile() -> Profile {
+    Profile {
+        encrypted: false,
+        notes: vec![],
+        trash: vec![],
+        journal: vec![],
+    }
+}
+
+fn add(p: &mut Profile, title: &str) {
+    let before = p.clone();
+    assert!(p.add_note(title, &[], Some(Status::Blank), false, false, false).is_ok());
+    let entry = Entry::between(&format!("add {:?}", title), &before, p).unwrap().unwrap();
+    record(&mut p.journal, entry);
+}
+
+fn titles(p: &Profile) -> Vec<String> {
+    p.notes.iter().map(|n| n.title.clone()).collect()
+}
+
+fn step(p: &mut Profile, forward: bool) {
+    let pos = if forward {
+        redo_pos(&p.journal).unwrap()
+    } else {
+        undo_pos(&p.journal).unwrap()
+    };
+    let entry = p.journal[pos].clone();
+    assert!(apply(p, &entry, forward, true).is_ok());
+    p.journal[pos].undone = !forward;
+}
+
+#[test]
+fn test_entry_between() {
+    let mut p = profile();
+    add(&mut p, "a");
+    let before = p.clone();
+    assert!(Entry::between("nothing", &before, &p).unwrap().is_none());
+    assert!(p.delete_note(&[1]).is_ok());
+    let entry = Entry::between("del 1", &before, &p).unwrap().unwrap();
+    assert_eq!(entry.notes.len(), 1);
+    assert!(entry.notes[0].before.is_some());
+    assert!(entry.notes[0].after.is_none());
+    assert_eq!(entry.trash.len(), 1);
+    assert!(entry.trash[0].before.is_none());
+    assert!(entry.encrypted.is_none());
+}
+
+#[test]
+fn test_undo_redo() {
+    let mut p = profile();
+    add(&mut p, "a");
+    add(&mut p, "b");
+    let before = p.clone();
+    assert!(p.delete_note(&[1]).is_ok());
+    let entry = Entry::between("del 1", &before, &p).unwrap().unwrap();
+    record(&mut p.journal, entry);
+    assert_eq!(titles(&p), vec!["b"]);
+
+    step(&mut p, false);
+    assert_eq!(titles(&p), vec!["a", "b"]);
+    assert_eq!(p.trash.len(), 0);
+    step(&mut p, false);
+    assert_eq!(titles(&p), vec!["a"]);
+    step(&mut p, true);
+    assert_eq!(titles(&p), vec!["a", "b"]);
+    assert_eq!(redo_pos(&p.journal), Some(2));
+
+    // a new command means what was undone can't be redone
+    add(&mut p, "c");
+    assert_eq!(p.journal.len(), 3);
+    assert_eq!(p.journal[2].id, 3);
+    assert_eq!(redo_pos(&p.journal), None);
+}
+
+fn edit(p: &mut Profile, title: &str) {
+    let before = p.clone();
+    assert!(p.edit_note(1,
+                        &title.to_string(),
+                        &[],
+                        Some(Status::Blank),
+                        false,
+                        BoolFlags::default())
+             .is_ok());
+    let entry = Entry::between(&format!("edit 1 {:?}", title), &before, p).unwrap().unwrap();
+    record(&mut p.journal, entry);
+}
+
+fn revisions(p: &Profile) -> Vec<usize> {
+    p.notes[0].history.iter().map(|r| r.rev).collect()
+}
+
+#[test]
+fn test_entry_without_history() {
+    let mut p = profile();
+    add(&mut p, "a");
+    edit(&mut p, "b");
+    edit(&mut p, "c");
+    let entry = p.journal.last().unwrap().clone();
+    assert!(entry.notes[0].before.as_ref().unwrap().history.is_empty());
+    assert!(entry.notes[0].after.as_ref().unwrap().history.is_empty());
+    let added: Vec<usize> = entry.notes[0].revisions.iter().map(|r| r.rev).collect();
+    assert_eq!(added, vec![2]);
+
+    // the history comes back with the note
+    step(&mut p, false);
+    assert_eq!(titles(&p), vec!["b"]);
+    assert_eq!(revisions(&p), vec![1]);
+    step(&mut p, true);
+    assert_eq!(titles(&p), vec!["c"]);
+    assert_eq!(revisions(&p), vec![1, 2]);
+
+    // a deleted note keeps its history in the trash
+    let before = p.clone();
+    assert!(p.delete_note(&[1]).is_ok());
+    let entry = Entry::between("del 1", &before, &p).unwrap().unwrap();
+    assert!(entry.notes[0].before.as_ref().unwrap().history.is_empty());
+    assert!(entry.trash[0].after.as_ref().unwrap().note.history.is_empty());
+    record(&mut p.journal, entry);
+    step(&mut p, false);
+    assert_eq!(revisions(&p), vec![1, 2]);
+
+    // a note that leaves the profile is kept with its history
+    let before = p.clone();
+    assert!(p.delete_note(&[1]).is_ok());
+    p.trash.clear();
+    let entry = Entry::between("clear --purge", &before, &p).unwrap().unwrap();
+    assert_eq!(entry.notes[0].before.as_ref().unwrap().history.len(), 2);
+    record(&mut p.journal, entry);
+    step(&mut p, false);
+    assert_eq!(revisions(&p), vec![1, 2]);
+}
+
+#[test]
+fn test_undo_keeps_ids() {
+    let mut p = profile();
+    add(&mut p, "a");
+    add(&mut p, "b");
+    let before = p.clone();
+    assert!(p.delete_note(&[1]).is_ok());
+    let entry = Entry::between("del 1", &before, &p).unwrap().unwrap();
+    record(&mut p.journal, entry);
+    step(&mut p, false);
+    assert_eq!(p.notes[0].id, 1);
+    assert_eq!(p.notes[1].id, 2);
+}
+
+#[test]
+fn test_merge_journal() {
+    let mut base = profile();
+    add(&mut base, "a");
+    add(&mut base, "b");
+    let mut ours = base.clone();
+    step(&mut ours, false);
+    add(&mut ours, "c");
+    let mut theirs = base.clone();
+    add(&mut theirs, "d");
+
+    let merged = merge_journal(&base.journal, &ours.journal, &theirs.journal);
+    let commands: Vec<String> = merged.iter().map(|e| e.command.clone()).collect();
+    // "b" was undone here and then dropped by adding "c"
+    assert_eq!(commands, vec!["add \"a\"", "add \"d\"", "add \"c\""]);
+    let ids: Vec<usize> = merged.iter().map(|e| e.id).collect();
+    assert_eq!(ids, vec![1, 3, 4]);
+
+    // undoing without running another command keeps the entry around to redo
+    let mut ours = base.clone();
+    step(&mut ours, false);
+    let merged = merge_journal(&base.journal, &ours.journal, &theirs.journal);
+    assert_eq!(merged.len(), 3);
+    assert!(merged[1].undone);
+    assert!(!merged[2].undone);
+}
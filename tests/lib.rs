@@ -1,13 +1,22 @@
 extern crate theca;
+extern crate rustc_serialize;
+extern crate time;
 
+use rustc_serialize::json::decode;
 use theca::{Profile, BoolFlags};
-use theca::item::Status;
+use theca::item::{Status, HISTORY_LEN};
+use theca::journal::Entry;
+use theca::profile::DATEFMT;
+use theca::utils::parse_date_input;
+use time::{at, strptime};
 
 #[test]
 fn test_add_note() {
     let mut p = Profile {
         encrypted: false,
         notes: vec![],
+        trash: vec![],
+        journal: vec![],
     };
     assert!(p.add_note("this is a title",
                        &[],
@@ -28,6 +37,8 @@ fn test_add_started_note() {
     let mut p = Profile {
         encrypted: false,
         notes: vec![],
+        trash: vec![],
+        journal: vec![],
     };
     assert!(p.add_note("this is a title",
                        &[],
@@ -48,6 +59,8 @@ fn test_add_urgent_note() {
     let mut p = Profile {
         encrypted: false,
         notes: vec![],
+        trash: vec![],
+        journal: vec![],
     };
     assert!(p.add_note("this is a title",
                        &[],
@@ -68,6 +81,8 @@ fn test_add_basic_body_note() {
     let mut p = Profile {
         encrypted: false,
         notes: vec![],
+        trash: vec![],
+        journal: vec![],
     };
     assert!(p.add_note("this is a title",
                        &["and what?".to_string()],
@@ -88,6 +103,8 @@ fn test_add_full_basic_body_note() {
     let mut p = Profile {
         encrypted: false,
         notes: vec![],
+        trash: vec![],
+        journal: vec![],
     };
     assert!(p.add_note("this is a title",
                        &["and what?".to_string()],
@@ -108,6 +125,8 @@ fn test_edit_note_title() {
     let mut p = Profile {
         encrypted: false,
         notes: vec![],
+        trash: vec![],
+        journal: vec![],
     };
     assert!(p.add_note("this is a title",
                        &[],
@@ -135,6 +154,8 @@ fn test_edit_note_status() {
     let mut p = Profile {
         encrypted: false,
         notes: vec![],
+        trash: vec![],
+        journal: vec![],
     };
     assert!(p.add_note("this is a title",
                        &[],
@@ -184,6 +205,8 @@ fn test_edit_note_body_basic() {
     let mut p = Profile {
         encrypted: false,
         notes: vec![],
+        trash: vec![],
+        journal: vec![],
     };
     let flags = BoolFlags::default();
     assert!(p.add_note("this is a title",
@@ -212,6 +235,8 @@ fn test_edit_full_note() {
     let mut p = Profile {
         encrypted: false,
         notes: vec![],
+        trash: vec![],
+        journal: vec![],
     };
     assert!(p.add_note("this is a title",
                        &[],
@@ -239,6 +264,8 @@ fn test_delete_single_note() {
     let mut p = Profile {
         encrypted: false,
         notes: vec![],
+        trash: vec![],
+        journal: vec![],
     };
     assert!(p.add_note("this is a title",
                        &[],
@@ -247,8 +274,10 @@ fn test_delete_single_note() {
                        false,
                        false)
              .is_ok());
-    p.delete_note(&[1]);
+    assert!(p.delete_note(&[1]).is_ok());
     assert_eq!(p.notes.len(), 0);
+    assert_eq!(p.trash.len(), 1);
+    assert_eq!(p.trash[0].note.title, "this is a title".to_string());
 }
 
 #[test]
@@ -256,6 +285,8 @@ fn test_delete_some_notes() {
     let mut p = Profile {
         encrypted: false,
         notes: vec![],
+        trash: vec![],
+        journal: vec![],
     };
     assert!(p.add_note("this is a title",
                        &[],
@@ -281,7 +312,7 @@ fn test_delete_some_notes() {
                        false)
              .is_ok());
     assert_eq!(p.notes.len(), 3);
-    p.delete_note(&[1, 3]);
+    assert!(p.delete_note(&[1, 3]).is_ok());
     assert_eq!(p.notes.len(), 1);
     assert_eq!(p.notes[0].id, 2);
     assert_eq!(p.notes[0].title, "this is a title".to_string());
@@ -294,6 +325,8 @@ fn test_clear_notes() {
     let mut p = Profile {
         encrypted: false,
         notes: vec![],
+        trash: vec![],
+        journal: vec![],
     };
     assert!(p.add_note("this is a title",
                        &[],
@@ -320,6 +353,347 @@ fn test_clear_notes() {
              .is_ok());
     assert_eq!(p.notes.len(), 3);
 
-    assert!(p.clear(true).is_ok());
+    assert!(p.clear(true, false).is_ok());
     assert_eq!(p.notes.len(), 0);
+    assert_eq!(p.trash.len(), 3);
+}
+
+#[test]
+fn test_clear_purge_notes() {
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![],
+        trash: vec![],
+        journal: vec![],
+    };
+    assert!(p.add_note("a", &[], Some(Status::Blank), false, false, false).is_ok());
+    assert!(p.add_note("b", &[], Some(Status::Blank), false, false, false).is_ok());
+    assert!(p.clear(true, true).is_ok());
+    assert_eq!(p.notes.len(), 0);
+    assert_eq!(p.trash.len(), 0);
+}
+
+#[test]
+fn test_restore_note() {
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![],
+        trash: vec![],
+        journal: vec![],
+    };
+    assert!(p.add_note("a", &[], Some(Status::Blank), false, false, false).is_ok());
+    assert!(p.add_note("b", &[], Some(Status::Blank), false, false, false).is_ok());
+    assert!(p.add_note("c", &[], Some(Status::Blank), false, false, false).is_ok());
+    let uuid = p.notes[1].uuid.clone();
+    assert!(p.delete_note(&[2]).is_ok());
+    assert!(p.restore_note("2").is_ok());
+    assert_eq!(p.trash.len(), 0);
+    assert_eq!(p.notes.len(), 3);
+    assert_eq!(p.notes[1].id, 2);
+    assert_eq!(p.notes[1].uuid, uuid);
+    assert!(p.restore_note("2").is_err());
+
+    // a restored note gets a new id if its id was given to another note
+    assert!(p.delete_note(&[3]).is_ok());
+    assert!(p.add_note("d", &[], Some(Status::Blank), false, false, false).is_ok());
+    assert_eq!(p.notes[2].id, 3);
+    let uuid = p.trash[0].note.uuid.clone();
+    assert!(p.restore_note(&uuid[..8]).is_ok());
+    assert_eq!(p.notes.len(), 4);
+    assert_eq!(p.notes[3].id, 4);
+    assert_eq!(p.notes[3].title, "c".to_string());
+}
+
+#[test]
+fn test_purge_trash() {
+    let json = r#"{"encrypted": false, "notes": [], "trash": [
+                  {"deleted": "2015-01-01 12:00:00 -0800", "note": {"id": 1, "title": "old",
+                   "status": "", "body": "", "last_touched": "2015-01-01 11:00:00 -0800"}},
+                  {"deleted": "2015-01-25 12:00:00 -0800", "note": {"id": 2, "title": "new",
+                   "status": "", "body": "", "last_touched": "2015-01-25 11:00:00 -0800"}}]}"#;
+    let mut p: Profile = decode(json).unwrap();
+    let mut before = p.clone();
+    before.trash.clear();
+    let entry = Entry::between("del 1 2", &before, &p).unwrap().unwrap();
+    p.journal.push(entry);
+    let now = strptime("2015-02-01 12:00:00 -0800", DATEFMT).unwrap();
+    assert_eq!(p.purge_trash(0, &now), 0);
+    assert_eq!(p.purge_trash(30, &now), 1);
+    assert_eq!(p.trash.len(), 1);
+    assert_eq!(p.trash[0].note.title, "new".to_string());
+    // the journal forgets the purged notes
+    assert_eq!(p.journal[0].trash.len(), 1);
+    assert_eq!(p.purge_trash(1, &now), 1);
+    assert_eq!(p.trash.len(), 0);
+    assert!(p.journal.is_empty());
+}
+
+#[test]
+fn test_empty_trash() {
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![],
+        trash: vec![],
+        journal: vec![],
+    };
+    assert!(p.add_note("a", &[], Some(Status::Blank), false, false, false).is_ok());
+    assert!(p.delete_note(&[1]).is_ok());
+    assert!(p.empty_trash(true).is_ok());
+    assert_eq!(p.trash.len(), 0);
+}
+
+#[test]
+fn test_tag_note() {
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![],
+        trash: vec![],
+        journal: vec![],
+    };
+    assert!(p.add_note("this is a title",
+                       &[],
+                       Some(Status::Blank),
+                       false,
+                       false,
+                       false)
+             .is_ok());
+    assert!(p.tag_note(1, &["infra".to_string(), "ops".to_string(), "infra".to_string()], &[])
+             .is_ok());
+    assert_eq!(p.notes[0].tags, vec!["infra".to_string(), "ops".to_string()]);
+    assert!(p.tag_note(1, &[], &["infra".to_string()]).is_ok());
+    assert_eq!(p.notes[0].tags, vec!["ops".to_string()]);
+    assert!(p.tag_note(2, &["infra".to_string()], &[]).is_err());
+}
+
+#[test]
+fn test_tag_counts() {
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![],
+        trash: vec![],
+        journal: vec![],
+    };
+    for _ in 0..3 {
+        assert!(p.add_note("this is a title",
+                           &[],
+                           Some(Status::Blank),
+                           false,
+                           false,
+                           false)
+                 .is_ok());
+    }
+    assert!(p.tag_note(1, &["infra".to_string()], &[]).is_ok());
+    assert!(p.tag_note(2, &["infra".to_string(), "ops".to_string()], &[]).is_ok());
+    let counts = p.tag_counts();
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts["infra"], 2);
+    assert_eq!(counts["ops"], 1);
+}
+
+#[test]
+fn test_decode_profile_without_tags() {
+    let p: Profile = decode(r#"{"encrypted": false, "notes": [{"id": 1, "title": "a title",
+                               "status": "", "body": "",
+                               "last_touched": "2015-01-22 19:43:24 -0800"}]}"#)
+                         .unwrap();
+    assert_eq!(p.notes.len(), 1);
+    assert!(p.notes[0].tags.is_empty());
+}
+
+#[test]
+fn test_decode_profile_without_uuids() {
+    let json = r#"{"encrypted": false, "notes": [{"id": 1, "title": "a title", "status": "",
+                  "body": "", "last_touched": "2015-01-22 19:43:24 -0800"}]}"#;
+    let a: Profile = decode(json).unwrap();
+    let b: Profile = decode(json).unwrap();
+    assert_eq!(a.notes[0].uuid.len(), 36);
+    // the same until the profile is saved with it
+    assert_eq!(a.notes[0].uuid, b.notes[0].uuid);
+}
+
+#[test]
+fn test_decode_profile_without_trash_or_journal() {
+    let json = r#"{"encrypted": false, "notes": []}"#;
+    let p: Profile = decode(json).unwrap();
+    assert_eq!(p.trash.len(), 0);
+    assert_eq!(p.journal.len(), 0);
+}
+
+#[test]
+fn test_decode_profile_without_history() {
+    let json = r#"{"encrypted": false, "notes": [{"id": 1, "title": "a title", "status": "",
+                  "body": "", "last_touched": "2015-01-22 19:43:24 -0800"}]}"#;
+    let p: Profile = decode(json).unwrap();
+    assert_eq!(p.notes[0].history.len(), 0);
+    assert_eq!(p.notes[0].revision(), 1);
+}
+
+#[test]
+fn test_note_revisions() {
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![],
+        trash: vec![],
+        journal: vec![],
+    };
+    assert!(p.add_note("a", &["body".to_string()], Some(Status::Blank), false, false, false)
+             .is_ok());
+    assert!(p.edit_note(1,
+                        &"b".to_string(),
+                        &[],
+                        Some(Status::Blank),
+                        false,
+                        BoolFlags::default())
+             .is_ok());
+    assert!(p.edit_note(1,
+                        &"".to_string(),
+                        &["new body".to_string()],
+                        Some(Status::Urgent),
+                        false,
+                        BoolFlags::default())
+             .is_ok());
+    assert_eq!(p.notes[0].revision(), 3);
+    assert_eq!(p.notes[0].history[0].changed(), vec!["title"]);
+    assert_eq!(p.notes[0].history[1].changed(), vec!["status", "body"]);
+
+    let first = p.notes[0].at_revision(1).unwrap();
+    assert_eq!(first.title, "a".to_string());
+    assert_eq!(first.body, "body".to_string());
+    assert_eq!(first.status, Status::Blank);
+    let second = p.notes[0].at_revision(2).unwrap();
+    assert_eq!(second.title, "b".to_string());
+    assert_eq!(second.body, "body".to_string());
+    assert!(p.notes[0].at_revision(4).is_none());
+
+    // reverting is an edit of its own
+    assert!(p.revert_note(1, "1").is_ok());
+    assert_eq!(p.notes[0].title, "a".to_string());
+    assert_eq!(p.notes[0].body, "body".to_string());
+    assert_eq!(p.notes[0].revision(), 4);
+    assert!(p.revert_note(1, "5").is_err());
+}
+
+#[test]
+fn test_note_history_is_bounded() {
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![],
+        trash: vec![],
+        journal: vec![],
+    };
+    assert!(p.add_note("0", &[], Some(Status::Blank), false, false, false).is_ok());
+    for i in 1..(HISTORY_LEN + 6) {
+        assert!(p.edit_note(1,
+                            &i.to_string(),
+                            &[],
+                            Some(Status::Blank),
+                            false,
+                            BoolFlags::default())
+                 .is_ok());
+    }
+    assert_eq!(p.notes[0].history.len(), HISTORY_LEN);
+    assert_eq!(p.notes[0].revision(), HISTORY_LEN + 6);
+    assert_eq!(p.notes[0].oldest_revision(), 6);
+    assert!(p.notes[0].at_revision(5).is_none());
+    assert_eq!(p.notes[0].at_revision(6).unwrap().title, "5".to_string());
+}
+
+#[test]
+fn test_add_note_uuid() {
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![],
+        trash: vec![],
+        journal: vec![],
+    };
+    assert!(p.add_note("a", &[], Some(Status::Blank), false, false, false).is_ok());
+    assert!(p.add_note("b", &[], Some(Status::Blank), false, false, false).is_ok());
+    assert_eq!(p.notes[0].uuid.len(), 36);
+    assert_eq!(&p.notes[0].uuid[14..15], "4");
+    assert!(p.notes[0].uuid != p.notes[1].uuid);
+}
+
+#[test]
+fn test_find_id() {
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![],
+        trash: vec![],
+        journal: vec![],
+    };
+    for title in &["a", "b", "c"] {
+        assert!(p.add_note(title, &[], Some(Status::Blank), false, false, false).is_ok());
+    }
+    p.notes[0].uuid = "3f2a0c1e-0000-4000-8000-000000000000".to_string();
+    p.notes[1].uuid = "3f2b9d00-0000-4000-8000-000000000000".to_string();
+    p.notes[2].uuid = "12345678-0000-4000-8000-000000000000".to_string();
+
+    assert_eq!(p.find_id("2").unwrap(), 2);
+    // short numbers are ids even when no note has that id
+    assert_eq!(p.find_id("1234").unwrap(), 1234);
+    assert_eq!(p.find_id("3f2a").unwrap(), 1);
+    assert_eq!(p.find_id("3F2B9D").unwrap(), 2);
+    assert_eq!(p.find_id("12345678").unwrap(), 3);
+    assert!(p.find_id("3f2").is_err());
+    assert_eq!(p.find_id("3f20").unwrap_err().desc,
+               "no note has a uuid starting with '3f20'");
+    assert_eq!(p.find_id("3f2-").unwrap_err().desc,
+               "no note has a uuid starting with '3f2-'");
+    assert!(p.find_id("zzzzzz").is_err());
+
+    p.notes[1].uuid = "3f2a9d00-0000-4000-8000-000000000000".to_string();
+    assert_eq!(p.find_id("3f2a").unwrap_err().desc,
+               "uuid prefix '3f2a' is ambiguous, it matches notes 1, 2");
+}
+
+#[test]
+fn test_find_id_digit_boundary() {
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![],
+        trash: vec![],
+        journal: vec![],
+    };
+    assert!(p.add_note("a", &[], Some(Status::Blank), false, false, false).is_ok());
+    p.notes[0].uuid = "12345678-9000-4000-8000-000000000000".to_string();
+
+    // up to 7 digits is an id, even when it's a prefix of a uuid
+    assert_eq!(p.find_id("1234567").unwrap(), 1234567);
+    // 8 or more digits is always a uuid prefix, never an id
+    assert_eq!(p.find_id("12345678").unwrap(), 1);
+    assert_eq!(p.find_id("87654321").unwrap_err().desc,
+               "no note has a uuid starting with '87654321'");
+    assert_eq!(p.find_id("123456789").unwrap_err().desc,
+               "no note has a uuid starting with '123456789'");
+}
+
+#[test]
+fn test_agenda() {
+    let mut p = Profile {
+        encrypted: false,
+        notes: vec![],
+        trash: vec![],
+        journal: vec![],
+    };
+    let now = at(strptime("2026-10-14 12:00:00 +0000", DATEFMT).unwrap().to_timespec());
+    for due in &["yesterday", "today", "+3d", "+2w", ""] {
+        assert!(p.add_note(due, &[], Some(Status::Blank), false, false, false).is_ok());
+        if !due.is_empty() {
+            let id = p.notes.len();
+            assert!(p.set_due(id, Some(parse_date_input(due, &now).unwrap())).is_ok());
+        }
+    }
+    let agenda = p.agenda(&now).unwrap();
+    assert_eq!(agenda.overdue.len(), 1);
+    assert_eq!(agenda.overdue[0].title, "yesterday");
+    assert_eq!(agenda.today.len(), 1);
+    assert_eq!(agenda.today[0].title, "today");
+    assert_eq!(agenda.this_week.len(), 1);
+    assert_eq!(agenda.this_week[0].title, "+3d");
+    assert_eq!(agenda.later.len(), 1);
+    assert_eq!(agenda.later[0].title, "+2w");
+
+    assert!(p.set_due(2, None).is_ok());
+    assert!(p.notes[1].due.is_none());
+    assert!(p.set_due(6, None).is_err());
 }
@@ -2,6 +2,7 @@ extern crate theca;
 
 use theca::item::{Status, Item};
 use theca::lineformat::LineFormat;
+use theca::utils::{display_width, format_field};
 
 struct LineTest {
     input_notes: Vec<Item>,
@@ -19,6 +20,8 @@ fn test_formatter(tests: &[LineTest]) {
         assert_eq!(t.expected_format.id_width, actual_format.id_width);
         assert_eq!(t.expected_format.title_width, actual_format.title_width);
         assert_eq!(t.expected_format.status_width, actual_format.status_width);
+        assert_eq!(t.expected_format.tags_width, actual_format.tags_width);
+        assert_eq!(t.expected_format.due_width, actual_format.due_width);
         assert_eq!(t.expected_format.touched_width, actual_format.touched_width);
     }
 }
@@ -28,19 +31,27 @@ fn test_new_line_format_basic() {
     let basic_tests = vec![LineTest {
                                input_notes: vec![Item {
                                                      id: 1,
+                                                     uuid: "".to_string(),
                                                      title: "a title".to_string(),
                                                      body: "".to_string(),
                                                      status: Status::Blank,
                                                      last_touched: "2015-01-22 19:43:24 -0800"
                                                                        .to_string(),
+                                                     tags: vec![],
+                                                     due: None,
+                                                     history: vec![],
                                                  },
                                                  Item {
                                                      id: 2,
+                                                     uuid: "".to_string(),
                                                      title: "a longer title".to_string(),
                                                      body: "".to_string(),
                                                      status: Status::Blank,
                                                      last_touched: "2015-01-22 19:43:24 -0800"
                                                                        .to_string(),
+                                                     tags: vec![],
+                                                     due: None,
+                                                     history: vec![],
                                                  }],
                                condensed: false,
                                search: false,
@@ -49,25 +60,35 @@ fn test_new_line_format_basic() {
                                    id_width: 2,
                                    title_width: 14,
                                    status_width: 0,
+                                   tags_width: 0,
+                                   due_width: 0,
                                    touched_width: 19,
                                },
                            },
                            LineTest {
                                input_notes: vec![Item {
                                                      id: 1,
+                                                     uuid: "".to_string(),
                                                      title: "a title".to_string(),
                                                      body: "".to_string(),
                                                      status: Status::Blank,
                                                      last_touched: "2015-01-22 19:43:24 -0800"
                                                                        .to_string(),
+                                                     tags: vec![],
+                                                     due: None,
+                                                     history: vec![],
                                                  },
                                                  Item {
                                                      id: 2,
+                                                     uuid: "".to_string(),
                                                      title: "a longer title".to_string(),
                                                      body: "".to_string(),
                                                      status: Status::Blank,
                                                      last_touched: "2015-01-22 19:43:24 -0800"
                                                                        .to_string(),
+                                                     tags: vec![],
+                                                     due: None,
+                                                     history: vec![],
                                                  }],
                                condensed: true,
                                search: false,
@@ -76,6 +97,8 @@ fn test_new_line_format_basic() {
                                    id_width: 1,
                                    title_width: 14,
                                    status_width: 0,
+                                   tags_width: 0,
+                                   due_width: 0,
                                    touched_width: 10,
                                },
                            }];
@@ -88,19 +111,27 @@ fn test_new_line_format_statuses() {
     let status_tests = vec![LineTest {
                                 input_notes: vec![Item {
                                                       id: 1,
+                                                      uuid: "".to_string(),
                                                       title: "a title".to_string(),
                                                       body: "".to_string(),
                                                       status: Status::Started,
                                                       last_touched: "2015-01-22 19:43:24 -0800"
                                                                         .to_string(),
+                                                      tags: vec![],
+                                                      due: None,
+                                                      history: vec![],
                                                   },
                                                   Item {
                                                       id: 2,
+                                                      uuid: "".to_string(),
                                                       title: "a longer title".to_string(),
                                                       body: "".to_string(),
                                                       status: Status::Blank,
                                                       last_touched: "2015-01-22 19:43:24 -0800"
                                                                         .to_string(),
+                                                      tags: vec![],
+                                                      due: None,
+                                                      history: vec![],
                                                   }],
                                 condensed: false,
                                 search: false,
@@ -109,25 +140,35 @@ fn test_new_line_format_statuses() {
                                     id_width: 2,
                                     title_width: 14,
                                     status_width: 7,
+                                    tags_width: 0,
+                                    due_width: 0,
                                     touched_width: 19,
                                 },
                             },
                             LineTest {
                                 input_notes: vec![Item {
                                                       id: 1,
+                                                      uuid: "".to_string(),
                                                       title: "a title".to_string(),
                                                       body: "".to_string(),
                                                       status: Status::Blank,
                                                       last_touched: "2015-01-22 19:43:24 -0800"
                                                                         .to_string(),
+                                                      tags: vec![],
+                                                      due: None,
+                                                      history: vec![],
                                                   },
                                                   Item {
                                                       id: 2,
+                                                      uuid: "".to_string(),
                                                       title: "a longer title".to_string(),
                                                       body: "".to_string(),
                                                       status: Status::Urgent,
                                                       last_touched: "2015-01-22 19:43:24 -0800"
                                                                         .to_string(),
+                                                      tags: vec![],
+                                                      due: None,
+                                                      history: vec![],
                                                   }],
                                 condensed: false,
                                 search: false,
@@ -136,25 +177,35 @@ fn test_new_line_format_statuses() {
                                     id_width: 2,
                                     title_width: 14,
                                     status_width: 6,
+                                    tags_width: 0,
+                                    due_width: 0,
                                     touched_width: 19,
                                 },
                             },
                             LineTest {
                                 input_notes: vec![Item {
                                                       id: 1,
+                                                      uuid: "".to_string(),
                                                       title: "a title".to_string(),
                                                       body: "".to_string(),
                                                       status: Status::Blank,
                                                       last_touched: "2015-01-22 19:43:24 -0800"
                                                                         .to_string(),
+                                                      tags: vec![],
+                                                      due: None,
+                                                      history: vec![],
                                                   },
                                                   Item {
                                                       id: 2,
+                                                      uuid: "".to_string(),
                                                       title: "a longer title".to_string(),
                                                       body: "".to_string(),
                                                       status: Status::Urgent,
                                                       last_touched: "2015-01-22 19:43:24 -0800"
                                                                         .to_string(),
+                                                      tags: vec![],
+                                                      due: None,
+                                                      history: vec![],
                                                   }],
                                 condensed: true,
                                 search: false,
@@ -163,6 +214,8 @@ fn test_new_line_format_statuses() {
                                     id_width: 1,
                                     title_width: 14,
                                     status_width: 1,
+                                    tags_width: 0,
+                                    due_width: 0,
                                     touched_width: 10,
                                 },
                             }];
@@ -175,19 +228,27 @@ fn test_new_line_format_body() {
     let body_tests = vec![LineTest {
                               input_notes: vec![Item {
                                                     id: 1,
+                                                    uuid: "".into(),
                                                     title: "a title".to_string(),
                                                     body: "".to_string(),
                                                     status: Status::Blank,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 },
                                                 Item {
                                                     id: 2,
+                                                    uuid: "".into(),
                                                     title: "a longer title".to_string(),
                                                     body: "this is a body".to_string(),
                                                     status: Status::Blank,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 }],
                               condensed: false,
                               search: false,
@@ -196,25 +257,35 @@ fn test_new_line_format_body() {
                                   id_width: 2,
                                   title_width: 18,
                                   status_width: 0,
+                                  tags_width: 0,
+                                  due_width: 0,
                                   touched_width: 19,
                               },
                           },
                           LineTest {
                               input_notes: vec![Item {
                                                     id: 1,
+                                                    uuid: "".into(),
                                                     title: "a title".to_string(),
                                                     body: "".to_string(),
                                                     status: Status::Blank,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 },
                                                 Item {
                                                     id: 2,
+                                                    uuid: "".into(),
                                                     title: "a longer title".to_string(),
                                                     body: "this is a body".to_string(),
                                                     status: Status::Blank,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 }],
                               condensed: true,
                               search: false,
@@ -223,25 +294,35 @@ fn test_new_line_format_body() {
                                   id_width: 1,
                                   title_width: 18,
                                   status_width: 0,
+                                  tags_width: 0,
+                                  due_width: 0,
                                   touched_width: 10,
                               },
                           },
                           LineTest {
                               input_notes: vec![Item {
                                                     id: 1,
+                                                    uuid: "".into(),
                                                     title: "a title".to_string(),
                                                     body: "".to_string(),
                                                     status: Status::Blank,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 },
                                                 Item {
                                                     id: 2,
+                                                    uuid: "".into(),
                                                     title: "a longer title".to_string(),
                                                     body: "this is a body".to_string(),
                                                     status: Status::Blank,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 }],
                               condensed: false,
                               search: true,
@@ -250,25 +331,35 @@ fn test_new_line_format_body() {
                                   id_width: 2,
                                   title_width: 14,
                                   status_width: 0,
+                                  tags_width: 0,
+                                  due_width: 0,
                                   touched_width: 19,
                               },
                           },
                           LineTest {
                               input_notes: vec![Item {
                                                     id: 1,
+                                                    uuid: "".into(),
                                                     title: "a title".to_string(),
                                                     body: "".to_string(),
                                                     status: Status::Blank,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 },
                                                 Item {
                                                     id: 2,
+                                                    uuid: "".into(),
                                                     title: "a longer title".to_string(),
                                                     body: "this is a body".to_string(),
                                                     status: Status::Blank,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 }],
                               condensed: true,
                               search: true,
@@ -277,6 +368,8 @@ fn test_new_line_format_body() {
                                   id_width: 1,
                                   title_width: 14,
                                   status_width: 0,
+                                  tags_width: 0,
+                                  due_width: 0,
                                   touched_width: 10,
                               },
                           }];
@@ -289,19 +382,27 @@ fn test_new_line_format_full() {
     let body_tests = vec![LineTest {
                               input_notes: vec![Item {
                                                     id: 1,
+                                                    uuid: "".into(),
                                                     title: "a title".to_string(),
                                                     body: "".to_string(),
                                                     status: Status::Started,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 },
                                                 Item {
                                                     id: 2,
+                                                    uuid: "".into(),
                                                     title: "a longer title".to_string(),
                                                     body: "this is a body".to_string(),
                                                     status: Status::Blank,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 }],
                               condensed: false,
                               search: false,
@@ -310,25 +411,35 @@ fn test_new_line_format_full() {
                                   id_width: 2,
                                   title_width: 18,
                                   status_width: 7,
+                                  tags_width: 0,
+                                  due_width: 0,
                                   touched_width: 19,
                               },
                           },
                           LineTest {
                               input_notes: vec![Item {
                                                     id: 1,
+                                                    uuid: "".into(),
                                                     title: "a title".to_string(),
                                                     body: "".to_string(),
                                                     status: Status::Started,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 },
                                                 Item {
                                                     id: 2,
+                                                    uuid: "".into(),
                                                     title: "a longer title".to_string(),
                                                     body: "this is a body".to_string(),
                                                     status: Status::Blank,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 }],
                               condensed: true,
                               search: false,
@@ -337,25 +448,35 @@ fn test_new_line_format_full() {
                                   id_width: 1,
                                   title_width: 18,
                                   status_width: 1,
+                                  tags_width: 0,
+                                  due_width: 0,
                                   touched_width: 10,
                               },
                           },
                           LineTest {
                               input_notes: vec![Item {
                                                     id: 1,
+                                                    uuid: "".into(),
                                                     title: "a title".to_string(),
                                                     body: "".to_string(),
                                                     status: Status::Urgent,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 },
                                                 Item {
                                                     id: 2,
+                                                    uuid: "".into(),
                                                     title: "a longer title".to_string(),
                                                     body: "this is a body".to_string(),
                                                     status: Status::Blank,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 }],
                               condensed: false,
                               search: true,
@@ -364,25 +485,35 @@ fn test_new_line_format_full() {
                                   id_width: 2,
                                   title_width: 14,
                                   status_width: 6,
+                                  tags_width: 0,
+                                  due_width: 0,
                                   touched_width: 19,
                               },
                           },
                           LineTest {
                               input_notes: vec![Item {
                                                     id: 1,
+                                                    uuid: "".into(),
                                                     title: "a title".to_string(),
                                                     body: "".to_string(),
                                                     status: Status::Blank,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 },
                                                 Item {
                                                     id: 2,
+                                                    uuid: "".into(),
                                                     title: "a longer title".to_string(),
                                                     body: "this is a body".to_string(),
                                                     status: Status::Urgent,
                                                     last_touched: "2015-01-22 19:43:24 -0800"
                                                                       .to_string(),
+                                                    tags: vec![],
+                                                    due: None,
+                                                    history: vec![],
                                                 }],
                               condensed: true,
                               search: true,
@@ -391,9 +522,174 @@ fn test_new_line_format_full() {
                                   id_width: 1,
                                   title_width: 14,
                                   status_width: 1,
+                                  tags_width: 0,
+                                  due_width: 0,
                                   touched_width: 10,
                               },
                           }];
 
     test_formatter(&body_tests[..]);
 }
+
+#[test]
+fn test_new_line_format_tags() {
+    let tag_tests = vec![LineTest {
+                             input_notes: vec![Item {
+                                                   id: 1,
+                                                   uuid: "".into(),
+                                                   title: "a title".to_string(),
+                                                   body: "".to_string(),
+                                                   status: Status::Blank,
+                                                   last_touched: "2015-01-22 19:43:24 -0800"
+                                                                     .to_string(),
+                                                   tags: vec!["infra".to_string(),
+                                                              "ops".to_string()],
+                                                   due: None,
+                                                   history: vec![],
+                                               },
+                                               Item {
+                                                   id: 2,
+                                                   uuid: "".into(),
+                                                   title: "a longer title".to_string(),
+                                                   body: "".to_string(),
+                                                   status: Status::Blank,
+                                                   last_touched: "2015-01-22 19:43:24 -0800"
+                                                                     .to_string(),
+                                                   tags: vec![],
+                                                   due: None,
+                                                   history: vec![],
+                                               }],
+                             condensed: false,
+                             search: false,
+                             expected_format: LineFormat {
+                                 colsep: 2,
+                                 id_width: 2,
+                                 title_width: 14,
+                                 status_width: 0,
+                                 tags_width: 9,
+                                 due_width: 0,
+                                 touched_width: 19,
+                             },
+                         },
+                         LineTest {
+                             input_notes: vec![Item {
+                                                   id: 1,
+                                                   uuid: "".into(),
+                                                   title: "a title".to_string(),
+                                                   body: "".to_string(),
+                                                   status: Status::Blank,
+                                                   last_touched: "2015-01-22 19:43:24 -0800"
+                                                                     .to_string(),
+                                                   tags: vec!["a".to_string()],
+                                                   due: None,
+                                                   history: vec![],
+                                               }],
+                             condensed: false,
+                             search: false,
+                             expected_format: LineFormat {
+                                 colsep: 2,
+                                 id_width: 2,
+                                 title_width: 7,
+                                 status_width: 0,
+                                 tags_width: 4,
+                                 due_width: 0,
+                                 touched_width: 19,
+                             },
+                         }];
+
+    test_formatter(&tag_tests[..]);
+}
+
+fn unicode_note(id: usize, title: &str, body: &str, tags: &[&str]) -> Item {
+    Item {
+        id: id,
+        uuid: "".to_string(),
+        title: title.to_string(),
+        body: body.to_string(),
+        status: Status::Blank,
+        last_touched: "2015-01-22 19:43:24 -0800".to_string(),
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        due: None,
+        history: vec![],
+    }
+}
+
+#[test]
+fn test_new_line_format_unicode() {
+    // widths are counted in terminal columns, not bytes or characters
+    let unicode_tests = vec![LineTest {
+                                 input_notes: vec![unicode_note(1, "日本語のメモ", "", &["仕事"]),
+                                                   unicode_note(2, "cafe\u{301} menu", "", &[])],
+                                 condensed: false,
+                                 search: false,
+                                 expected_format: LineFormat {
+                                     colsep: 2,
+                                     id_width: 2,
+                                     title_width: 12,
+                                     status_width: 0,
+                                     tags_width: 4,
+                                     due_width: 0,
+                                     touched_width: 19,
+                                 },
+                             },
+                             LineTest {
+                                 input_notes: vec![unicode_note(1, "日本語", "body", &[]),
+                                                   unicode_note(2, "cafe\u{301}s", "", &[])],
+                                 condensed: true,
+                                 search: false,
+                                 expected_format: LineFormat {
+                                     colsep: 1,
+                                     id_width: 1,
+                                     title_width: 10,
+                                     status_width: 0,
+                                     tags_width: 0,
+                                     due_width: 0,
+                                     touched_width: 10,
+                                 },
+                             }];
+
+    test_formatter(&unicode_tests[..]);
+}
+
+#[test]
+fn test_format_field_unicode() {
+    assert_eq!(display_width("日本語"), 6);
+    assert_eq!(display_width("cafe\u{301}"), 4);
+
+    // padding counts columns
+    assert_eq!(format_field("日本", 6, false), "日本  ");
+    assert_eq!(format_field("cafe\u{301}", 6, false), "cafe\u{301}  ");
+
+    // a wide character that doesn't fit is left out and the column padded
+    assert_eq!(format_field("日本語のメモ", 9, true), "日本語...");
+    assert_eq!(format_field("日本語のメモ", 10, true), "日本語... ");
+    assert_eq!(format_field("日本語のメモ", 5, false), "日本 ");
+
+    // combining marks stay with the character they belong to
+    assert_eq!(format_field("cafe\u{301} au lait", 7, true), "cafe\u{301}...");
+    assert_eq!(format_field("cafe\u{301} au lait", 4, false), "cafe\u{301}");
+    assert_eq!(format_field("e\u{301}e\u{301}e\u{301}e\u{301}e\u{301}", 4, true),
+               "e\u{301}...");
+}
+
+#[test]
+fn test_line_format_narrow_console() {
+    let notes = vec![Item {
+                         due: Some("2015-01-30 00:00:00 -0800".to_string()),
+                         ..unicode_note(1, "a fairly long title here", "", &["infra", "ops"])
+                     },
+                     unicode_note(2, "short", "", &[])];
+    let widths = |console_width: usize| {
+        let f = LineFormat::with_width(&notes, false, false, console_width).unwrap();
+        (f.title_width, f.tags_width, f.due_width, f.line_width())
+    };
+    // 0 means the width isn't known
+    assert_eq!(widths(0), (24, 9, 10, 72));
+    assert_eq!(widths(60), (12, 9, 10, 60));
+    // then the tags, which are dropped before the title gets any narrower
+    assert_eq!(widths(54), (10, 5, 10, 54));
+    assert_eq!(widths(50), (10, 0, 10, 47));
+    // narrower than the columns that are always printed
+    assert_eq!(widths(20), (10, 0, 0, 35));
+    assert_eq!(widths(1), (10, 0, 0, 35));
+}
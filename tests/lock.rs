@@ -0,0 +1,63 @@
+extern crate theca;
+extern crate tempdir;
+extern crate libc;
+
+use theca::lock::{lock_path, ProfileLock};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use tempdir::TempDir;
+
+fn read_file(file: &mut File) -> String {
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).unwrap();
+    contents
+}
+
+#[test]
+fn test_lock_records_pid() {
+    let dir = TempDir::new("theca").unwrap();
+    let folder = dir.path().to_str().unwrap();
+    let _lock = ProfileLock::acquire("default", folder, 0).unwrap();
+    let path = lock_path("default", folder).unwrap();
+    assert_eq!(path, dir.path().join(".default.json.lock"));
+    let pid = unsafe { libc::getpid() };
+    assert_eq!(read_file(&mut File::open(path).unwrap()),
+               format!("{}\n", pid));
+}
+
+#[test]
+fn test_lock_reentrant() {
+    let dir = TempDir::new("theca").unwrap();
+    let folder = dir.path().to_str().unwrap();
+    let first = ProfileLock::acquire("default", folder, 0).unwrap();
+    let second = ProfileLock::acquire("default", folder, 0).unwrap();
+    drop(second);
+    drop(first);
+    // released, so it can be taken by another file
+    let _third = ProfileLock::acquire("default", folder, 0).unwrap();
+}
+
+#[test]
+fn test_lock_held_elsewhere() {
+    let dir = TempDir::new("theca").unwrap();
+    let folder = dir.path().to_str().unwrap();
+
+    // pretend another process holds the lock
+    let path = lock_path("default", folder).unwrap();
+    let mut other = OpenOptions::new().write(true).create(true).open(&path).unwrap();
+    assert_eq!(unsafe { libc::flock(other.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) },
+               0);
+    other.write_all(b"4242\n").unwrap();
+
+    let err = ProfileLock::acquire("default", folder, 0).err().unwrap();
+    assert_eq!(err.desc,
+               "profile 'default' is locked by another theca process (pid 4242), gave up \
+                waiting after 0 seconds");
+
+    // other profiles aren't affected
+    assert!(ProfileLock::acquire("other", folder, 0).is_ok());
+
+    drop(other);
+    assert!(ProfileLock::acquire("default", folder, 0).is_ok());
+}
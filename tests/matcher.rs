@@ -0,0 +1,91 @@
+extern crate theca;
+
+use theca::BoolFlags;
+use theca::matcher::{edit_distance, write_highlighted, Matcher};
+
+fn matcher(pattern: &str, regex: bool, ignore_case: bool, fuzzy: bool) -> Matcher {
+    let mut flags = BoolFlags::default();
+    flags.regex = regex;
+    flags.ignore_case = ignore_case;
+    flags.fuzzy = fuzzy;
+    Matcher::new(pattern, &flags).unwrap()
+}
+
+fn chars(s: &str) -> Vec<char> {
+    s.chars().collect()
+}
+
+#[test]
+fn test_edit_distance() {
+    assert_eq!(edit_distance(&chars("deploy"), &chars("deploy")), 0);
+    assert_eq!(edit_distance(&chars("deplyo"), &chars("deploy")), 1);
+    assert_eq!(edit_distance(&chars("relase"), &chars("release")), 1);
+    assert_eq!(edit_distance(&chars("kitten"), &chars("sitting")), 3);
+    assert_eq!(edit_distance(&chars(""), &chars("abc")), 3);
+}
+
+#[test]
+fn test_keyword() {
+    let m = matcher("Deploy", false, false, false);
+    assert_eq!(m.score("Deploy the API, Deploy again"), Some(2));
+    assert_eq!(m.score("deploy the API"), None);
+    assert_eq!(m.spans("a Deploy"), vec![(2, 8)]);
+    // an empty pattern matches everything and highlights nothing
+    let m = matcher("", false, false, false);
+    assert_eq!(m.score("anything"), Some(0));
+    assert!(m.spans("anything").is_empty());
+}
+
+#[test]
+fn test_ignore_case() {
+    let m = matcher("deploy", false, true, false);
+    assert_eq!(m.score("Deploy the API"), Some(1));
+    assert_eq!(m.spans("DEPLOY, redeploy"), vec![(0, 6), (10, 16)]);
+    assert_eq!(matcher("ÜBER", false, true, false).spans("a über b"), vec![(2, 7)]);
+
+    let m = matcher("^dep", true, true, false);
+    assert_eq!(m.spans("Deploy"), vec![(0, 3)]);
+    assert_eq!(matcher("^dep", true, false, false).score("Deploy"), None);
+}
+
+#[test]
+fn test_fuzzy() {
+    let m = matcher("deplyo", false, false, true);
+    assert!(m.score("Deploy the API").is_some());
+    assert!(m.score("deployment checklist").is_some());
+    assert_eq!(m.score("write the release notes"), None);
+
+    // the word itself beats a misspelling, which beats letters left out
+    let m = matcher("deploy", false, false, true);
+    let exact = m.score("Deploy the API").unwrap();
+    let typo = matcher("deplo", false, false, true).score("Depoly the API").unwrap();
+    let sparse = matcher("dply", false, false, true).score("Deploy the API").unwrap();
+    assert!(exact > typo && typo > sparse);
+    assert!(m.score("the Deploy") > m.score("redeploy"));
+
+    // every word of the pattern has to be found
+    let m = matcher("dply chk", false, false, true);
+    assert!(m.score("deployment checklist").is_some());
+    assert_eq!(m.score("deployment notes"), None);
+    assert_eq!(m.spans("deployment checklist"),
+               vec![(0, 1), (2, 4), (5, 6), (11, 13), (15, 16)]);
+
+    // short words can't be misspelled
+    assert_eq!(matcher("api", false, false, true).score("the apo"), None);
+}
+
+#[test]
+fn test_bad_pattern() {
+    let mut flags = BoolFlags::default();
+    flags.regex = true;
+    assert!(Matcher::new("(", &flags).is_err());
+    flags.fuzzy = true;
+    assert!(Matcher::new("x", &flags).is_err());
+}
+
+#[test]
+fn test_write_highlighted() {
+    let mut out: Vec<u8> = vec![];
+    write_highlighted(&mut out, "no spans", &[]).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "no spans");
+}
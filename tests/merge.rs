@@ -0,0 +1,149 @@
+extern crate theca;
+
+use theca::item::{Item, Revision, Status};
+use theca::merge::{merge_notes, merge_trash, Conflict, Field};
+use theca::profile::Trashed;
+
+fn uuid(id: usize) -> String {
+    format!("1715d61b-f3c4-41d7-8fb2-{:012}", id)
+}
+
+fn note(id: usize, title: &str, body: &str) -> Item {
+    Item {
+        id: id,
+        uuid: uuid(id),
+        title: title.to_string(),
+        status: Status::Blank,
+        body: body.to_string(),
+        last_touched: "2016-07-08 16:31:14 -0700".to_string(),
+        tags: vec![],
+        due: None,
+        history: vec![],
+    }
+}
+
+fn trashed(id: usize) -> Trashed {
+    Trashed {
+        deleted: "2016-07-10 09:00:00 -0700".to_string(),
+        note: note(id, "deleted", ""),
+    }
+}
+
+fn titles(notes: &[Item]) -> Vec<(usize, String)> {
+    notes.iter().map(|n| (n.id, n.title.clone())).collect()
+}
+
+#[test]
+fn test_merge_unchanged() {
+    let base = vec![note(1, "a", ""), note(2, "b", "")];
+    let merge = merge_notes(&base, &base, &base);
+    assert!(merge.conflicts.is_empty());
+    assert_eq!(titles(&merge.notes), titles(&base));
+}
+
+#[test]
+fn test_merge_different_fields() {
+    let base = vec![note(1, "a", "body")];
+    let mut ours = base.clone();
+    ours[0].body = "new body".to_string();
+    ours[0].last_touched = "2016-07-09 10:00:00 -0700".to_string();
+    let mut theirs = base.clone();
+    theirs[0].title = "new title".to_string();
+    theirs[0].status = Status::Urgent;
+
+    let merge = merge_notes(&base, &ours, &theirs);
+    assert!(merge.conflicts.is_empty());
+    assert_eq!(merge.notes.len(), 1);
+    assert_eq!(merge.notes[0].title, "new title".to_string());
+    assert_eq!(merge.notes[0].status, Status::Urgent);
+    assert_eq!(merge.notes[0].body, "new body".to_string());
+    assert_eq!(merge.notes[0].last_touched,
+               "2016-07-09 10:00:00 -0700".to_string());
+}
+
+#[test]
+fn test_merge_adds_and_deletes() {
+    let base = vec![note(1, "a", ""), note(2, "b", ""), note(3, "c", "")];
+    // deleted 1, added 4
+    let mut added = note(4, "ours", "");
+    added.uuid = uuid(15);
+    let ours = vec![note(2, "b", ""), note(3, "c", ""), added];
+    // deleted 3, added 4
+    let theirs = vec![note(1, "a", ""), note(2, "b", ""), note(4, "theirs", "")];
+
+    let merge = merge_notes(&base, &ours, &theirs);
+    assert!(merge.conflicts.is_empty());
+    assert_eq!(titles(&merge.notes),
+               vec![(2, "b".to_string()), (4, "theirs".to_string()), (5, "ours".to_string())]);
+    assert_eq!(merge.renumbered, vec![(4, 5)]);
+}
+
+#[test]
+fn test_merge_conflicts() {
+    let base = vec![note(1, "a", ""), note(2, "b", ""), note(3, "c", "")];
+    let ours = vec![note(1, "ours", ""), note(3, "c changed here", "")];
+    let theirs = vec![note(1, "theirs", ""), note(2, "b changed there", "")];
+
+    let mut merge = merge_notes(&base, &ours, &theirs);
+    assert_eq!(merge.conflicts.len(), 3);
+
+    // conflicts are left as they are on disk until resolved
+    assert_eq!(titles(&merge.notes),
+               vec![(1, "theirs".to_string()), (2, "b changed there".to_string())]);
+
+    match merge.conflicts[0] {
+        Conflict::Field { field, .. } => assert_eq!(field, Field::Title),
+        _ => panic!("expected a field conflict"),
+    }
+    match merge.conflicts[1] {
+        Conflict::DeletedHere { ref theirs } => assert_eq!(theirs.id, 2),
+        _ => panic!("expected a deleted here conflict"),
+    }
+    match merge.conflicts[2] {
+        Conflict::DeletedThere { ref ours } => assert_eq!(ours.id, 3),
+        _ => panic!("expected a deleted there conflict"),
+    }
+
+    for conflict in merge.conflicts.clone().iter() {
+        merge.resolve(conflict, true);
+    }
+    assert_eq!(titles(&merge.notes),
+               vec![(1, "ours".to_string()), (3, "c changed here".to_string())]);
+}
+
+#[test]
+fn test_merge_trash() {
+    let base = vec![trashed(1), trashed(2), trashed(3)];
+    // 1 was restored here, 4 deleted here, 5 deleted on disk, 2 purged on disk
+    let ours = vec![trashed(2), trashed(3), trashed(4)];
+    let theirs = vec![trashed(1), trashed(3), trashed(5)];
+    let notes = vec![note(1, "deleted", "")];
+    let merged = merge_trash(&base, &ours, &theirs, &notes);
+    let ids: Vec<usize> = merged.iter().map(|t| t.note.id).collect();
+    assert_eq!(ids, vec![3, 5, 4]);
+}
+
+#[test]
+fn test_merge_history() {
+    let base = vec![note(1, "a", "body")];
+    let mut ours = base.clone();
+    ours[0].body = "new body".to_string();
+    ours[0].last_touched = "2016-07-09 10:00:00 -0700".to_string();
+    assert!(ours[0].record_revision(&base[0]));
+    let mut theirs = base.clone();
+    theirs[0].title = "new title".to_string();
+    theirs[0].last_touched = "2016-07-09 09:00:00 -0700".to_string();
+    assert!(theirs[0].record_revision(&base[0]));
+
+    let merge = merge_notes(&base, &ours, &theirs);
+    assert!(merge.conflicts.is_empty());
+    let merged = &merge.notes[0];
+    assert_eq!(merged.revision(), 3);
+    let history: Vec<Revision> = merged.history.clone();
+    assert_eq!(history[1].changed(), vec!["body"]);
+    assert_eq!(history[1].last_touched, "2016-07-09 09:00:00 -0700".to_string());
+    // undoing our change gives the version on disk, undoing theirs the original
+    assert_eq!(merged.at_revision(2).unwrap().title, "new title".to_string());
+    assert_eq!(merged.at_revision(2).unwrap().body, "body".to_string());
+    assert_eq!(merged.at_revision(1).unwrap().title, "a".to_string());
+}
@@ -0,0 +1,115 @@
+extern crate theca;
+
+use theca::item::{Item, Status};
+use theca::org::{add_ids, from_org, headline, notes_to_org, to_org};
+
+fn note(id: usize, title: &str, status: Status, body: &str) -> Item {
+    Item {
+        id: id,
+        uuid: format!("1715d61b-f3c4-41d7-8fb2-{:012}", id),
+        title: title.to_string(),
+        status: status,
+        body: body.to_string(),
+        last_touched: "2015-01-22 19:43:24 -0800".to_string(),
+        tags: vec![],
+        due: None,
+        history: vec![],
+    }
+}
+
+#[test]
+fn test_headline() {
+    let mut n = note(1, "Deploy the site", Status::Urgent, "");
+    n.tags = vec!["work".to_string(), "ops team".to_string(), "@desk".to_string()];
+    assert_eq!(headline(&n), "* [#A] Deploy the site :work:ops_team:@desk:");
+    assert_eq!(headline(&note(2, "Half done", Status::Started, "")), "* STARTED Half done");
+    assert_eq!(headline(&note(3, "", Status::Blank, "")), "*");
+    let mut n = note(4, "Pay rent", Status::Urgent, "");
+    n.tags = vec!["done".to_string(), "home".to_string()];
+    assert_eq!(headline(&n), "* DONE [#A] Pay rent :home:");
+}
+
+#[test]
+fn test_to_org() {
+    let mut n = note(1, "Deploy", Status::Blank, "* not a headline\n,comma\n");
+    n.due = Some("2015-01-30 00:00:00 -0800".to_string());
+    assert_eq!(to_org(&n).unwrap(),
+               "* Deploy\n\
+                DEADLINE: <2015-01-30 Fri>\n\
+                :PROPERTIES:\n\
+                :ID:           1715d61b-f3c4-41d7-8fb2-000000000001\n\
+                :THECA_ID:     1\n\
+                :LAST_TOUCHED: 2015-01-22 19:43:24 -0800\n\
+                :END:\n\
+                ,* not a headline\n\
+                ,,comma\n\
+                \n");
+}
+
+#[test]
+fn test_org_round_trip() {
+    let mut notes = vec![note(1, "one", Status::Urgent, "* a\n,b\n\nc"),
+                         note(2, "two", Status::Started, "ends with a newline\n"),
+                         note(3, "three", Status::Blank, "")];
+    notes[0].tags = vec!["work".to_string()];
+    notes[1].due = Some("2015-01-30 00:00:00 -0800".to_string());
+    let parsed = from_org(&notes_to_org(&notes).unwrap()).unwrap();
+    assert_eq!(parsed.len(), 3);
+    for (&(_, ref p), n) in parsed.iter().zip(notes.iter()) {
+        assert_eq!(p.uuid, Some(n.uuid.clone()));
+        assert_eq!(p.id, Some(n.id));
+        assert_eq!(p.title, Some(n.title.clone()));
+        assert_eq!(p.status, Some(n.status));
+        assert_eq!(p.last_touched, Some(n.last_touched.clone()));
+        assert_eq!(p.tags, n.tags);
+        assert_eq!(p.body, Some(n.body.clone()));
+    }
+    assert_eq!(parsed[1].1.due, Some("2015-01-30".to_string()));
+}
+
+#[test]
+fn test_from_org_hand_written() {
+    let text = "#+TITLE: notes\n\
+                intro\n\
+                * TODO [#B] call mom :family:\n\
+                SCHEDULED: <2015-01-20 Tue>\n\
+                ** a sub heading\n\
+                * DONE [#A] pay rent\n\
+                * title: with colons\n";
+    let parsed = from_org(text).unwrap();
+    assert_eq!(parsed.len(), 3);
+    assert_eq!(parsed[0].0, 2);
+    assert_eq!(parsed[0].1.title, Some("call mom".to_string()));
+    assert_eq!(parsed[0].1.status, Some(Status::Started));
+    assert_eq!(parsed[0].1.tags, vec!["family"]);
+    assert_eq!(parsed[0].1.due, None);
+    assert_eq!(parsed[0].1.body, Some("** a sub heading".to_string()));
+    assert_eq!(parsed[1].1.tags, vec!["done"]);
+    assert_eq!(parsed[1].1.status, Some(Status::Urgent));
+    assert_eq!(parsed[2].1.title, Some("title: with colons".to_string()));
+    assert!(from_org("* x\n:PROPERTIES:\n:ID: abc\n").is_err());
+}
+
+#[test]
+fn test_add_ids() {
+    let text = "* one\nDEADLINE: <2015-01-30 Fri>\nbody\n* two\n:PROPERTIES:\n:FOO: bar\n:END:\n";
+    let one = note(1, "one", Status::Blank, "");
+    let two = note(2, "two", Status::Blank, "");
+    let with_ids = add_ids(text, &[(0, &one), (3, &two)]);
+    assert_eq!(with_ids,
+               "* one\n\
+                DEADLINE: <2015-01-30 Fri>\n\
+                :PROPERTIES:\n\
+                :ID:           1715d61b-f3c4-41d7-8fb2-000000000001\n\
+                :END:\n\
+                body\n\
+                * two\n\
+                :PROPERTIES:\n\
+                :ID:           1715d61b-f3c4-41d7-8fb2-000000000002\n\
+                :FOO: bar\n\
+                :END:\n");
+    let parsed = from_org(&with_ids).unwrap();
+    assert_eq!(parsed[0].1.uuid, Some(one.uuid.clone()));
+    assert_eq!(parsed[0].1.body, Some("body".to_string()));
+    assert_eq!(parsed[1].1.uuid, Some(two.uuid.clone()));
+}
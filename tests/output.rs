@@ -0,0 +1,61 @@
+extern crate theca;
+
+use theca::item::{Item, Status};
+use theca::output::{Info, Template};
+
+fn note() -> Item {
+    Item {
+        id: 3,
+        uuid: "1715d61b-f3c4-41d7-8fb2-000000000003".to_string(),
+        title: "Deploy the API".to_string(),
+        status: Status::Urgent,
+        body: "check the CDN".to_string(),
+        last_touched: "2015-01-22 19:43:24 -0800".to_string(),
+        tags: vec!["work".to_string(), "ops".to_string()],
+        due: None,
+        history: vec![],
+    }
+}
+
+#[test]
+fn test_render_note() {
+    let render = |t: &str| Template::parse(t).unwrap().render_note(&note()).unwrap();
+    assert_eq!(render("*{title}* ({status})\\n{body}"),
+               "*Deploy the API* (Urgent)\ncheck the CDN");
+    assert_eq!(render("{id}\\t{ tags }{due} rev {revision}"), "3\twork, ops rev 1");
+    assert_eq!(render("{{{uuid}}} \\\\n"), "{1715d61b-f3c4-41d7-8fb2-000000000003} \\n");
+    assert_eq!(render("no fields"), "no fields");
+}
+
+#[test]
+fn test_render_hit() {
+    let template = Template::parse("{profile}/{id}: {title}").unwrap();
+    assert_eq!(template.render_hit("work", &note()).unwrap(), "work/3: Deploy the API");
+    // {profile} is only there for notes found in several profiles
+    assert!(Template::parse("{profile}").unwrap().render_note(&note()).is_err());
+}
+
+#[test]
+fn test_render_info() {
+    let info = Info {
+        name: "work".to_string(),
+        encrypted: false,
+        kdf: None,
+        notes: 4,
+        none: 1,
+        started: 2,
+        urgent: 1,
+        oldest: "2015-01-22 19:43:24".to_string(),
+        newest: "2015-01-30 08:00:00".to_string(),
+    };
+    let template = Template::parse("{name}: {notes} notes, {urgent} urgent").unwrap();
+    assert_eq!(template.render_info(&info).unwrap(), "work: 4 notes, 1 urgent");
+    assert!(Template::parse("{title}").unwrap().render_info(&info).is_err());
+}
+
+#[test]
+fn test_bad_template() {
+    assert!(Template::parse("{title").is_err());
+    assert!(Template::parse("title}").is_err());
+    assert!(Template::parse("{titel}").unwrap().render_note(&note()).is_err());
+}
@@ -0,0 +1,115 @@
+extern crate theca;
+extern crate time;
+
+use time::{strptime, at, Tm};
+
+use theca::item::{Item, Status};
+use theca::query::Query;
+
+fn note(id: usize, title: &str, status: Status, body: &str, touched: &str) -> Item {
+    Item {
+        id: id,
+        uuid: format!("1715d61b-f3c4-41d7-8fb2-{:012}", id),
+        title: title.to_string(),
+        status: status,
+        body: body.to_string(),
+        last_touched: format!("{} -0800", touched),
+        tags: vec![],
+        due: None,
+        history: vec![],
+    }
+}
+
+fn notes() -> Vec<Item> {
+    let mut deploy = note(2,
+                          "Deploy notes",
+                          Status::Urgent,
+                          "the staging deploy uses Postgres",
+                          "2015-01-20 09:00:00");
+    deploy.tags = vec!["Work".to_string()];
+    deploy.due = Some("2015-01-23 00:00:00 -0800".to_string());
+    vec![note(1,
+              "Postgres migration plan",
+              Status::Urgent,
+              "move the production database to postgres",
+              "2015-01-21 12:00:00"),
+         deploy,
+         note(3, "Groceries", Status::Blank, "milk, eggs", "2015-01-02 10:00:00"),
+         note(4, "deploy checklist", Status::Started, "", "2014-12-01 10:00:00")]
+}
+
+fn now() -> Tm {
+    at(strptime("2015-01-22 12:00:00 -0800", "%Y-%m-%d %H:%M:%S %z").unwrap().to_timespec())
+}
+
+fn ids(query: &str) -> Vec<usize> {
+    let q = Query::parse(query, &now()).unwrap();
+    notes().iter().filter(|n| q.matches(n)).map(|n| n.id).collect()
+}
+
+fn error(query: &str) -> String {
+    Query::parse(query, &now()).unwrap_err().desc
+}
+
+#[test]
+fn test_query_text() {
+    assert_eq!(ids("postgres"), vec![1, 2]);
+    assert_eq!(ids("title:postgres"), vec![1]);
+    assert_eq!(ids("body:postgres -body:staging"), vec![1]);
+    assert_eq!(ids("title:\"deploy notes\""), vec![2]);
+    assert_eq!(ids("title:/^[Dd]eploy/"), vec![2, 4]);
+    assert_eq!(ids("body:/^$/"), vec![4]);
+}
+
+#[test]
+fn test_query_fields() {
+    assert_eq!(ids("status:urgent"), vec![1, 2]);
+    assert_eq!(ids("status:none"), vec![3]);
+    assert_eq!(ids("tag:work"), vec![2]);
+    assert_eq!(ids("due:none"), vec![1, 3, 4]);
+    assert_eq!(ids("due:tomorrow"), vec![2]);
+    assert_eq!(ids("due:<=+1d"), vec![2]);
+    assert_eq!(ids("due:<+1d"), Vec::<usize>::new());
+}
+
+#[test]
+fn test_query_touched() {
+    assert_eq!(ids("touched:>7d"), vec![1, 2]);
+    assert_eq!(ids("touched:<7d"), vec![3, 4]);
+    assert_eq!(ids("touched:>24h"), vec![1]);
+    assert_eq!(ids("touched:yesterday"), vec![1]);
+    assert_eq!(ids("touched:>=2015-01-02 touched:<=2015-01-20"), vec![2, 3]);
+    assert_eq!(ids("touched:>2015-01-20"), vec![1]);
+    assert_eq!(ids("touched:<2w"), vec![3, 4]);
+}
+
+#[test]
+fn test_query_boolean() {
+    assert_eq!(ids("status:urgent touched:>7d body:postgres -body:staging"), vec![1]);
+    assert_eq!(ids("status:started OR tag:work"), vec![2, 4]);
+    assert_eq!(ids("milk OR status:urgent AND title:plan"), vec![1, 3]);
+    assert_eq!(ids("-(status:urgent OR status:started)"), vec![3]);
+    assert_eq!(ids("NOT deploy"), vec![1, 3]);
+    // keywords only count on their own and in capitals
+    assert_eq!(ids("ORDER"), Vec::<usize>::new());
+    assert_eq!(ids("postgres or"), Vec::<usize>::new());
+}
+
+#[test]
+fn test_query_errors() {
+    assert_eq!(error("size:big"),
+               "there is no field 'size' to query, use one of title, body, status, tag, \
+                touched, due at position 1 of the query");
+    assert_eq!(error("status:soon"), "unknown status 'soon' at position 8 of the query");
+    assert_eq!(error("a (b OR c"), "a ( that isn't closed at position 3 of the query");
+    assert_eq!(error("a ) b"), "a ) that wasn't opened at position 3 of the query");
+    assert_eq!(error("title:\"x"), "a quote that isn't closed at position 7 of the query");
+    assert_eq!(error("body:/x"), "a regex that isn't closed at position 6 of the query");
+    assert_eq!(error("title: x"), "expected a value for title at position 7 of the query");
+    assert_eq!(error("a OR"), "expected a term at position 5 of the query");
+    assert_eq!(error("- a"), "expected a term after - at position 2 of the query");
+    assert_eq!(error("due:>soon"),
+               "couldn't understand the date 'soon' at position 5 of the query");
+    assert!(error("title:/(/").starts_with("regex error"));
+    assert_eq!(error(""), "expected a term at position 1 of the query");
+}
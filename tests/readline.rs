@@ -0,0 +1,66 @@
+extern crate theca;
+
+use theca::readline::{LineBuffer, LineEditor, HISTORY_LEN};
+use theca::tui::{parse_keys, Key};
+
+fn type_keys(buffer: &mut LineBuffer, bytes: &[u8]) {
+    for key in parse_keys(bytes) {
+        buffer.apply(key);
+    }
+}
+
+#[test]
+fn test_line_buffer_editing() {
+    let mut buffer = LineBuffer::default();
+    type_keys(&mut buffer, b"add tpyo\x1b[D\x1b[D\x7fy");
+    assert_eq!(buffer.line(), "add tyyo");
+    assert_eq!(buffer.cursor, 6);
+    type_keys(&mut buffer, b"\x01\x1b[3~A\x05!");
+    assert_eq!(buffer.line(), "Add tyyo!");
+    type_keys(&mut buffer, b"\x1b[D\x1b[D\x0b");
+    assert_eq!(buffer.line(), "Add tyy");
+    assert!(!buffer.apply(Key::Ctrl('i')));
+}
+
+#[test]
+fn test_line_buffer_kill() {
+    let mut buffer = LineBuffer::new("edit 3 -s  ");
+    buffer.apply(Key::Ctrl('w'));
+    assert_eq!(buffer.line(), "edit 3 ");
+    buffer.apply(Key::Left);
+    buffer.apply(Key::Ctrl('u'));
+    assert_eq!(buffer.line(), " ");
+    assert_eq!(buffer.cursor, 0);
+}
+
+#[test]
+fn test_line_buffer_window() {
+    let mut buffer = LineBuffer::new("abcdef");
+    assert_eq!(buffer.window(10), (0, 6));
+    assert_eq!(buffer.window(4), (2, 6));
+    buffer.apply(Key::Home);
+    assert_eq!(buffer.window(4), (0, 4));
+
+    // wide characters take two columns each
+    let mut buffer = LineBuffer::new("日本語テキスト");
+    assert_eq!(buffer.window(5), (5, 7));
+    buffer.apply(Key::Left);
+    assert_eq!(buffer.window(5), (4, 6));
+    buffer.apply(Key::Home);
+    assert_eq!(buffer.window(5), (0, 2));
+}
+
+#[test]
+fn test_history() {
+    let mut editor = LineEditor::new();
+    editor.add_history("add one");
+    editor.add_history("add one");
+    editor.add_history("  ");
+    editor.add_history("list");
+    assert_eq!(editor.history, vec!["add one", "list"]);
+    for i in 0..HISTORY_LEN {
+        editor.add_history(&i.to_string());
+    }
+    assert_eq!(editor.history.len(), HISTORY_LEN);
+    assert_eq!(editor.history[0], "0");
+}
@@ -0,0 +1,93 @@
+extern crate theca;
+extern crate tempdir;
+extern crate rustc_serialize;
+
+use rustc_serialize::json::decode;
+use theca::Profile;
+use std::env::current_exe;
+use std::fs::{create_dir, File};
+use std::io::{Read, Write};
+use std::iter::repeat;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tempdir::TempDir;
+
+// the theca binary built next to the test binary (in target/debug/deps)
+fn theca() -> PathBuf {
+    let exe = current_exe().unwrap();
+    exe.parent().unwrap().parent().unwrap().join("theca")
+}
+
+// run theca with `args` on the profiles in `folder`, feeding it `input`
+fn run(folder: &Path, args: &[&str], input: &str) -> String {
+    let config = folder.join("config.toml");
+    File::create(&config).unwrap();
+    let mut child = Command::new(theca())
+                        .arg("-f")
+                        .arg(folder)
+                        .args(args)
+                        .env("THECA_CONFIG", &config)
+                        .stdin(Stdio::piped())
+                        .stdout(Stdio::piped())
+                        .spawn()
+                        .unwrap();
+    child.stdin.as_mut().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn read_profile(path: &Path) -> Profile {
+    let mut contents = String::new();
+    File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+    decode(&contents).unwrap()
+}
+
+#[test]
+fn test_failure_after_save_keeps_the_saved_profile() {
+    let dir = TempDir::new("theca").unwrap();
+    let notes = dir.path().join("notes");
+    create_dir(&notes).unwrap();
+    // the name leaves no room for the temporary file the id is written back
+    // with, so import-dir fails after the profile has been saved
+    let name: String = repeat('a').take(245).collect();
+    let source = notes.join(format!("{}.md", name));
+    File::create(&source).unwrap().write_all(b"imported body").unwrap();
+
+    run(dir.path(), &["new-profile", "-y"], "");
+    run(dir.path(),
+        &["shell"],
+        &format!("import-dir {}\nadd second\n", notes.display()));
+    let mut contents = String::new();
+    File::open(&source).unwrap().read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "imported body");
+
+    let profile = read_profile(&dir.path().join("default.json"));
+    let bodies: Vec<String> = profile.notes.iter().map(|n| n.body.clone()).collect();
+    assert_eq!(bodies, vec!["imported body", ""]);
+    assert_eq!(profile.notes[1].title, "second");
+}
+
+#[test]
+fn test_purged_notes_leave_nothing_behind() {
+    let dir = TempDir::new("theca").unwrap();
+    run(dir.path(), &["new-profile", "-y"], "");
+    run(dir.path(),
+        &["shell"],
+        "add 'purged title' -b 'purged body'\nedit 1 'purged edit'\nadd 'trashed title'\n\
+         del 2\nclear --purge -y\nadd kept\n");
+
+    let mut contents = String::new();
+    File::open(dir.path().join("default.json")).unwrap().read_to_string(&mut contents).unwrap();
+    assert!(!contents.contains("purged"));
+    let profile = read_profile(&dir.path().join("default.json"));
+    assert_eq!(profile.trash.len(), 1);
+    // only the commands that still have something to undo are left
+    let commands: Vec<String> = profile.journal.iter().map(|e| e.command.clone()).collect();
+    assert_eq!(commands, vec!["add \"trashed title\"", "del 2", "add \"kept\""]);
+
+    run(dir.path(), &["shell"], "trash --empty -y\n");
+    let mut contents = String::new();
+    File::open(dir.path().join("default.json")).unwrap().read_to_string(&mut contents).unwrap();
+    assert!(!contents.contains("trashed"));
+}
@@ -0,0 +1,153 @@
+extern crate theca;
+extern crate tempdir;
+
+use std::fs::File;
+use std::io::Write;
+
+use tempdir::TempDir;
+
+use theca::Profile;
+use theca::export::import_todotxt;
+use theca::item::{Item, Status};
+use theca::todotxt::{parse_line, to_line};
+
+#[test]
+fn test_parse_line() {
+    let task = parse_line("(A) 2015-01-20 Call Mom +Family @phone due:2015-01-25").unwrap();
+    assert!(!task.done);
+    assert_eq!(task.priority, Some('A'));
+    assert_eq!(task.created, Some("2015-01-20".to_string()));
+    assert_eq!(task.text, "Call Mom");
+    assert_eq!(task.tags(), vec!["Family", "@phone"]);
+    assert_eq!(task.due, Some("2015-01-25".to_string()));
+    assert_eq!(task.status(), Status::Urgent);
+
+    let task = parse_line("x 2015-01-22 2015-01-19 Pay rent +home pri:C").unwrap();
+    assert!(task.done);
+    assert_eq!(task.completed, Some("2015-01-22".to_string()));
+    assert_eq!(task.created, Some("2015-01-19".to_string()));
+    assert_eq!(task.text, "Pay rent");
+    assert_eq!(task.status(), Status::Started);
+
+    let task = parse_line("  email (A) about a+b @ key:value").unwrap();
+    assert_eq!(task.priority, None);
+    assert_eq!(task.created, None);
+    assert_eq!(task.text, "email (A) about a+b @ key:value");
+    assert_eq!(task.status(), Status::Blank);
+
+    assert!(parse_line("   ").is_none());
+}
+
+#[test]
+fn test_to_line() {
+    let mut note = Item {
+        id: 1,
+        uuid: "".to_string(),
+        title: "Deploy the site".to_string(),
+        status: Status::Urgent,
+        body: String::new(),
+        last_touched: "2015-01-22 19:43:24 -0800".to_string(),
+        tags: vec!["work".to_string(), "@desk".to_string(), "ops team".to_string()],
+        due: Some("2015-01-30 00:00:00 -0800".to_string()),
+        history: vec![],
+    };
+    assert_eq!(to_line(&note).unwrap(),
+               "(A) 2015-01-22 Deploy the site +work @desk +ops-team due:2015-01-30");
+    note.status = Status::Started;
+    note.tags = vec!["done".to_string()];
+    note.due = None;
+    assert_eq!(to_line(&note).unwrap(),
+               "x 2015-01-22 2015-01-22 Deploy the site pri:B");
+    note.status = Status::Blank;
+    note.tags.clear();
+    note.title = "x marks the spot".to_string();
+    let line = to_line(&note).unwrap();
+    assert_eq!(line, "2015-01-22 x marks the spot");
+    assert_eq!(parse_line(&line).unwrap().text, "x marks the spot");
+}
+
+#[test]
+fn test_body_and_uuid() {
+    let note = Item {
+        id: 1,
+        uuid: "1715d61b-f3c4-41d7-8fb2-1d52ea70db91".to_string(),
+        title: "Deploy".to_string(),
+        status: Status::Blank,
+        body: "100% done\n\tnext: the API".to_string(),
+        last_touched: "2015-01-22 19:43:24 -0800".to_string(),
+        tags: vec![],
+        due: None,
+        history: vec![],
+    };
+    let line = to_line(&note).unwrap();
+    assert_eq!(line,
+               "2015-01-22 Deploy uuid:1715d61b-f3c4-41d7-8fb2-1d52ea70db91 \
+                body:100%25%20done%0A%09next:%20the%20API");
+    let task = parse_line(&line).unwrap();
+    assert_eq!(task.text, "Deploy");
+    assert_eq!(task.uuid, Some(note.uuid.clone()));
+    assert_eq!(task.body, note.body);
+    assert_eq!(parse_line("a body:50%_off%").unwrap().body, "50%_off%");
+}
+
+fn profile() -> Profile {
+    Profile {
+        encrypted: false,
+        notes: vec![],
+        trash: vec![],
+        journal: vec![],
+    }
+}
+
+#[test]
+fn test_import_todotxt() {
+    let dir = TempDir::new("theca").unwrap();
+    let path = dir.path().join("todo.txt");
+    let mut f = File::create(&path).unwrap();
+    f.write_all(b"(B) 2015-01-20 Call Mom +Family due:2015-01-25\n\
+                  \n\
+                  x 2015-01-22 Pay rent\n\
+                  Buy milk @store body:skimmed%20please\n")
+     .unwrap();
+    let mut p = profile();
+    import_todotxt(&mut p, &path).unwrap();
+    assert_eq!(p.notes.len(), 3);
+    assert_eq!(p.notes[0].title, "Call Mom");
+    assert_eq!(p.notes[0].status, Status::Started);
+    assert_eq!(p.notes[0].last_touched, "2015-01-20 00:00:00 -0800");
+    assert_eq!(p.notes[0].due, Some("2015-01-25 00:00:00 -0800".to_string()));
+    // done tasks are kept, tagged done
+    assert_eq!(p.notes[1].title, "Pay rent");
+    assert_eq!(p.notes[1].tags, vec!["done"]);
+    assert_eq!(p.notes[2].tags, vec!["@store"]);
+    assert_eq!(p.notes[2].body, "skimmed please");
+    assert!(p.trash.is_empty());
+}
+
+#[test]
+fn test_todotxt_round_trip() {
+    let dir = TempDir::new("theca").unwrap();
+    let path = dir.path().join("todo.txt");
+    File::create(&path)
+        .unwrap()
+        .write_all(b"(A) 2015-01-20 Call Mom +Family\nx 2015-01-22 Pay rent body:by%20friday\n")
+        .unwrap();
+    let mut p = profile();
+    import_todotxt(&mut p, &path).unwrap();
+
+    // exporting and importing again matches the tasks to their notes by uuid
+    let exported: Vec<String> = p.notes.iter().map(|n| to_line(n).unwrap()).collect();
+    File::create(&path).unwrap().write_all(exported.join("\n").as_bytes()).unwrap();
+    let fields = |p: &Profile| {
+        p.notes
+         .iter()
+         .map(|n| (n.uuid.clone(), n.title.clone(), n.last_touched.clone(), n.history.len()))
+         .collect::<Vec<_>>()
+    };
+    let before = fields(&p);
+    import_todotxt(&mut p, &path).unwrap();
+    assert_eq!(fields(&p), before);
+    assert!(p.trash.is_empty());
+    assert_eq!(p.notes[1].tags, vec!["done"]);
+    assert_eq!(p.notes[1].body, "by friday");
+}
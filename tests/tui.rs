@@ -0,0 +1,60 @@
+extern crate theca;
+
+use theca::item::{Item, Status};
+use theca::tui::{matches, next_status, parse_keys, wrap, Key};
+
+#[test]
+fn test_parse_keys() {
+    assert_eq!(parse_keys(b"jk/"),
+               vec![Key::Char('j'), Key::Char('k'), Key::Char('/')]);
+    assert_eq!(parse_keys(b"\x1b[A\x1b[B\x1bOH\x1b[4~\x1b[5~\x1b[6~"),
+               vec![Key::Up, Key::Down, Key::Home, Key::End, Key::PageUp, Key::PageDown]);
+    assert_eq!(parse_keys(b"\x1b[D\x1b[C\x1b[3~"),
+               vec![Key::Left, Key::Right, Key::Delete]);
+    assert_eq!(parse_keys(b"\x1b\r\x7f\x03"),
+               vec![Key::Esc, Key::Enter, Key::Backspace, Key::Ctrl('c')]);
+    // unknown sequences are dropped
+    assert_eq!(parse_keys(b"\x1b[15~q"), vec![Key::Char('q')]);
+    assert_eq!(parse_keys("é".as_bytes()), vec![Key::Char('é')]);
+}
+
+#[test]
+fn test_wrap() {
+    assert_eq!(wrap("a short line", 20), vec!["a short line"]);
+    assert_eq!(wrap("wrap these words please", 10),
+               vec!["wrap these", "words", "please"]);
+    assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+    assert_eq!(wrap("one\n\ntwo", 10), vec!["one", "", "two"]);
+    assert!(wrap("", 10).is_empty());
+    // wide characters take two columns each
+    assert_eq!(wrap("日本語の ノート", 6), vec!["日本語", "の", "ノート"]);
+    assert_eq!(wrap("日本語", 3), vec!["日", "本", "語"]);
+    assert_eq!(wrap("e\u{301}e\u{301}e\u{301}", 2),
+               vec!["e\u{301}e\u{301}", "e\u{301}"]);
+}
+
+#[test]
+fn test_matches() {
+    let note = Item {
+        id: 1,
+        uuid: "".to_string(),
+        title: "Deploy the site".to_string(),
+        status: Status::Blank,
+        body: "check the CDN".to_string(),
+        last_touched: "2015-01-22 19:43:24 -0800".to_string(),
+        tags: vec!["work".to_string()],
+        due: None,
+        history: vec![],
+    };
+    assert!(matches(&note, "deploy"));
+    assert!(matches(&note, "cdn"));
+    assert!(matches(&note, "WORK"));
+    assert!(!matches(&note, "home"));
+}
+
+#[test]
+fn test_next_status() {
+    assert_eq!(next_status(Status::Blank), Status::Started);
+    assert_eq!(next_status(Status::Started), Status::Urgent);
+    assert_eq!(next_status(Status::Urgent), Status::Blank);
+}
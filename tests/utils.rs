@@ -1,7 +1,19 @@
 extern crate theca;
+extern crate time;
+extern crate tempdir;
 
-use theca::utils::{cmp_last_touched, format_field};
+use theca::BoolFlags;
+use theca::item::{Item, Status};
+use theca::utils::{cmp_last_touched, filter_notes, format_field, parse_date_input, profile_names,
+                   write_atomic, backup_path};
+use theca::profile::DATEFMT;
 use std::cmp::Ordering;
+use std::fs::{create_dir, read_dir, set_permissions, File};
+use std::io::{Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tempdir::TempDir;
+use time::{at, strftime, strptime, Tm};
 
 #[test]
 fn test_format_field() {
@@ -22,3 +34,128 @@ fn test_cmp_last_touched() {
     assert_eq!(cmp_last_touched(old, new).ok().unwrap(), Ordering::Less);
     assert_eq!(cmp_last_touched(new, old).ok().unwrap(), Ordering::Greater);
 }
+
+fn wednesday() -> Tm {
+    at(strptime("2026-10-14 12:00:00 +0000", DATEFMT).unwrap().to_timespec())
+}
+
+fn parsed_date(input: &str) -> String {
+    strftime("%F %T", &parse_date_input(input, &wednesday()).unwrap()).unwrap()
+}
+
+#[test]
+fn test_parse_date_input() {
+    assert_eq!(parsed_date("today"), "2026-10-14 00:00:00");
+    assert_eq!(parsed_date("Tomorrow"), "2026-10-15 00:00:00");
+    assert_eq!(parsed_date("+3d"), "2026-10-17 00:00:00");
+    assert_eq!(parsed_date("+2w"), "2026-10-28 00:00:00");
+    assert_eq!(parsed_date("-1d"), "2026-10-13 00:00:00");
+    assert_eq!(parsed_date("friday"), "2026-10-16 00:00:00");
+    assert_eq!(parsed_date("next wednesday"), "2026-10-21 00:00:00");
+    assert_eq!(parsed_date("2026-11-01"), "2026-11-01 00:00:00");
+    assert_eq!(parsed_date("2026-11-01 14:30"), "2026-11-01 14:30:00");
+
+    assert!(parse_date_input("+3x", &wednesday()).is_err());
+    assert!(parse_date_input("someday", &wednesday()).is_err());
+}
+
+fn read_file(path: &Path) -> String {
+    let mut contents = String::new();
+    File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+    contents
+}
+
+#[test]
+fn test_write_atomic() {
+    let dir = TempDir::new("theca").unwrap();
+    let path = dir.path().join("default.json");
+
+    write_atomic(&path, b"first", false).unwrap();
+    assert_eq!(read_file(&path), "first");
+
+    let mut permissions = path.metadata().unwrap().permissions();
+    permissions.set_mode(0o600);
+    set_permissions(&path, permissions).unwrap();
+
+    write_atomic(&path, b"second", false).unwrap();
+    assert_eq!(read_file(&path), "second");
+    assert_eq!(path.metadata().unwrap().permissions().mode() & 0o777, 0o600);
+    assert!(!backup_path(&path).exists());
+
+    // only the profile should be left, no temporary files
+    assert_eq!(read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_write_atomic_backup() {
+    let dir = TempDir::new("theca").unwrap();
+    let path = dir.path().join("default.json");
+
+    write_atomic(&path, b"first", true).unwrap();
+    assert!(!backup_path(&path).exists());
+
+    write_atomic(&path, b"second", true).unwrap();
+    assert_eq!(read_file(&path), "second");
+    assert_eq!(backup_path(&path), dir.path().join("default.json.bak"));
+    assert_eq!(read_file(&backup_path(&path)), "first");
+}
+
+#[test]
+fn test_profile_names() {
+    let dir = TempDir::new("theca").unwrap();
+    let write = |name: &str, contents: &[u8]| {
+        File::create(dir.path().join(name)).unwrap().write_all(contents).unwrap();
+    };
+    write("work.json", b"{\"encrypted\": false, \"notes\": []}");
+    write("default.json", b"{\"encrypted\": false, \"notes\": []}");
+    write("secret.json", &[0xff, 0xfe, 0x01]);
+    write("broken.json", b"{\"notes\": ");
+    write("default.json.bak", b"{\"encrypted\": false, \"notes\": []}");
+    write("README", b"not a profile");
+    create_dir(dir.path().join("notes")).unwrap();
+
+    assert_eq!(profile_names(dir.path()).unwrap(),
+               vec![("default".to_string(), false),
+                    ("secret".to_string(), true),
+                    ("work".to_string(), false)]);
+    assert!(profile_names(&dir.path().join("missing")).unwrap().is_empty());
+}
+
+#[test]
+fn test_filter_notes_datesort_keeps_ranking() {
+    let touched = |id: usize, last_touched: &str| {
+        Item {
+            id: id,
+            uuid: format!("1715d61b-f3c4-41d7-8fb2-{:012}", id),
+            title: "a".to_string(),
+            status: Status::Blank,
+            body: "".to_string(),
+            last_touched: last_touched.to_string(),
+            tags: vec![],
+            due: None,
+            history: vec![],
+        }
+    };
+    let ranked = vec![touched(1, "2015-01-22 19:43:24 -0800"),
+                      touched(2, "2014-01-22 19:43:24 -0800"),
+                      touched(3, "2016-01-22 19:43:24 -0800")];
+    let ids = |notes: &[Item]| notes.iter().map(|n| n.id).collect::<Vec<usize>>();
+
+    let mut notes = ranked.clone();
+    filter_notes(&mut notes,
+                 0,
+                 &BoolFlags { datesort: true, ..BoolFlags::default() },
+                 None,
+                 &[],
+                 None,
+                 None,
+                 None);
+    assert_eq!(ids(&notes), vec![2, 1, 3]);
+
+    for flags in &[BoolFlags { datesort: true, fuzzy: true, ..BoolFlags::default() },
+                   BoolFlags { datesort: true, full_text: true, ..BoolFlags::default() }] {
+        let mut notes = ranked.clone();
+        filter_notes(&mut notes, 2, flags, None, &[], None, None, None);
+        assert_eq!(ids(&notes), vec![1, 2]);
+    }
+}
@@ -13,7 +13,7 @@
 from jsonschema import validate as validate_schema
 import json
 from time import strptime
-from hashlib import sha256
+from hashlib import sha256, scrypt
 from passlib.utils.pbkdf2 import pbkdf2
 from Crypto.Cipher import AES
 import tempfile
@@ -46,7 +46,40 @@ STATUSES = ["", "Started", "Urgent"]
 DATEFMT = "%Y-%m-%d %H:%M:%S %z"
 SCHEMA_PATH = "docs/schema.json"
 
+HEADER_MAGIC = b"THECA"
+KDF_PBKDF2_SHA256 = 1
+KDF_SCRYPT = 2
+
+def decrypt_headered_profile(ciphertext, passphrase):
+    # magic, version, kdf, kdf params, salt, nonce, ciphertext, tag
+    passphrase = bytes(passphrase.encode("utf-8"))
+    if ciphertext[6] == KDF_SCRYPT:
+        log_n = ciphertext[7]
+        r = int.from_bytes(ciphertext[8:12], "big")
+        p = int.from_bytes(ciphertext[12:16], "big")
+        header_len = 16
+    elif ciphertext[6] == KDF_PBKDF2_SHA256:
+        rounds = int.from_bytes(ciphertext[7:11], "big")
+        header_len = 11
+    else:
+        raise AssertionError("unknown key derivation function %d" % ciphertext[6])
+    salt = ciphertext[header_len:header_len+16]
+    nonce = ciphertext[header_len+16:header_len+28]
+    header_len += 28
+    if ciphertext[6] == KDF_SCRYPT:
+        key = scrypt(passphrase, salt=salt, n=2**log_n, r=r, p=p, maxmem=256*r*2**log_n, dklen=32)
+    else:
+        key = pbkdf2(passphrase, salt, rounds, 32, "hmac-sha256")
+    decryptor = AES.new(key, AES.MODE_GCM, nonce=nonce)
+    decryptor.update(ciphertext[0:header_len])
+    try:
+        return decryptor.decrypt_and_verify(ciphertext[header_len:-16], ciphertext[-16:]).decode("utf-8")
+    except ValueError:
+        raise AssertionError("profile could not be decrypted")
+
 def decrypt_profile(ciphertext, passphrase):
+    if ciphertext.startswith(HEADER_MAGIC):
+        return decrypt_headered_profile(ciphertext, passphrase)
     key = pbkdf2(
         bytes(passphrase.encode("utf-8")),
         sha256(bytes(passphrase.encode("utf-8"))).hexdigest().encode("utf-8"),
@@ -108,6 +141,12 @@ def validate_profile_contents(profile):
         profile_ids = [n['id'] for n in profile['notes']]
         if len(profile_ids) != len(set(profile_ids)):
             raise AssertionError("there are duplicate IDs in 'notes'")
+        profile_uuids = [n['uuid'] for n in profile['notes'] if 'uuid' in n]
+        if len(profile_uuids) != len(set(profile_uuids)):
+            raise AssertionError("there are duplicate UUIDs in 'notes'")
+        trash_uuids = [t['note']['uuid'] for t in profile.get('trash', [])]
+        if set(profile_uuids) & set(trash_uuids):
+            raise AssertionError("there are notes in both 'notes' and 'trash'")
 
 def compare_notes(clean, dirty):
     try:
@@ -122,6 +161,8 @@ def compare_notes(clean, dirty):
 def compare_profile(clean, dirty):
     if not clean['encrypted'] == dirty['encrypted']: raise AssertionError()
     if not len(clean['notes']) == len(dirty['notes']): raise AssertionError()
+    if 'trash' in clean and not len(clean['trash']) == len(dirty.get('trash', [])):
+        raise AssertionError()
     for c, d in zip(clean['notes'], dirty['notes']):
         compare_notes(c, d)
 
@@ -183,6 +224,11 @@ def bench_harness(benches):
 def test_harness(tests, cond=False):
     TMPDIR = tempfile.mkdtemp()
     SCHEMA = read_json_file(SCHEMA_PATH)
+    # don't let the config file of whoever runs the tests change the output
+    CONFIG_DIR = tempfile.mkdtemp()
+    config_path = os.path.join(CONFIG_DIR, "config.toml")
+    open(config_path, "w").close()
+    os.environ["THECA_CONFIG"] = config_path
     failed = 0
 
     print("# {}\n#    {}".format(tests['title'], tests['desc']))
@@ -253,6 +299,7 @@ def test_harness(tests, cond=False):
                 shutil.rmtree(f_o_p)
 
     rmtree(TMPDIR)
+    rmtree(CONFIG_DIR)
     print("\n[passed: {}, failed {}]\n".format(len(tests['tests'])-failed, failed))
     return failed
 